@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use itertools::Itertools;
+use velodyne_lidar::{converter::ConverterN, iter::packet_iter_from_file, Config32, DataPacket};
+
+fn load_packets() -> Vec<DataPacket> {
+    packet_iter_from_file("tests/velodyne_vlp32.pcap")
+        .unwrap()
+        .map_ok(|packet| packet.try_into_data().ok())
+        .flatten_ok()
+        .try_collect()
+        .unwrap()
+}
+
+fn convert_benchmark(c: &mut Criterion) {
+    let packets = load_packets();
+    let config = Config32::new_vlp_32c_strongest();
+    let converter = ConverterN::<32, 1>::new(&config);
+
+    let mut group = c.benchmark_group("vlp32_firing_to_xyz");
+
+    group.bench_function("config", |b| {
+        b.iter(|| {
+            for packet in &packets {
+                for firing in packet.firing_block_iter_n::<32, 1>() {
+                    #[allow(deprecated)]
+                    black_box(firing.to_firing_xyz(&config));
+                }
+            }
+        })
+    });
+
+    group.bench_function("converter", |b| {
+        b.iter(|| {
+            for packet in &packets {
+                for firing in packet.firing_block_iter_n::<32, 1>() {
+                    black_box(converter.firing_block_to_xyz(&firing));
+                }
+            }
+        })
+    });
+
+    group.bench_function("packet_firing_xyz_iter", |b| {
+        b.iter(|| {
+            for packet in &packets {
+                for firing in packet.firing_xyz_iter_n::<32, 1>(&config) {
+                    black_box(firing);
+                }
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, convert_benchmark);
+criterion_main!(benches);
@@ -1,23 +1,46 @@
 //! Group consecutive elements.
 
 use crate::{
-    traits::AzimuthRange,
+    traits::{AzimuthRange, FiringLike},
     types::{
-        firing_block::{FiringBlockD16, FiringBlockD32, FiringBlockS16, FiringBlockS32},
-        firing_xyz::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-        format::FormatKind,
+        firing_block::FiringBlockN,
+        firing_xyz::FiringXyzN,
+        format::{with_layouts, FormatKind, LayoutFamily},
     },
 };
-use std::mem;
+use measurements::Angle;
+use std::{iter, marker::PhantomData, mem};
 
-/// A helper that groups consecutive elements into frames according to
-/// their azimuth ranges.
+pub use strategy::*;
+
+/// A helper that groups consecutive elements into frames.
+///
+/// Where frames are split is decided by the [BatchStrategy]. By
+/// default, a new frame starts when the azimuth wraps past 0°.
 #[derive(Debug, Clone)]
-pub struct Batcher<E>
-where
-    E: AzimuthRange,
-{
+pub struct Batcher<E, S = AzimuthWrap> {
     buffer: Vec<E>,
+    spare: Vec<Vec<E>>,
+    strategy: S,
+    seq: u64,
+    packet_index: u64,
+    buffer_packet: Option<u64>,
+    num_packets: usize,
+    sensor_id: Option<u32>,
+}
+
+/// A group of consecutive elements produced by a [Batcher].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Batch<E> {
+    /// The sequence number of the batch, starting from zero.
+    pub seq: u64,
+    /// Number of packets contributing elements to the batch. It is
+    /// counted only for elements pushed by [Batcher::push_packet].
+    pub num_packets: usize,
+    /// The sensor ID given by [Batcher::with_sensor_id].
+    pub sensor_id: Option<u32>,
+    /// The elements in the order they are pushed.
+    pub items: Vec<E>,
 }
 
 impl<E> Batcher<E>
@@ -28,21 +51,109 @@ where
     pub fn new() -> Self {
         Self::default()
     }
+}
+
+impl<E> Batcher<E, CutAngle>
+where
+    E: AzimuthRange,
+{
+    /// Create a new instance that splits frames at the given azimuth.
+    pub fn with_cut_angle(cut_angle: Angle) -> Self {
+        Self::with_strategy(CutAngle::new(cut_angle))
+    }
+}
+
+impl<E, S> Batcher<E, S> {
+    /// Create a new instance with a custom strategy.
+    pub fn with_strategy(strategy: S) -> Self {
+        Self {
+            buffer: vec![],
+            spare: vec![],
+            strategy,
+            seq: 0,
+            packet_index: 0,
+            buffer_packet: None,
+            num_packets: 0,
+            sensor_id: None,
+        }
+    }
+
+    /// Tags the batches with the ID of the sensor.
+    pub fn with_sensor_id(self, sensor_id: Option<u32>) -> Self {
+        Self { sensor_id, ..self }
+    }
+
+    /// Gets the sensor ID.
+    pub fn sensor_id(&self) -> Option<u32> {
+        self.sensor_id
+    }
+
+    /// Gets the batching strategy.
+    pub fn strategy(&self) -> &S {
+        &self.strategy
+    }
 
+    /// Takes a batch of buffered elements.
+    pub fn take(&mut self) -> Option<Vec<E>> {
+        Some(self.take_batch()?.items)
+    }
+
+    /// Takes a batch of buffered elements along with the bookkeeping
+    /// information.
+    pub fn take_batch(&mut self) -> Option<Batch<E>> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+
+        let batch = Batch {
+            seq: self.seq,
+            num_packets: self.num_packets,
+            sensor_id: self.sensor_id,
+            items: mem::replace(&mut self.buffer, self.spare.pop().unwrap_or_default()),
+        };
+        self.seq += 1;
+        self.buffer_packet = None;
+        self.num_packets = 0;
+        Some(batch)
+    }
+
+    /// Returns the storage of a consumed batch to the batcher.
+    ///
+    /// The vector is cleared and reused by later batches, so that a
+    /// caller that recycles every batch stops allocating once the
+    /// buffers are large enough.
+    pub fn recycle(&mut self, mut items: Vec<E>) {
+        items.clear();
+        self.spare.push(items);
+    }
+
+    fn append(&mut self, firing: E) {
+        if self.packet_index > 0 && self.buffer_packet != Some(self.packet_index) {
+            self.buffer_packet = Some(self.packet_index);
+            self.num_packets += 1;
+        }
+        self.buffer.push(firing);
+    }
+}
+
+impl<E, S> Batcher<E, S>
+where
+    S: BatchStrategy<E>,
+{
     /// Pushes one element and returns a batch if the pushed element
-    /// is back aronud.
+    /// starts a new batch.
     pub fn push_one(&mut self, firing: E) -> Option<Vec<E>> {
-        let buffer = &mut self.buffer;
-        let wrap =
-            matches!(buffer.last(), Some(prev) if prev.start_azimuth() > firing.start_azimuth());
-
-        if wrap {
-            let output = mem::replace(buffer, vec![firing]);
-            Some(output)
-        } else {
-            buffer.push(firing);
-            None
-        }
+        Some(self.push_one_batch(firing)?.items)
+    }
+
+    /// Pushes one element and returns a batch along with the
+    /// bookkeeping information if the pushed element starts a new
+    /// batch.
+    pub fn push_one_batch(&mut self, firing: E) -> Option<Batch<E>> {
+        let split = self.strategy.is_boundary(&self.buffer, &firing) && !self.buffer.is_empty();
+        let output = if split { self.take_batch() } else { None };
+        self.append(firing);
+        output
     }
 
     /// Pushes one element and returns an iterator of batches.
@@ -55,10 +166,17 @@ where
             .filter_map(|firing| self.push_one(firing))
     }
 
-    /// Takes a batch of buffered elements.
-    pub fn take(&mut self) -> Option<Vec<E>> {
-        let firings = mem::take(&mut self.buffer);
-        (!firings.is_empty()).then_some(firings)
+    /// Pushes the elements decoded from one packet and returns an
+    /// iterator of batches.
+    pub fn push_packet<'a, I>(&'a mut self, firings: I) -> impl Iterator<Item = Batch<E>> + 'a
+    where
+        I: IntoIterator<Item = E> + 'a,
+    {
+        self.packet_index += 1;
+        self.strategy.begin_packet();
+        firings
+            .into_iter()
+            .filter_map(|firing| self.push_one_batch(firing))
     }
 
     /// Converts an iterator of elements to an iterator of batches.
@@ -66,13 +184,15 @@ where
     where
         I: IntoIterator<Item = E>,
     {
-        itertools::unfold(Some((firings.into_iter(), self)), |state| {
-            if let Some((iter, conv)) = state {
+        let mut state = Some((firings.into_iter(), self));
+
+        iter::from_fn(move || {
+            if let Some((iter, conv)) = &mut state {
                 Some(if let Some(firing) = iter.next() {
                     conv.push_one(firing)
                 } else {
                     let output = conv.take();
-                    *state = None;
+                    state = None;
                     output
                 })
             } else {
@@ -83,31 +203,369 @@ where
     }
 }
 
-impl<E> Default for Batcher<E>
+impl<E, S> Default for Batcher<E, S>
 where
-    E: AzimuthRange,
+    S: Default,
 {
     fn default() -> Self {
-        Self { buffer: vec![] }
+        Self::with_strategy(S::default())
     }
 }
 
-pub type FiringBlockBatcherS16<'a> = Batcher<FiringBlockS16<'a>>;
-pub type FiringBlockBatcherS32<'a> = Batcher<FiringBlockS32<'a>>;
-pub type FiringBlockBatcherD16<'a> = Batcher<FiringBlockD16<'a>>;
-pub type FiringBlockBatcherD32<'a> = Batcher<FiringBlockD32<'a>>;
-
-pub type FiringBlockBatcher<'a> = FormatKind<
-    FiringBlockBatcherS16<'a>,
-    FiringBlockBatcherS32<'a>,
-    FiringBlockBatcherD16<'a>,
-    FiringBlockBatcherD32<'a>,
->;
-
-pub type FiringXyzBatcher =
-    FormatKind<FiringXyzBatcherS16, FiringXyzBatcherS32, FiringXyzBatcherD16, FiringXyzBatcherD32>;
-
-pub type FiringXyzBatcherS16 = Batcher<FiringXyzS16>;
-pub type FiringXyzBatcherS32 = Batcher<FiringXyzS32>;
-pub type FiringXyzBatcherD16 = Batcher<FiringXyzD16>;
-pub type FiringXyzBatcherD32 = Batcher<FiringXyzD32>;
+mod strategy {
+    use super::*;
+    use crate::utils::AngleExt as _;
+    use std::time::Duration;
+
+    /// Decides where consecutive elements are split into batches.
+    pub trait BatchStrategy<E> {
+        /// Returns true if `next` starts a new batch after the elements
+        /// of the current `batch`.
+        ///
+        /// It is called for every pushed element. The return value is
+        /// ignored if the batch is empty.
+        fn is_boundary(&mut self, batch: &[E], next: &E) -> bool;
+
+        /// Notifies that the following elements are decoded from a new
+        /// packet.
+        fn begin_packet(&mut self) {}
+    }
+
+    impl<E, S> BatchStrategy<E> for &mut S
+    where
+        S: BatchStrategy<E> + ?Sized,
+    {
+        fn is_boundary(&mut self, batch: &[E], next: &E) -> bool {
+            (**self).is_boundary(batch, next)
+        }
+
+        fn begin_packet(&mut self) {
+            (**self).begin_packet()
+        }
+    }
+
+    impl<E, S> BatchStrategy<E> for Box<S>
+    where
+        S: BatchStrategy<E> + ?Sized,
+    {
+        fn is_boundary(&mut self, batch: &[E], next: &E) -> bool {
+            (**self).is_boundary(batch, next)
+        }
+
+        fn begin_packet(&mut self) {
+            (**self).begin_packet()
+        }
+    }
+
+    /// Starts a new batch when the azimuth wraps past 0°.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    pub struct AzimuthWrap;
+
+    impl<E> BatchStrategy<E> for AzimuthWrap
+    where
+        E: AzimuthRange,
+    {
+        fn is_boundary(&mut self, batch: &[E], next: &E) -> bool {
+            matches!(batch.last(), Some(prev) if prev.start_azimuth() > next.start_azimuth())
+        }
+    }
+
+    /// Starts a new batch at the first element whose start azimuth
+    /// passes the cut angle.
+    ///
+    /// An element crossing the cut angle is assigned by its start
+    /// azimuth, that is, it stays in the batch where it starts.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct CutAngle {
+        angle: Angle,
+    }
+
+    impl CutAngle {
+        pub fn new(angle: Angle) -> Self {
+            Self { angle }
+        }
+
+        pub fn angle(&self) -> Angle {
+            self.angle
+        }
+    }
+
+    impl<E> BatchStrategy<E> for CutAngle
+    where
+        E: AzimuthRange,
+    {
+        fn is_boundary(&mut self, batch: &[E], next: &E) -> bool {
+            let Some(prev) = batch.last() else {
+                return false;
+            };
+            let prev_azimuth = (prev.start_azimuth() - self.angle).wrap_to_2pi();
+            let azimuth = (next.start_azimuth() - self.angle).wrap_to_2pi();
+            prev_azimuth > azimuth
+        }
+    }
+
+    /// Splits elements into fixed-duration slices.
+    ///
+    /// The slices are aligned to multiples of the period on the `toh`
+    /// clock. An element is assigned by its start time. A new slice
+    /// starts when the `toh` wraps around the hour.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct TimeWindow {
+        period: Duration,
+    }
+
+    impl TimeWindow {
+        pub fn new(period: Duration) -> Self {
+            assert!(!period.is_zero(), "period must be non-zero");
+            Self { period }
+        }
+
+        pub fn period(&self) -> Duration {
+            self.period
+        }
+
+        fn slot(&self, toh: Duration) -> u128 {
+            toh.as_nanos() / self.period.as_nanos()
+        }
+    }
+
+    impl<E> BatchStrategy<E> for TimeWindow
+    where
+        E: FiringLike,
+    {
+        fn is_boundary(&mut self, batch: &[E], next: &E) -> bool {
+            matches!(
+                batch.last(),
+                Some(prev) if self.slot(prev.start_toh()) != self.slot(next.start_toh())
+            )
+        }
+    }
+
+    /// Splits elements into batches of a fixed number of elements.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FiringCount {
+        count: usize,
+    }
+
+    impl FiringCount {
+        pub fn new(count: usize) -> Self {
+            assert!(count > 0, "count must be positive");
+            Self { count }
+        }
+
+        pub fn count(&self) -> usize {
+            self.count
+        }
+    }
+
+    impl<E> BatchStrategy<E> for FiringCount {
+        fn is_boundary(&mut self, batch: &[E], _next: &E) -> bool {
+            batch.len() >= self.count
+        }
+    }
+
+    /// Splits elements into batches of the elements from a fixed number
+    /// of packets.
+    ///
+    /// It relies on packet notifications, that is, the elements must be
+    /// pushed by [Batcher::push_packet].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct PacketCount {
+        count: usize,
+        num_packets: usize,
+        packet_started: bool,
+    }
+
+    impl PacketCount {
+        pub fn new(count: usize) -> Self {
+            assert!(count > 0, "count must be positive");
+            Self {
+                count,
+                num_packets: 0,
+                packet_started: false,
+            }
+        }
+
+        pub fn count(&self) -> usize {
+            self.count
+        }
+    }
+
+    impl<E> BatchStrategy<E> for PacketCount {
+        fn is_boundary(&mut self, batch: &[E], _next: &E) -> bool {
+            if !mem::take(&mut self.packet_started) {
+                return false;
+            }
+
+            if batch.is_empty() {
+                self.num_packets = 1;
+                false
+            } else if self.num_packets >= self.count {
+                self.num_packets = 1;
+                true
+            } else {
+                self.num_packets += 1;
+                false
+            }
+        }
+
+        fn begin_packet(&mut self) {
+            self.packet_started = true;
+        }
+    }
+}
+
+/// Selects the [Batcher] of [FiringBlockN] in a [FormatKind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FiringBlockBatcherFamily<'a>(PhantomData<&'a ()>);
+
+impl<'a> LayoutFamily for FiringBlockBatcherFamily<'a> {
+    type Of<const LINES: usize, const RETURNS: usize> = Batcher<FiringBlockN<'a, LINES, RETURNS>>;
+}
+
+pub type FiringBlockBatcher<'a> = FormatKind<FiringBlockBatcherFamily<'a>>;
+
+/// Selects the [Batcher] of [FiringXyzN] in a [FormatKind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FiringXyzBatcherFamily;
+
+impl LayoutFamily for FiringXyzBatcherFamily {
+    type Of<const LINES: usize, const RETURNS: usize> = Batcher<FiringXyzN<LINES, RETURNS>>;
+}
+
+pub type FiringXyzBatcher = FormatKind<FiringXyzBatcherFamily>;
+
+macro_rules! declare_batch_strategy_kind {
+    ($(($variant:ident, $lines:literal, $returns:literal)),*) => {
+        /// A strategy that batches the elements of the family in every
+        /// supported layout, which is required to batch runtime kinds.
+        pub trait BatchStrategyKind<F: LayoutFamily>:
+            $(BatchStrategy<F::Of<$lines, $returns>> +)* Sized
+        {
+        }
+
+        impl<F, S> BatchStrategyKind<F> for S
+        where
+            F: LayoutFamily,
+            S: $(BatchStrategy<F::Of<$lines, $returns>> +)* Sized,
+        {
+        }
+    };
+}
+
+with_layouts!(declare_batch_strategy_kind);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ops::Range, time::Duration};
+
+    struct Element(Range<Angle>);
+
+    impl AzimuthRange for Element {
+        fn azimuth_range(&self) -> Range<Angle> {
+            self.0.clone()
+        }
+    }
+
+    struct Stamp(Duration);
+
+    impl FiringLike for Stamp {
+        type Point<'a> = ();
+
+        fn start_toh(&self) -> Duration {
+            self.0
+        }
+
+        fn num_points(&self) -> usize {
+            0
+        }
+
+        fn point_at(&self, _index: usize) -> Option<Self::Point<'_>> {
+            None
+        }
+    }
+
+    #[test]
+    fn batcher_cut_angle_test() {
+        let elements = (0..720).step_by(40).map(|degrees| {
+            let start = Angle::from_degrees((degrees % 360) as f64);
+            let end = Angle::from_degrees((degrees % 360 + 40) as f64);
+            Element(start..end)
+        });
+
+        let batcher = Batcher::with_cut_angle(Angle::from_degrees(180.0));
+        let starts: Vec<Vec<_>> = batcher
+            .with_iter(elements)
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|elem| elem.start_azimuth().as_degrees().round() as i64)
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(
+            starts,
+            [
+                vec![0, 40, 80, 120, 160],
+                vec![200, 240, 280, 320, 0, 40, 80, 120, 160],
+                vec![200, 240, 280, 320],
+            ]
+        );
+    }
+
+    #[test]
+    fn batcher_time_window_test() {
+        // every 30 ms across the hour boundary of the toh
+        let elements = (0..8).map(|index| {
+            let millis = (3_599_900 + index * 30) % 3_600_000;
+            Stamp(Duration::from_millis(millis))
+        });
+
+        let batcher = Batcher::with_strategy(TimeWindow::new(Duration::from_millis(50)));
+        let tohs: Vec<Vec<_>> = batcher
+            .with_iter(elements)
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|elem| elem.start_toh().as_millis() as u64)
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(
+            tohs,
+            [
+                vec![3_599_900, 3_599_930],
+                vec![3_599_960, 3_599_990],
+                vec![20],
+                vec![50, 80],
+                vec![110],
+            ]
+        );
+    }
+
+    #[test]
+    fn batcher_packet_count_test() {
+        let mut batcher = Batcher::with_strategy(PacketCount::new(2));
+        let packet = || {
+            [0.0, 1.0, 2.0].map(|degrees| {
+                let start = Angle::from_degrees(degrees);
+                Element(start..start)
+            })
+        };
+
+        let mut batches = vec![];
+        for _ in 0..5 {
+            batches.extend(batcher.push_packet(packet()));
+        }
+        batches.extend(batcher.take_batch());
+
+        let summary: Vec<_> = batches
+            .iter()
+            .map(|batch| (batch.seq, batch.num_packets, batch.items.len()))
+            .collect();
+        assert_eq!(summary, [(0, 2, 6), (1, 2, 6), (2, 1, 3)]);
+    }
+}
@@ -1,13 +1,23 @@
 //! Defines a set of Velodyne LiDAR configurations.
 
-use crate::{consts, packet::ReturnMode};
-use eyre::Result;
+use crate::{
+    consts,
+    packet::ReturnMode,
+    timing::TimingModel,
+    transform::{CoordinateConvention, Transform},
+    utils::AngleExt as _,
+};
+use eyre::{ensure, Result};
 use itertools::izip;
 use measurements::{Angle, Length};
+use std::{f64::consts::PI, fs, ops::Range, path::Path};
+use velodyne_params::VelodyneParams;
 
 pub use config_::*;
 mod config_ {
-    use crate::types::format::{Format, FormatKind};
+    use crate::types::format::{
+        dispatch_format, Format, FormatKind, LayoutFamily, LayoutN, SupportedLayout,
+    };
 
     use super::*;
 
@@ -19,468 +29,410 @@ mod config_ {
         pub return_mode: ReturnMode,
         pub distance_resolution: Length,
         pub lasers: Vec<Beam>,
+        /// The sensor-to-vehicle transform applied to every point, if
+        /// any. It is applied after the coordinate convention, so it is
+        /// expressed in the axes of the convention.
+        pub transform: Option<Transform>,
+        /// The axes of the output coordinates.
+        pub convention: CoordinateConvention,
+        /// The validity of returns and the handling of invalid ones.
+        pub return_filter: ReturnFilter,
+        /// The azimuth sectors to decode. All azimuths are decoded if
+        /// empty.
+        pub azimuth_windows: Vec<AzimuthWindow>,
+        /// The firing timing of the sensor model. If missing, all lasers
+        /// are timed at the start of the firing.
+        pub timing: Option<TimingModel>,
+        /// The caller-supplied ID of the sensor, which is copied to the
+        /// frame headers.
+        pub sensor_id: Option<u32>,
     }
 
+    /// Config type for a fixed number of lasers.
     #[derive(Debug, Clone)]
-    pub struct Config16 {
+    pub struct ConfigN<const SIZE: usize> {
         pub return_mode: ReturnMode,
         pub distance_resolution: Length,
-        pub lasers: [Beam; 16],
+        pub lasers: [Beam; SIZE],
+        /// The sensor-to-vehicle transform applied to every point, if
+        /// any. It is applied after the coordinate convention, so it is
+        /// expressed in the axes of the convention.
+        pub transform: Option<Transform>,
+        /// The axes of the output coordinates.
+        pub convention: CoordinateConvention,
+        /// The validity of returns and the handling of invalid ones.
+        pub return_filter: ReturnFilter,
+        /// The azimuth sectors to decode. All azimuths are decoded if
+        /// empty.
+        pub azimuth_windows: Vec<AzimuthWindow>,
+        /// The firing timing of the sensor model. If missing, all lasers
+        /// are timed at the start of the firing.
+        pub timing: Option<TimingModel>,
+        /// The caller-supplied ID of the sensor, which is copied to the
+        /// frame headers.
+        pub sensor_id: Option<u32>,
     }
 
-    #[derive(Debug, Clone)]
-    pub struct Config32 {
-        pub return_mode: ReturnMode,
-        pub distance_resolution: Length,
-        pub lasers: [Beam; 32],
-    }
-
-    // impls
-
-    impl Config {
-        pub fn try_into_kind(
-            self,
-        ) -> Result<FormatKind<Config16, Config32, Config16, Config32>, Self> {
-            use FormatKind as K;
-            use ReturnMode::*;
-
-            let Self {
-                return_mode,
-                distance_resolution,
-                lasers,
-            } = self;
-
-            Ok(match (return_mode, lasers.len()) {
-                (Strongest | Last, 16) => K::from_s16(Config16 {
-                    return_mode,
-                    distance_resolution,
-                    lasers: lasers.try_into().unwrap(),
-                }),
-                (Dual, 16) => K::from_d16(Config16 {
-                    return_mode,
-                    distance_resolution,
-                    lasers: lasers.try_into().unwrap(),
-                }),
-                (Strongest | Last, 32) => K::from_s32(Config32 {
-                    return_mode,
-                    distance_resolution,
-                    lasers: lasers.try_into().unwrap(),
-                }),
-                (Dual, 32) => K::from_d32(Config32 {
-                    return_mode,
-                    distance_resolution,
-                    lasers: lasers.try_into().unwrap(),
-                }),
-                _ => {
-                    return Err(Self {
-                        return_mode,
-                        distance_resolution,
-                        lasers,
-                    })
-                }
-            })
-        }
-
-        pub fn try_format(&self) -> Option<Format> {
-            Format::try_new(self.lasers.len(), self.return_mode)
-        }
-
-        pub fn format(&self) -> Format {
-            self.try_format().unwrap()
-        }
-
-        pub fn new_vlp_16_last() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_16();
-
-            Self {
-                return_mode: ReturnMode::Last,
-                lasers,
-                distance_resolution,
-            }
-        }
-
-        pub fn new_vlp_16_strongest() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_16();
-
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers,
-                distance_resolution,
-            }
-        }
-
-        pub fn new_vlp_16_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_16();
-
-            Self {
-                return_mode: ReturnMode::Dual,
-                lasers,
-                distance_resolution,
-            }
-        }
-
-        pub fn new_puck_hires_last() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_hires();
-
-            Self {
-                return_mode: ReturnMode::Last,
-                lasers,
-                distance_resolution,
-            }
-        }
-
-        pub fn new_puck_hires_strongest() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_hires();
+    pub type Config16 = ConfigN<16>;
+    pub type Config32 = ConfigN<32>;
 
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers,
-                distance_resolution,
-            }
-        }
-
-        pub fn new_puck_hires_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_hires();
-
-            Self {
-                return_mode: ReturnMode::Dual,
-                lasers,
-                distance_resolution,
-            }
-        }
+    /// Selects the [ConfigN] of the number of lasers in a [FormatKind].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct ConfigFamily;
 
-        pub fn new_puck_lite_last() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_lite();
-
-            Self {
-                return_mode: ReturnMode::Last,
-                lasers,
-                distance_resolution,
-            }
-        }
-
-        pub fn new_puck_lite_strongest() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_lite();
-
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers,
-                distance_resolution,
-            }
-        }
+    impl LayoutFamily for ConfigFamily {
+        type Of<const LINES: usize, const RETURNS: usize> = ConfigN<LINES>;
+    }
 
-        pub fn new_puck_lite_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_lite();
+    pub type ConfigKind = FormatKind<ConfigFamily>;
 
-            Self {
-                return_mode: ReturnMode::Dual,
-                lasers,
-                distance_resolution,
-            }
-        }
+    // impls
 
-        pub fn new_vlp_32c_last() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_32c();
+    /// Implements the builder methods shared by the configuration types.
+    macro_rules! impl_builders {
+        ([$($generics:tt)*] $ty:ty) => {
+            impl<$($generics)*> $ty {
+                /// Sets the sensor-to-vehicle transform applied to every point.
+                pub fn with_transform(self, transform: Transform) -> Self {
+                    Self {
+                        transform: Some(transform),
+                        ..self
+                    }
+                }
 
-            Self {
-                return_mode: ReturnMode::Last,
-                lasers,
-                distance_resolution,
-            }
-        }
+                /// Sets the axes of the output coordinates.
+                pub fn with_convention(self, convention: CoordinateConvention) -> Self {
+                    Self { convention, ..self }
+                }
 
-        pub fn new_vlp_32c_strongest() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_32c();
+                /// Sets the validity of returns and the handling of invalid ones.
+                pub fn with_return_filter(self, return_filter: ReturnFilter) -> Self {
+                    Self {
+                        return_filter,
+                        ..self
+                    }
+                }
 
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers,
-                distance_resolution,
-            }
-        }
+                /// Restricts decoding to the azimuth sectors.
+                pub fn with_azimuth_windows(self, azimuth_windows: Vec<AzimuthWindow>) -> Self {
+                    Self {
+                        azimuth_windows,
+                        ..self
+                    }
+                }
 
-        pub fn new_vlp_32c_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_32c();
+                /// Sets the firing timing of the sensor model.
+                pub fn with_timing(self, timing: TimingModel) -> Self {
+                    Self {
+                        timing: Some(timing),
+                        ..self
+                    }
+                }
 
-            Self {
-                return_mode: ReturnMode::Dual,
-                lasers,
-                distance_resolution,
+                /// Sets the ID of the sensor copied to the frame headers.
+                pub fn with_sensor_id(self, sensor_id: u32) -> Self {
+                    Self {
+                        sensor_id: Some(sensor_id),
+                        ..self
+                    }
+                }
             }
-        }
+        };
     }
 
-    impl Config16 {
-        pub fn format(&self) -> Format {
-            use Format::*;
-            use ReturnMode::*;
+    impl_builders!([] Config);
+    impl_builders!([const SIZE: usize] ConfigN<SIZE>);
 
-            match self.return_mode {
-                Strongest | Last => Single16,
-                Dual => Dual16,
+    /// Declares the preset constructors of a sensor model, one per
+    /// return mode.
+    macro_rules! declare_presets {
+        ($beams:ident, $timing:ident, $last:ident, $strongest:ident, $dual:ident) => {
+            pub fn $last() -> Self {
+                Self::preset(BeamConfig::$beams(), ReturnMode::Last, TimingModel::$timing)
             }
-        }
-
-        pub fn new_vlp_16_last() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_16();
 
-            Self {
-                return_mode: ReturnMode::Last,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
+            pub fn $strongest() -> Self {
+                Self::preset(
+                    BeamConfig::$beams(),
+                    ReturnMode::Strongest,
+                    TimingModel::$timing,
+                )
             }
-        }
 
-        pub fn new_vlp_16_strongest() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_16();
-
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
+            pub fn $dual() -> Self {
+                Self::preset(BeamConfig::$beams(), ReturnMode::Dual, TimingModel::$timing)
             }
-        }
-
-        pub fn new_vlp_16_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_16();
-
-            Self {
-                return_mode: ReturnMode::Dual,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
-            }
-        }
-
-        pub fn new_puck_hires_last() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_hires();
+        };
+    }
 
-            Self {
-                return_mode: ReturnMode::Last,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
+    impl Config {
+        /// Converts to the configuration of the format, or gives back
+        /// the configuration if the format is not supported.
+        // The error hands back the input rather than describing a failure.
+        #[allow(clippy::result_large_err)]
+        pub fn try_into_kind(self) -> Result<ConfigKind, Self> {
+            let Some(format) = self.try_format() else {
+                return Err(self);
+            };
+
+            // the format agrees with the number of lasers
+            Ok(dispatch_format!(format, <LINES, RETURNS> => {
+                FormatKind::from_layout::<LINES, RETURNS>(self.try_into().unwrap())
+            }))
+        }
+
+        /// Converts to the configuration of the layout, or gives back
+        /// the configuration if it has another layout.
+        #[allow(clippy::result_large_err)]
+        pub fn try_into_layout<const LINES: usize, const RETURNS: usize>(
+            self,
+        ) -> Result<ConfigN<LINES>, Self>
+        where
+            LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        {
+            if self.try_format() != Some(LayoutN::<LINES, RETURNS>::FORMAT) {
+                return Err(self);
             }
+            self.try_into()
         }
 
-        pub fn new_puck_hires_strongest() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_hires();
-
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
-            }
+        /// Gets the format of the configuration, or `None` if no format
+        /// has as many lasers.
+        pub fn try_format(&self) -> Option<Format> {
+            Format::try_new(self.lasers.len(), self.return_mode)
         }
 
-        pub fn new_puck_hires_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_hires();
+        /// Gets the ring of each laser, indexed by laser ID.
+        ///
+        /// Rings are ordered by elevation, where ring 0 is the lowest
+        /// beam.
+        pub fn rings(&self) -> Vec<usize> {
+            rings_of(&self.lasers)
+        }
+
+        /// Builds the configuration from calibration parameters.
+        ///
+        /// The mount transform in the parameters, if any, is carried
+        /// over. The timing is derived from the number of lasers if it
+        /// determines the timing. It is left unset for 32 lasers, which
+        /// may be a VLP-32C or an HDL-32E, and can be given by
+        /// [Self::with_timing].
+        ///
+        /// It fails if no format decodes the number of lasers in the
+        /// return mode.
+        pub fn from_params(params: VelodyneParams, return_mode: ReturnMode) -> Result<Self> {
+            let VelodyneParams {
+                distance_resolution,
+                mut lasers,
+                transform,
+            } = params;
+
+            ensure!(
+                Format::try_new(lasers.len(), return_mode).is_some(),
+                "{} lasers in {:?} return mode are not supported",
+                lasers.len(),
+                return_mode
+            );
+
+            lasers.sort_by_key(|laser| laser.laser_id);
+            ensure!(
+                lasers
+                    .iter()
+                    .enumerate()
+                    .all(|(index, laser)| laser.laser_id as usize == index),
+                "laser IDs must range from 0 to {}",
+                lasers.len().saturating_sub(1)
+            );
+
+            let lasers = lasers
+                .into_iter()
+                .map(|laser| Beam {
+                    elevation: Angle::from_radians(laser.vert_correction),
+                    // the rotational correction is subtracted from the
+                    // azimuth
+                    azimuth_offset: Angle::from_radians(-laser.rot_correction),
+                    vertical_offset: Length::from_meters(laser.vert_offset_correction),
+                    horizontal_offset: Length::from_meters(laser.horiz_offset_correction),
+                })
+                .collect::<Vec<_>>();
+
+            // the parameters do not tell the model, and the 32-laser
+            // models differ in timing
+            let timing = match lasers.len() {
+                16 => Some(TimingModel::Vlp16),
+                128 => Some(TimingModel::Vls128),
+                _ => None,
+            };
+
+            let beams = BeamConfig {
+                lasers,
+                distance_resolution,
+            };
 
-            Self {
-                return_mode: ReturnMode::Dual,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
-            }
+            Ok(Self {
+                transform: transform.map(Transform::from),
+                timing,
+                ..Self::base(beams, return_mode)
+            })
         }
 
-        pub fn new_puck_lite_last() -> Self {
+        /// Loads the configuration from a calibration YAML text.
+        pub fn from_yaml_str(text: &str, return_mode: ReturnMode) -> Result<Self> {
+            let params: VelodyneParams = serde_yaml::from_str(text)?;
+            Self::from_params(params, return_mode)
+        }
+
+        /// Loads the configuration from a calibration YAML file.
+        pub fn from_yaml_file<P>(path: P, return_mode: ReturnMode) -> Result<Self>
+        where
+            P: AsRef<Path>,
+        {
+            let text = fs::read_to_string(path)?;
+            Self::from_yaml_str(&text, return_mode)
+        }
+
+        declare_presets!(
+            new_vlp_16,
+            Vlp16,
+            new_vlp_16_last,
+            new_vlp_16_strongest,
+            new_vlp_16_dual
+        );
+        declare_presets!(
+            new_puck_hires,
+            PuckHiRes,
+            new_puck_hires_last,
+            new_puck_hires_strongest,
+            new_puck_hires_dual
+        );
+        declare_presets!(
+            new_puck_lite,
+            Vlp16,
+            new_puck_lite_last,
+            new_puck_lite_strongest,
+            new_puck_lite_dual
+        );
+        declare_presets!(
+            new_vlp_32c,
+            Vlp32c,
+            new_vlp_32c_last,
+            new_vlp_32c_strongest,
+            new_vlp_32c_dual
+        );
+
+        /// Creates the configuration with the default settings.
+        fn base(beams: BeamConfig, return_mode: ReturnMode) -> Self {
             let BeamConfig {
                 lasers,
                 distance_resolution,
-            } = BeamConfig::new_puck_lite();
+            } = beams;
 
             Self {
-                return_mode: ReturnMode::Last,
-                lasers: lasers.try_into().unwrap(),
+                return_mode,
                 distance_resolution,
-            }
-        }
-
-        pub fn new_puck_lite_strongest() -> Self {
-            let BeamConfig {
                 lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_lite();
-
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
+                transform: None,
+                convention: CoordinateConvention::Native,
+                return_filter: ReturnFilter::default(),
+                azimuth_windows: vec![],
+                timing: None,
+                sensor_id: None,
             }
         }
 
-        pub fn new_puck_lite_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_puck_lite();
-
+        fn preset(beams: BeamConfig, return_mode: ReturnMode, timing: TimingModel) -> Self {
             Self {
-                return_mode: ReturnMode::Dual,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
+                timing: Some(timing),
+                ..Self::base(beams, return_mode)
             }
         }
     }
 
-    impl Config32 {
-        pub fn format(&self) -> Format {
-            use Format::*;
-            use ReturnMode::*;
-
-            match self.return_mode {
-                Strongest | Last => Single32,
-                Dual => Dual32,
-            }
+    impl<const SIZE: usize> ConfigN<SIZE> {
+        /// Gets the ring of each laser, indexed by laser ID.
+        ///
+        /// Rings are ordered by elevation, where ring 0 is the lowest
+        /// beam.
+        pub fn rings(&self) -> [usize; SIZE] {
+            rings_of_array(&self.lasers)
         }
 
-        pub fn new_vlp_32c_last() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_32c();
-
-            Self {
-                return_mode: ReturnMode::Last,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
-            }
+        /// Gets the format of the configuration, or `None` if no format
+        /// has `SIZE` lasers.
+        pub fn try_format(&self) -> Option<Format> {
+            Format::try_new(SIZE, self.return_mode)
         }
 
-        pub fn new_vlp_32c_strongest() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_32c();
-
-            Self {
-                return_mode: ReturnMode::Strongest,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
-            }
+        fn preset(beams: BeamConfig, return_mode: ReturnMode, timing: TimingModel) -> Self {
+            // presets are only declared for their number of lasers
+            Config::preset(beams, return_mode, timing)
+                .try_into()
+                .unwrap()
         }
+    }
 
-        pub fn new_vlp_32c_dual() -> Self {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = BeamConfig::new_vlp_32c();
-
-            Self {
-                return_mode: ReturnMode::Dual,
-                lasers: lasers.try_into().unwrap(),
-                distance_resolution,
-            }
-        }
+    impl Config16 {
+        declare_presets!(
+            new_vlp_16,
+            Vlp16,
+            new_vlp_16_last,
+            new_vlp_16_strongest,
+            new_vlp_16_dual
+        );
+        declare_presets!(
+            new_puck_hires,
+            PuckHiRes,
+            new_puck_hires_last,
+            new_puck_hires_strongest,
+            new_puck_hires_dual
+        );
+        declare_presets!(
+            new_puck_lite,
+            Vlp16,
+            new_puck_lite_last,
+            new_puck_lite_strongest,
+            new_puck_lite_dual
+        );
     }
 
-    impl From<Config16> for Config {
-        fn from(from: Config16) -> Self {
-            let Config16 {
-                return_mode,
-                lasers,
-                distance_resolution,
-            } = from;
-            Self {
-                return_mode,
-                lasers: lasers.into(),
-                distance_resolution,
-            }
-        }
+    impl Config32 {
+        declare_presets!(
+            new_vlp_32c,
+            Vlp32c,
+            new_vlp_32c_last,
+            new_vlp_32c_strongest,
+            new_vlp_32c_dual
+        );
     }
 
-    impl From<Config32> for Config {
-        fn from(from: Config32) -> Self {
-            let Config32 {
+    impl<const SIZE: usize> From<ConfigN<SIZE>> for Config {
+        fn from(from: ConfigN<SIZE>) -> Self {
+            let ConfigN {
                 return_mode,
                 lasers,
                 distance_resolution,
+                transform,
+                convention,
+                return_filter,
+                azimuth_windows,
+                timing,
+                sensor_id,
             } = from;
             Self {
                 return_mode,
                 lasers: lasers.into(),
                 distance_resolution,
+                transform,
+                convention,
+                return_filter,
+                azimuth_windows,
+                timing,
+                sensor_id,
             }
         }
     }
 
-    impl TryFrom<Config> for Config16 {
-        type Error = Config;
-
-        fn try_from(from: Config) -> Result<Self, Self::Error> {
-            let Config {
-                return_mode,
-                lasers,
-                distance_resolution,
-            } = from;
-
-            let lasers = lasers.try_into().map_err(|lasers| Config {
-                return_mode,
-                lasers,
-                distance_resolution,
-            })?;
-
-            Ok(Self {
-                return_mode,
-                lasers,
-                distance_resolution,
-            })
-        }
-    }
-
-    impl TryFrom<Config> for Config32 {
+    impl<const SIZE: usize> TryFrom<Config> for ConfigN<SIZE> {
         type Error = Config;
 
         fn try_from(from: Config) -> Result<Self, Self::Error> {
@@ -488,18 +440,36 @@ mod config_ {
                 return_mode,
                 lasers,
                 distance_resolution,
+                transform,
+                convention,
+                return_filter,
+                azimuth_windows,
+                timing,
+                sensor_id,
             } = from;
 
             let lasers = lasers.try_into().map_err(|lasers| Config {
                 return_mode,
                 lasers,
                 distance_resolution,
+                transform,
+                convention,
+                return_filter,
+                azimuth_windows: azimuth_windows.clone(),
+                timing,
+                sensor_id,
             })?;
 
             Ok(Self {
                 return_mode,
                 lasers,
                 distance_resolution,
+                transform,
+                convention,
+                return_filter,
+                azimuth_windows,
+                timing,
+                sensor_id,
             })
         }
     }
@@ -515,16 +485,129 @@ mod params {
         pub distance_resolution: Length,
     }
 
-    #[derive(Debug, Clone)]
-    pub(super) struct BeamConfig16 {
-        pub lasers: [Beam; 16],
-        pub distance_resolution: Length,
+    /// Ranks the beams by elevation. Beams of equal elevation are ranked
+    /// by laser ID.
+    pub(crate) fn rings_of(lasers: &[Beam]) -> Vec<usize> {
+        let mut order = vec![0; lasers.len()];
+        let mut rings = vec![0; lasers.len()];
+        rank_by_elevation(lasers, &mut order, &mut rings);
+        rings
     }
 
-    #[derive(Debug, Clone)]
-    pub(super) struct BeamConfig32 {
-        pub lasers: [Beam; 32],
-        pub distance_resolution: Length,
+    /// The allocation-free version of [rings_of].
+    pub(crate) fn rings_of_array<const SIZE: usize>(lasers: &[Beam; SIZE]) -> [usize; SIZE] {
+        let mut order = [0; SIZE];
+        let mut rings = [0; SIZE];
+        rank_by_elevation(lasers, &mut order, &mut rings);
+        rings
+    }
+
+    fn rank_by_elevation(lasers: &[Beam], order: &mut [usize], rings: &mut [usize]) {
+        for (laser_id, slot) in order.iter_mut().enumerate() {
+            *slot = laser_id;
+        }
+        order.sort_unstable_by(|&lhs, &rhs| {
+            let lhs_elevation = lasers[lhs].elevation.as_radians();
+            let rhs_elevation = lasers[rhs].elevation.as_radians();
+            lhs_elevation.total_cmp(&rhs_elevation).then(lhs.cmp(&rhs))
+        });
+
+        for (ring, &laser_id) in order.iter().enumerate() {
+            rings[laser_id] = ring;
+        }
+    }
+
+    /// Decides which returns are valid and how invalid ones are output.
+    ///
+    /// A zero distance means no return and is always invalid.
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub struct ReturnFilter {
+        /// Returns nearer than this are invalid.
+        pub min_range: Option<Length>,
+        /// Returns farther than this are invalid.
+        pub max_range: Option<Length>,
+        pub policy: InvalidPolicy,
+    }
+
+    impl ReturnFilter {
+        /// Creates a filter that rejects returns outside the range.
+        pub fn with_range(min_range: Length, max_range: Length) -> Self {
+            Self {
+                min_range: Some(min_range),
+                max_range: Some(max_range),
+                ..Self::default()
+            }
+        }
+
+        pub fn with_policy(self, policy: InvalidPolicy) -> Self {
+            Self { policy, ..self }
+        }
+
+        /// Checks if a return at the distance is valid.
+        pub fn is_valid(&self, distance: Length) -> bool {
+            distance > Length::default()
+                && self.min_range.is_none_or(|min| distance >= min)
+                && self.max_range.is_none_or(|max| distance <= max)
+        }
+    }
+
+    /// The output of invalid returns.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub enum InvalidPolicy {
+        /// Outputs invalid returns like valid ones.
+        #[default]
+        Keep,
+        /// Sets the coordinates of invalid returns to NaN.
+        Nan,
+        /// Omits invalid points from sparse outputs, such as point lists
+        /// and point clouds. Dense outputs, such as firings and frames,
+        /// set their coordinates to NaN.
+        Skip,
+    }
+
+    /// A clockwise azimuth sector of `span` from `start`.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct AzimuthWindow {
+        pub start: Angle,
+        /// The clockwise angle from the start to the end, at most a
+        /// full turn.
+        pub span: Angle,
+    }
+
+    impl AzimuthWindow {
+        /// Creates the sector from `start` to `end`.
+        ///
+        /// The sector wraps around zero azimuth if `end` is less than
+        /// `start`. It is a full turn if `end` is at least a turn past
+        /// `start`, such as from 0 to 360 degrees.
+        pub fn new(start: Angle, end: Angle) -> Self {
+            let span = end - start;
+            let span = if span.as_radians() >= PI * 2.0 {
+                Angle::from_radians(PI * 2.0)
+            } else {
+                span.wrap_to_2pi()
+            };
+
+            Self {
+                start: start.wrap_to_2pi(),
+                span,
+            }
+        }
+
+        pub fn end(&self) -> Angle {
+            (self.start + self.span).wrap_to_2pi()
+        }
+
+        pub fn contains(&self, azimuth: Angle) -> bool {
+            (azimuth - self.start).wrap_to_2pi() <= self.span
+        }
+
+        /// Checks if the clockwise azimuth range intersects the sector.
+        pub fn overlaps(&self, range: &Range<Angle>) -> bool {
+            let offset = (range.start - self.start).wrap_to_2pi();
+            let len = range.end - range.start;
+            offset <= self.span || (offset + len).as_radians() >= PI * 2.0
+        }
     }
 
     #[derive(Debug, Clone)]
@@ -628,72 +711,81 @@ mod params {
             }
         }
     }
+}
 
-    impl From<BeamConfig16> for BeamConfig {
-        fn from(from: BeamConfig16) -> Self {
-            let BeamConfig16 {
-                lasers,
-                distance_resolution,
-            } = from;
-            Self {
-                lasers: lasers.into(),
-                distance_resolution,
-            }
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    impl From<BeamConfig32> for BeamConfig {
-        fn from(from: BeamConfig32) -> Self {
-            let BeamConfig32 {
-                lasers,
-                distance_resolution,
-            } = from;
-            Self {
-                lasers: lasers.into(),
-                distance_resolution,
-            }
+    #[test]
+    fn config_rings_test() {
+        let rings = Config::new_vlp_16_strongest().rings();
+        for (row, laser_id) in consts::vlp_16::ELEVAION_INDEX.into_iter().enumerate() {
+            assert_eq!(rings[laser_id], 15 - row);
         }
-    }
 
-    impl TryFrom<BeamConfig> for BeamConfig16 {
-        type Error = BeamConfig;
-
-        fn try_from(from: BeamConfig) -> Result<Self, Self::Error> {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = from;
-
-            let lasers = lasers.try_into().map_err(|lasers| BeamConfig {
-                lasers,
-                distance_resolution,
-            })?;
-
-            Ok(Self {
-                lasers,
-                distance_resolution,
-            })
+        let rings = Config32::new_vlp_32c_strongest().rings();
+        for (row, laser_id) in consts::vlp_32c::ELEVAION_INDEX.into_iter().enumerate() {
+            assert_eq!(rings[laser_id], 31 - row);
         }
     }
 
-    impl TryFrom<BeamConfig> for BeamConfig32 {
-        type Error = BeamConfig;
-
-        fn try_from(from: BeamConfig) -> Result<Self, Self::Error> {
-            let BeamConfig {
-                lasers,
-                distance_resolution,
-            } = from;
+    #[test]
+    fn config_from_yaml_test() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../velodyne-params/params/VeloView-VLP-32C.yaml"
+        );
+        let text = fs::read_to_string(path).unwrap();
+        let transform = "transform: {translation: [1.0, 0.0, 2.0], rotation: {roll: 0.0, pitch: 0.0, yaw: 0.0}}";
+        let config =
+            Config::from_yaml_str(&format!("{text}\n{transform}"), ReturnMode::Strongest).unwrap();
+        let expect = Config::new_vlp_32c_strongest();
+
+        assert_eq!(config.lasers.len(), 32);
+        for (laser, expect) in config.lasers.iter().zip(&expect.lasers) {
+            assert!((laser.elevation.as_degrees() - expect.elevation.as_degrees()).abs() < 1e-6);
+            assert!(
+                (laser.azimuth_offset.as_degrees() - expect.azimuth_offset.as_degrees()).abs()
+                    < 1e-6
+            );
+        }
+
+        assert_eq!(config.timing, None);
+
+        let transform = config.transform.unwrap();
+        let [x, y, z] = transform
+            .apply([Length::from_meters(1.0); 3])
+            .map(|v| v.as_meters());
+        assert_eq!([x, y, z], [2.0, 1.0, 3.0]);
+    }
 
-            let lasers = lasers.try_into().map_err(|lasers| BeamConfig {
-                lasers,
-                distance_resolution,
-            })?;
+    #[test]
+    fn config_from_params_unsupported_test() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../velodyne-params/params/VeloView-VLP-32C.yaml"
+        );
+        let text = fs::read_to_string(path).unwrap();
+        let mut params: VelodyneParams = serde_yaml::from_str(&text).unwrap();
+        params.lasers.truncate(8);
+
+        let err = Config::from_params(params, ReturnMode::Strongest).unwrap_err();
+        assert!(err.to_string().contains("not supported"), "{err}");
+    }
 
-            Ok(Self {
-                lasers,
-                distance_resolution,
-            })
-        }
+    #[test]
+    fn azimuth_window_test() {
+        let window = AzimuthWindow::new(Angle::from_degrees(350.0), Angle::from_degrees(10.0));
+        assert!((window.span.as_degrees() - 20.0).abs() < 1e-9);
+        assert!(window.contains(Angle::from_degrees(5.0)));
+        assert!(!window.contains(Angle::from_degrees(180.0)));
+
+        // a full turn does not collapse to an empty sector
+        let full = AzimuthWindow::new(Angle::default(), Angle::from_degrees(360.0));
+        assert!((full.span.as_degrees() - 360.0).abs() < 1e-9);
+        assert!((0..360).all(|degrees| full.contains(Angle::from_degrees(degrees as f64))));
+        let range = Angle::from_degrees(90.0)..Angle::from_degrees(90.2);
+        assert!(full.overlaps(&range));
     }
 }
@@ -1,8 +1,10 @@
 //! Useful constants for Velodyne data structures and calculations.
 
+use core::time::Duration;
+#[cfg(feature = "std")]
 use measurements::Length;
+#[cfg(feature = "std")]
 use once_cell::sync::Lazy;
-use std::time::Duration;
 
 /// Default UDP data port used by Velodyne LiDARs.
 pub const DATA_PORT: u16 = 2368;
@@ -31,10 +33,12 @@ pub const FIRING_PERIOD: Duration = Duration::from_nanos((FIRING_PERIOD_MICROS *
 // VLP-16 parameters
 
 pub mod vlp_16 {
+    #[cfg(feature = "std")]
     use super::*;
 
     pub const DISTANCE_RESOLUTION_MILLIS: f64 = 2.0;
 
+    #[cfg(feature = "std")]
     pub static DISTANCE_RESOLUTION: Lazy<Length> =
         Lazy::new(|| Length::from_millimeters(DISTANCE_RESOLUTION_MILLIS));
 
@@ -64,10 +68,12 @@ pub mod vlp_16 {
 // Puck-Lite parameters
 
 pub mod puck_lite {
+    #[cfg(feature = "std")]
     use super::*;
 
     pub const DISTANCE_RESOLUTION_MILLIS: f64 = 2.0;
 
+    #[cfg(feature = "std")]
     pub static DISTANCE_RESOLUTION: Lazy<Length> =
         Lazy::new(|| Length::from_millimeters(DISTANCE_RESOLUTION_MILLIS));
 
@@ -92,10 +98,12 @@ pub mod puck_lite {
 
 // Puck Hi-Res parameters
 pub mod puck_hires {
+    #[cfg(feature = "std")]
     use super::*;
 
     pub const DISTANCE_RESOLUTION_MILLIS: f64 = 2.0;
 
+    #[cfg(feature = "std")]
     pub static DISTANCE_RESOLUTION: Lazy<Length> =
         Lazy::new(|| Length::from_millimeters(DISTANCE_RESOLUTION_MILLIS));
 
@@ -121,10 +129,12 @@ pub mod puck_hires {
 // VLP-32C parameters
 
 pub mod vlp_32c {
+    #[cfg(feature = "std")]
     use super::*;
 
     pub const DISTANCE_RESOLUTION_MILLIS: f64 = 4.0;
 
+    #[cfg(feature = "std")]
     pub static DISTANCE_RESOLUTION: Lazy<Length> =
         Lazy::new(|| Length::from_millimeters(DISTANCE_RESOLUTION_MILLIS));
 
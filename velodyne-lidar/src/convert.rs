@@ -1,316 +1,133 @@
 use crate::{
-    config::Beam,
-    consts::{CHANNEL_PERIOD, FIRING_PERIOD},
+    converter::{PreparedBeam, PreparedBeams},
+    packet::Channel,
     types::{
-        channel_array::ChannelArrayDRef,
-        firing_block::{FiringBlockD16, FiringBlockD32, FiringBlockS16, FiringBlockS32},
-        firing_xyz::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-        measurements::{Measurement, MeasurementDual},
-        point::{PointD, PointS},
+        firing_polar::{FiringPolarN, PointPolarN},
+        firing_xyz::FiringXyzN,
+        point::PointN,
     },
     utils::{AngleExt as _, DurationExt as _},
-    Config16, Config32,
 };
-use itertools::izip;
 use measurements::{Angle, Length};
-use std::iter;
-
-pub fn firing_block_to_xyz_s16(firing: &FiringBlockS16, beams: &Config16) -> FiringXyzS16 {
-    let Config16 {
-        ref lasers,
-        distance_resolution,
-        ..
-    } = *beams;
-    let FiringBlockS16 {
-        toh: firing_toh,
-        ref azimuth_range,
-        channels,
-        ..
-    } = *firing;
-
-    let channel_tohs = iter::successors(Some(firing_toh), |&prev| Some(prev + CHANNEL_PERIOD));
-
-    let points: Vec<_> = izip!(0.., channel_tohs, channels, lasers)
-        .map(move |(laser_id, channel_toh, channel, laser)| -> PointS {
-            let ratio = (channel_toh - firing_toh).div_duration(FIRING_PERIOD);
-            let Beam {
-                elevation,
-                azimuth_offset,
-                vertical_offset,
-                horizontal_offset,
-            } = *laser;
-
-            // clockwise angle with origin points to front of sensor
-            let azimuth = {
-                let azimuth = azimuth_range.start
-                    + ((azimuth_range.end - azimuth_range.start) * ratio)
-                    + azimuth_offset;
-                azimuth.wrap_to_2pi()
-            };
-            let distance = distance_resolution * channel.distance as f64;
-            let xyz = spherical_to_xyz(
-                distance,
-                elevation,
-                azimuth,
-                vertical_offset,
-                horizontal_offset,
-            );
-
-            PointS {
-                laser_id,
-                toh: channel_toh,
-                azimuth,
-                measurement: Measurement {
-                    distance,
-                    intensity: channel.intensity,
-                    xyz,
-                },
-            }
-        })
-        .collect();
-    let points: [_; 16] = points.try_into().unwrap_or_else(|_| unreachable!());
-
-    FiringXyzS16 {
-        toh: firing_toh,
-        azimuth_range: azimuth_range.clone(),
-        points,
-    }
+use std::{array, ops::Range, time::Duration};
+
+/// Computes the time and the azimuth of a laser in a firing.
+fn beam_time_azimuth(
+    firing_toh: Duration,
+    azimuth_range: &Range<Angle>,
+    firing_period: Duration,
+    beam: &PreparedBeam,
+) -> (Duration, Angle) {
+    let channel_toh = firing_toh + beam.time_offset;
+    let ratio = (channel_toh - firing_toh).div_duration(firing_period);
+
+    // clockwise angle with origin points to front of sensor
+    let azimuth = {
+        let azimuth = azimuth_range.start
+            + ((azimuth_range.end - azimuth_range.start) * ratio)
+            + beam.azimuth_offset;
+        azimuth.wrap_to_2pi()
+    };
+
+    (channel_toh, azimuth)
 }
 
-pub fn firing_block_to_xyz_s32(firing: &FiringBlockS32, beams: &Config32) -> FiringXyzS32 {
-    let Config32 {
-        ref lasers,
-        distance_resolution,
-        ..
-    } = *beams;
-    let FiringBlockS32 {
-        toh: firing_toh,
-        ref azimuth_range,
-        channels,
-        ..
-    } = *firing;
-
-    let channel_tohs = iter::successors(Some(firing_toh), |&prev| Some(prev + CHANNEL_PERIOD))
-        .flat_map(|toh| [toh, toh]);
-
-    let points: Vec<_> = izip!(0.., channel_tohs, channels, lasers)
-        .map(move |(laser_id, channel_toh, channel, laser)| {
-            // let timestamp = lower_timestamp + CHANNEL_PERIOD.mul_f64((channel_idx / 2) as f64);
-
-            let ratio = (channel_toh - firing_toh).div_duration(FIRING_PERIOD);
-            let Beam {
-                elevation,
-                azimuth_offset,
-                vertical_offset,
-                horizontal_offset,
-            } = *laser;
-
-            // clockwise angle with origin points to front of sensor
-            let azimuth = {
-                let azimuth = azimuth_range.start
-                    + ((azimuth_range.end - azimuth_range.start) * ratio)
-                    + azimuth_offset;
-                azimuth.wrap_to_2pi()
-            };
-            let distance = distance_resolution * channel.distance as f64;
-            let xyz = spherical_to_xyz(
-                distance,
-                elevation,
-                azimuth,
-                vertical_offset,
-                horizontal_offset,
-            );
-
-            PointS {
-                laser_id,
-                toh: channel_toh,
-                azimuth,
-                measurement: Measurement {
-                    distance,
-                    intensity: channel.intensity,
-                    xyz,
-                },
-            }
-        })
-        .collect();
-
-    let points = points.try_into().unwrap_or_else(|_| unreachable!());
-
-    FiringXyzS32 {
+/// Converts a firing, whose channels are given by the return and the
+/// laser ID, to points.
+pub(crate) fn firing_to_xyz<'c, const LINES: usize, const RETURNS: usize>(
+    firing_toh: Duration,
+    azimuth_range: &Range<Angle>,
+    channel: impl Fn(usize, usize) -> &'c Channel,
+    prepared: &PreparedBeams<LINES>,
+) -> FiringXyzN<LINES, RETURNS> {
+    let PreparedBeams { ref beams, .. } = *prepared;
+
+    let points = array::from_fn(|laser_id| -> PointN<RETURNS> {
+        let beam = &beams[laser_id];
+        let (channel_toh, azimuth) =
+            beam_time_azimuth(firing_toh, azimuth_range, prepared.firing_period, beam);
+
+        PointN {
+            laser_id,
+            ring: beam.ring,
+            toh: channel_toh,
+            azimuth,
+            measurements: array::from_fn(|return_index| {
+                prepared.channel_to_measurement(beam, channel(return_index, laser_id), azimuth)
+            }),
+        }
+    });
+
+    FiringXyzN {
         toh: firing_toh,
         azimuth_range: azimuth_range.clone(),
         points,
     }
 }
 
-pub fn firing_block_to_xyz_d16(firing: &FiringBlockD16, beams: &Config16) -> FiringXyzD16 {
-    let Config16 {
-        ref lasers,
-        distance_resolution,
-        ..
-    } = *beams;
-    let FiringBlockD16 {
-        toh: firing_toh,
-        ref azimuth_range,
-        channels:
-            ChannelArrayDRef {
-                strongest: channels_strongest,
-                last: channels_last,
-            },
-        ..
-    }: FiringBlockD16<'_> = *firing;
-
-    let channel_tohs = iter::successors(Some(firing_toh), |&prev| Some(prev + CHANNEL_PERIOD));
-
-    let points: Vec<_> = izip!(0.., channel_tohs, channels_strongest, channels_last, lasers)
-        .map(
-            move |(laser_id, channel_toh, channel_strongest, channel_last, laser)| -> PointD {
-                let ratio = (channel_toh - firing_toh).div_duration(FIRING_PERIOD);
-                let Beam {
-                    elevation,
-                    azimuth_offset,
-                    vertical_offset,
-                    horizontal_offset,
-                } = *laser;
-
-                // clockwise angle with origin points to front of sensor
-                let azimuth = {
-                    let azimuth = azimuth_range.start
-                        + ((azimuth_range.end - azimuth_range.start) * ratio)
-                        + azimuth_offset;
-                    azimuth.wrap_to_2pi()
-                };
-                let distance_strongest = distance_resolution * channel_strongest.distance as f64;
-                let distance_last = distance_resolution * channel_last.distance as f64;
-
-                let xyz_strongest = spherical_to_xyz(
-                    distance_strongest,
-                    elevation,
-                    azimuth,
-                    vertical_offset,
-                    horizontal_offset,
-                );
-                let xyz_last = spherical_to_xyz(
-                    distance_last,
-                    elevation,
-                    azimuth,
-                    vertical_offset,
-                    horizontal_offset,
-                );
-
-                PointD {
-                    laser_id,
-                    toh: channel_toh,
-                    azimuth,
-                    measurements: MeasurementDual {
-                        strongest: Measurement {
-                            distance: distance_strongest,
-                            intensity: channel_strongest.intensity,
-                            xyz: xyz_strongest,
-                        },
-                        last: Measurement {
-                            distance: distance_last,
-                            intensity: channel_last.intensity,
-                            xyz: xyz_last,
-                        },
-                    },
-                }
-            },
-        )
-        .collect();
-    let points: [_; 16] = points.try_into().unwrap_or_else(|_| unreachable!());
-
-    FiringXyzD16 {
+/// Converts a firing, whose channels are given by the return and the
+/// laser ID, to polar coordinates.
+pub(crate) fn firing_to_polar<'c, const LINES: usize, const RETURNS: usize>(
+    firing_toh: Duration,
+    azimuth_range: &Range<Angle>,
+    channel: impl Fn(usize, usize) -> &'c Channel,
+    prepared: &PreparedBeams<LINES>,
+) -> FiringPolarN<LINES, RETURNS> {
+    let PreparedBeams { ref beams, .. } = *prepared;
+
+    let points = array::from_fn(|laser_id| -> PointPolarN<RETURNS> {
+        let beam = &beams[laser_id];
+        let (channel_toh, azimuth) =
+            beam_time_azimuth(firing_toh, azimuth_range, prepared.firing_period, beam);
+
+        PointPolarN {
+            laser_id,
+            ring: beam.ring,
+            toh: channel_toh,
+            azimuth,
+            elevation: beam.elevation,
+            measurements: array::from_fn(|return_index| {
+                prepared.channel_to_polar(channel(return_index, laser_id), azimuth)
+            }),
+        }
+    });
+
+    FiringPolarN {
         toh: firing_toh,
         azimuth_range: azimuth_range.clone(),
         points,
     }
 }
 
-pub fn firing_block_to_xyz_d32(firing: &FiringBlockD32, beams: &Config32) -> FiringXyzD32 {
-    let Config32 {
-        ref lasers,
-        distance_resolution,
-        ..
-    } = *beams;
-    let FiringBlockD32 {
-        toh: firing_toh,
-        ref azimuth_range,
-        channels:
-            ChannelArrayDRef {
-                strongest: channels_strongest,
-                last: channels_last,
-            },
-        ..
-    } = *firing;
-
-    let channel_tohs = iter::successors(Some(firing_toh), |&prev| Some(prev + CHANNEL_PERIOD))
-        .flat_map(|toh| [toh, toh]);
-
-    let points: Vec<_> = izip!(0.., channel_tohs, channels_strongest, channels_last, lasers)
-        .map(
-            move |(laser_id, channel_toh, channel_strongest, channel_last, laser)| {
-                // let timestamp = lower_timestamp + CHANNEL_PERIOD.mul_f64((channel_idx / 2) as f64);
-
-                let ratio = (channel_toh - firing_toh).div_duration(FIRING_PERIOD);
-                let Beam {
-                    elevation,
-                    azimuth_offset,
-                    vertical_offset,
-                    horizontal_offset,
-                } = *laser;
-
-                // clockwise angle with origin points to front of sensor
-                let azimuth = {
-                    let azimuth = azimuth_range.start
-                        + ((azimuth_range.end - azimuth_range.start) * ratio)
-                        + azimuth_offset;
-                    azimuth.wrap_to_2pi()
-                };
-                let distance_strongest = distance_resolution * channel_strongest.distance as f64;
-                let distance_last = distance_resolution * channel_last.distance as f64;
-
-                let xyz_strongest = spherical_to_xyz(
-                    distance_strongest,
-                    elevation,
-                    azimuth,
-                    vertical_offset,
-                    horizontal_offset,
-                );
-                let xyz_last = spherical_to_xyz(
-                    distance_last,
-                    elevation,
-                    azimuth,
-                    vertical_offset,
-                    horizontal_offset,
-                );
-
-                PointD {
-                    laser_id,
-                    toh: channel_toh,
-                    azimuth,
-                    measurements: MeasurementDual {
-                        strongest: Measurement {
-                            distance: distance_strongest,
-                            intensity: channel_strongest.intensity,
-                            xyz: xyz_strongest,
-                        },
-                        last: Measurement {
-                            distance: distance_last,
-                            intensity: channel_last.intensity,
-                            xyz: xyz_last,
-                        },
-                    },
-                }
-            },
-        )
-        .collect();
-
-    let points: [_; 32] = points.try_into().unwrap_or_else(|_| unreachable!());
-
-    FiringXyzD32 {
-        toh: firing_toh,
-        azimuth_range: azimuth_range.clone(),
+pub(crate) fn polar_to_xyz<const LINES: usize, const RETURNS: usize>(
+    firing: &FiringPolarN<LINES, RETURNS>,
+    prepared: &PreparedBeams<LINES>,
+) -> FiringXyzN<LINES, RETURNS> {
+    let points = array::from_fn(|index| -> PointN<RETURNS> {
+        let PointPolarN {
+            laser_id,
+            ring,
+            toh,
+            azimuth,
+            ref measurements,
+            ..
+        } = firing.points[index];
+        let beam = &prepared.beams[laser_id];
+
+        PointN {
+            laser_id,
+            ring,
+            toh,
+            azimuth,
+            measurements: array::from_fn(|return_index| {
+                prepared.polar_to_measurement(beam, &measurements[return_index], azimuth)
+            }),
+        }
+    });
+
+    FiringXyzN {
+        toh: firing.toh,
+        azimuth_range: firing.azimuth_range.clone(),
         points,
     }
 }
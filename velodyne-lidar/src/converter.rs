@@ -0,0 +1,922 @@
+//! Prepared point converters with cached beam trigonometry.
+
+use crate::{
+    batcher::{Batch, BatchStrategy, Batcher},
+    config::{rings_of_array, AzimuthWindow, Beam, InvalidPolicy, ReturnFilter},
+    consts::{AZIMUTH_COUNT_PER_REV, FIRING_PERIOD},
+    convert::{firing_to_polar, firing_to_xyz, polar_to_xyz},
+    packet::{Channel, DataPacket, ReturnMode},
+    timing::TimingModel,
+    transform::{CoordinateConvention, Transform},
+    types::{
+        firing_block::{FiringBlock, FiringBlockN},
+        firing_polar::{FiringPolarN, PolarMeasurement},
+        firing_raw::FiringRawN,
+        firing_xyz::{FiringXyz, FiringXyzN},
+        format::{dispatch, Format, FormatKind, LayoutFamily, LayoutN, SupportedLayout},
+        measurements::Measurement,
+        point::PointN,
+        point_cloud::PointCloud,
+    },
+    utils::AngleExt as _,
+    Config, ConfigN,
+};
+use eyre::{ensure, format_err, Result};
+use measurements::{Angle, Length};
+use once_cell::sync::Lazy;
+use std::{array, f64::consts::PI, ops::Range, time::Duration};
+
+pub use crate::convert::spherical_to_xyz;
+
+/// Number of distinct azimuth encoder values in a revolution.
+const NUM_AZIMUTH_TICKS: usize = AZIMUTH_COUNT_PER_REV - 1;
+
+/// The azimuth trigonometry shared by all converters.
+static AZIMUTH_TABLE: Lazy<AzimuthTable> = Lazy::new(AzimuthTable::new);
+
+/// Sine and cosine of every azimuth encoder value.
+///
+/// An azimuth hits the table only if it is bitwise equal to the
+/// encoder value, so a lookup gives the same result as computing the
+/// trigonometry.
+#[derive(Clone, PartialEq)]
+pub struct AzimuthTable {
+    radians: Vec<f64>,
+    sin_cos: Vec<[f64; 2]>,
+}
+
+impl AzimuthTable {
+    pub fn new() -> Self {
+        // same as the block azimuth of a packet
+        let radians: Vec<f64> = (0..NUM_AZIMUTH_TICKS)
+            .map(|count| 2.0 * PI * count as f64 / NUM_AZIMUTH_TICKS as f64)
+            .collect();
+        let sin_cos = radians
+            .iter()
+            .map(|radians| [radians.sin(), radians.cos()])
+            .collect();
+
+        Self { radians, sin_cos }
+    }
+
+    /// Gets the sine and cosine of an azimuth if it is an encoder
+    /// value.
+    pub fn get(&self, azimuth: Angle) -> Option<[f64; 2]> {
+        let radians = azimuth.as_radians();
+        let index = (radians / (2.0 * PI) * NUM_AZIMUTH_TICKS as f64).round();
+        if !(0.0..NUM_AZIMUTH_TICKS as f64).contains(&index) {
+            return None;
+        }
+        let index = index as usize;
+        (self.radians[index].to_bits() == radians.to_bits()).then(|| self.sin_cos[index])
+    }
+
+    /// Gets the sine and cosine of an azimuth from the table, or
+    /// computes them if it is not an encoder value.
+    pub fn sin_cos(&self, azimuth: Angle) -> [f64; 2] {
+        self.get(azimuth)
+            .unwrap_or_else(|| [azimuth.sin(), azimuth.cos()])
+    }
+}
+
+impl Default for AzimuthTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for AzimuthTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AzimuthTable")
+            .field("num_ticks", &self.radians.len())
+            .finish()
+    }
+}
+
+/// The per-beam parameters with precomputed elevation trigonometry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparedBeam {
+    pub ring: usize,
+    /// Time of the laser since the start of the firing. It is zero
+    /// unless the timing of the sensor model is known.
+    pub time_offset: Duration,
+    pub elevation: Angle,
+    pub elevation_sin: f64,
+    pub elevation_cos: f64,
+    pub azimuth_offset: Angle,
+    pub vertical_offset: Length,
+    pub horizontal_offset: Length,
+}
+
+/// Beam parameters prepared for point conversion.
+///
+/// The elevation trigonometry is cached per beam. The azimuth
+/// trigonometry is looked up per encoder tick in a table shared by all
+/// converters, and computed for azimuths between ticks.
+#[derive(Debug, Clone)]
+pub struct PreparedBeams<const SIZE: usize> {
+    pub return_mode: ReturnMode,
+    pub distance_resolution: Length,
+    pub beams: [PreparedBeam; SIZE],
+    /// The transform applied to every point after the convention, if
+    /// any.
+    pub transform: Option<Transform>,
+    /// The axes of the output coordinates.
+    pub convention: CoordinateConvention,
+    /// The validity of returns and the handling of invalid ones.
+    pub return_filter: ReturnFilter,
+    /// The azimuth sectors to decode. All azimuths are decoded if
+    /// empty.
+    pub azimuth_windows: Vec<AzimuthWindow>,
+    /// Duration of a firing sequence, over which the azimuth is
+    /// interpolated.
+    pub firing_period: Duration,
+}
+
+impl<const SIZE: usize> PreparedBeams<SIZE> {
+    pub fn new(
+        return_mode: ReturnMode,
+        distance_resolution: Length,
+        lasers: &[Beam; SIZE],
+    ) -> Self {
+        let rings = rings_of_array(lasers);
+        let beams = array::from_fn(|laser_id| {
+            let Beam {
+                elevation,
+                azimuth_offset,
+                vertical_offset,
+                horizontal_offset,
+            } = lasers[laser_id];
+
+            PreparedBeam {
+                ring: rings[laser_id],
+                time_offset: Duration::ZERO,
+                elevation,
+                elevation_sin: elevation.sin(),
+                elevation_cos: elevation.cos(),
+                azimuth_offset,
+                vertical_offset,
+                horizontal_offset,
+            }
+        });
+
+        Self {
+            return_mode,
+            distance_resolution,
+            beams,
+            transform: None,
+            convention: CoordinateConvention::Native,
+            return_filter: ReturnFilter::default(),
+            azimuth_windows: vec![],
+            firing_period: FIRING_PERIOD,
+        }
+    }
+
+    /// Uses the firing timing of the sensor model. Without it, all
+    /// lasers are timed at the start of the firing.
+    pub fn with_timing(mut self, timing: TimingModel) -> Self {
+        self.firing_period = timing.firing_period();
+        for (laser_id, beam) in self.beams.iter_mut().enumerate() {
+            beam.time_offset = timing.laser_offset(laser_id);
+        }
+        self
+    }
+
+    /// Converts a channel of the beam to a measurement, applying the
+    /// return filter.
+    pub(crate) fn channel_to_measurement(
+        &self,
+        beam: &PreparedBeam,
+        channel: &Channel,
+        azimuth: Angle,
+    ) -> Measurement {
+        let polar = self.channel_to_polar(channel, azimuth);
+        self.polar_to_measurement(beam, &polar, azimuth)
+    }
+
+    /// Converts a channel to a measurement without Cartesian
+    /// coordinates, applying the return filter.
+    pub(crate) fn channel_to_polar(&self, channel: &Channel, azimuth: Angle) -> PolarMeasurement {
+        let distance = self.distance_resolution * channel.distance as f64;
+        let valid =
+            channel.distance != 0 && self.return_filter.is_valid(distance) && self.in_fov(azimuth);
+
+        PolarMeasurement {
+            distance,
+            intensity: channel.intensity,
+            valid,
+        }
+    }
+
+    /// Projects a polar measurement of the beam to Cartesian
+    /// coordinates.
+    pub(crate) fn polar_to_measurement(
+        &self,
+        beam: &PreparedBeam,
+        polar: &PolarMeasurement,
+        azimuth: Angle,
+    ) -> Measurement {
+        let PolarMeasurement {
+            distance,
+            intensity,
+            valid,
+        } = *polar;
+        let xyz = if valid || self.return_filter.policy == InvalidPolicy::Keep {
+            self.beam_to_xyz(beam, distance, azimuth)
+        } else {
+            [Length::from_meters(f64::NAN); 3]
+        };
+
+        Measurement {
+            distance,
+            intensity,
+            xyz,
+            valid,
+        }
+    }
+
+    /// Checks if the azimuth falls into any of the azimuth windows.
+    pub fn in_fov(&self, azimuth: Angle) -> bool {
+        self.azimuth_windows.is_empty()
+            || self
+                .azimuth_windows
+                .iter()
+                .any(|window| window.contains(azimuth))
+    }
+
+    /// Checks if the azimuth range of a firing intersects any of the
+    /// azimuth windows. Per-beam azimuth offsets are not accounted for.
+    pub fn overlaps_fov(&self, azimuth_range: &Range<Angle>) -> bool {
+        self.azimuth_windows.is_empty()
+            || self
+                .azimuth_windows
+                .iter()
+                .any(|window| window.overlaps(azimuth_range))
+    }
+
+    /// Checks if invalid points are omitted from sparse outputs.
+    pub(crate) fn skips_invalid(&self) -> bool {
+        self.return_filter.policy == InvalidPolicy::Skip
+    }
+
+    /// Computes the position of a return, which is identical to
+    /// [spherical_to_xyz] on the same beam followed by the coordinate
+    /// convention and the transform.
+    pub(crate) fn beam_to_xyz(
+        &self,
+        beam: &PreparedBeam,
+        distance: Length,
+        azimuth: Angle,
+    ) -> [Length; 3] {
+        let PreparedBeam {
+            elevation_sin,
+            elevation_cos,
+            vertical_offset,
+            horizontal_offset,
+            ..
+        } = *beam;
+        let [azimuth_sin, azimuth_cos] = AZIMUTH_TABLE.sin_cos(azimuth);
+
+        let distance_plane = distance * elevation_cos - vertical_offset * elevation_sin;
+        let x = distance_plane * azimuth_sin - horizontal_offset * azimuth_cos;
+        let y = distance_plane * azimuth_cos + horizontal_offset * azimuth_sin;
+        let z = distance * elevation_sin + vertical_offset * elevation_cos;
+
+        let xyz = self.convention.apply([x, y, z]);
+        match &self.transform {
+            Some(transform) => transform.apply(xyz),
+            None => xyz,
+        }
+    }
+}
+
+impl<const SIZE: usize> From<&ConfigN<SIZE>> for PreparedBeams<SIZE> {
+    fn from(config: &ConfigN<SIZE>) -> Self {
+        let prepared = Self {
+            transform: config.transform,
+            convention: config.convention,
+            return_filter: config.return_filter,
+            azimuth_windows: config.azimuth_windows.clone(),
+            ..Self::new(
+                config.return_mode,
+                config.distance_resolution,
+                &config.lasers,
+            )
+        };
+
+        match config.timing {
+            Some(timing) => prepared.with_timing(timing),
+            None => prepared,
+        }
+    }
+}
+
+/// Selects [ConverterN] in a [FormatKind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConverterFamily;
+
+impl LayoutFamily for ConverterFamily {
+    type Of<const LINES: usize, const RETURNS: usize> = ConverterN<LINES, RETURNS>;
+}
+
+pub type Converter = FormatKind<ConverterFamily>;
+
+impl Converter {
+    /// Prepares a converter for the configuration.
+    pub fn new(config: &Config) -> Result<Self> {
+        let config_kind = config
+            .clone()
+            .try_into_kind()
+            .map_err(|_| format_err!("invalid configuration"))?;
+
+        Ok(dispatch!(config_kind, <LINES, RETURNS> config => {
+            ConverterN::<LINES, RETURNS>::new(&config).into()
+        }))
+    }
+
+    /// Converts a firing of the same format as the converter.
+    pub fn firing_block_to_xyz(&self, firing: &FiringBlock<'_>) -> Result<FiringXyz> {
+        dispatch!(self, <LINES, RETURNS> converter => {
+            let firing = firing
+                .as_layout::<LINES, RETURNS>()
+                .ok_or_else(|| format_err!("the firing format does not match the converter"))?;
+            Ok(converter.firing_block_to_xyz(firing).into())
+        })
+    }
+}
+
+impl<const LINES: usize, const RETURNS: usize> From<ConverterN<LINES, RETURNS>> for Converter
+where
+    LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+{
+    fn from(v: ConverterN<LINES, RETURNS>) -> Self {
+        Self::from_layout(v)
+    }
+}
+
+/// A converter that caches the beam trigonometry, rings and timing of
+/// `LINES` lasers, whose firings have `RETURNS` returns each.
+#[derive(Debug, Clone)]
+pub struct ConverterN<const LINES: usize, const RETURNS: usize> {
+    pub prepared: PreparedBeams<LINES>,
+}
+
+impl<const LINES: usize, const RETURNS: usize> ConverterN<LINES, RETURNS> {
+    pub fn new(config: &ConfigN<LINES>) -> Self {
+        Self::from_prepared(config.into())
+    }
+
+    pub fn from_prepared(prepared: PreparedBeams<LINES>) -> Self {
+        Self { prepared }
+    }
+
+    pub fn firing_block_to_xyz(
+        &self,
+        firing: &FiringBlockN<'_, LINES, RETURNS>,
+    ) -> FiringXyzN<LINES, RETURNS> {
+        firing_to_xyz(
+            firing.toh,
+            &firing.azimuth_range,
+            |return_index, laser_id| firing.channel(return_index, laser_id),
+            &self.prepared,
+        )
+    }
+
+    pub fn firing_raw_to_xyz(
+        &self,
+        firing: &FiringRawN<LINES, RETURNS>,
+    ) -> FiringXyzN<LINES, RETURNS> {
+        firing_to_xyz(
+            firing.toh,
+            &firing.azimuth_range,
+            |return_index, laser_id| firing.channel(return_index, laser_id),
+            &self.prepared,
+        )
+    }
+
+    /// Converts a firing to polar coordinates without computing the
+    /// Cartesian coordinates.
+    pub fn firing_block_to_polar(
+        &self,
+        firing: &FiringBlockN<'_, LINES, RETURNS>,
+    ) -> FiringPolarN<LINES, RETURNS> {
+        firing_to_polar(
+            firing.toh,
+            &firing.azimuth_range,
+            |return_index, laser_id| firing.channel(return_index, laser_id),
+            &self.prepared,
+        )
+    }
+
+    pub fn firing_raw_to_polar(
+        &self,
+        firing: &FiringRawN<LINES, RETURNS>,
+    ) -> FiringPolarN<LINES, RETURNS> {
+        firing_to_polar(
+            firing.toh,
+            &firing.azimuth_range,
+            |return_index, laser_id| firing.channel(return_index, laser_id),
+            &self.prepared,
+        )
+    }
+
+    /// Projects a polar firing to Cartesian coordinates, which gives the
+    /// same result as converting the source firing directly.
+    pub fn firing_polar_to_xyz(
+        &self,
+        firing: &FiringPolarN<LINES, RETURNS>,
+    ) -> FiringXyzN<LINES, RETURNS> {
+        polar_to_xyz(firing, &self.prepared)
+    }
+}
+
+/// The packet decoding of a converter, which depends on how the firings
+/// are laid out in the data packets.
+impl<const LINES: usize, const RETURNS: usize> ConverterN<LINES, RETURNS>
+where
+    LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+{
+    pub fn format(&self) -> Format {
+        LayoutN::<LINES, RETURNS>::FORMAT
+    }
+
+    /// Iterates over the firings of a packet, skipping those outside the
+    /// azimuth windows.
+    pub fn firing_blocks<'a>(
+        &'a self,
+        packet: &'a DataPacket,
+    ) -> impl Iterator<Item = FiringBlockN<'a, LINES, RETURNS>> + Clone + 'a {
+        self.firing_blocks_with_end_azimuth(packet, None)
+    }
+
+    /// Iterates over the firings of a packet like [Self::firing_blocks],
+    /// where the last firing ends at `end_azimuth` if it is given.
+    pub fn firing_blocks_with_end_azimuth<'a>(
+        &'a self,
+        packet: &'a DataPacket,
+        end_azimuth: Option<Angle>,
+    ) -> impl Iterator<Item = FiringBlockN<'a, LINES, RETURNS>> + Clone + 'a {
+        packet
+            .firing_block_iter_n_with_end_azimuth::<LINES, RETURNS>(end_azimuth)
+            .filter(|firing| self.prepared.overlaps_fov(&firing.azimuth_range))
+    }
+
+    /// Converts the firings of a packet and appends them to `output`.
+    ///
+    /// It does not allocate if `output` has enough spare capacity.
+    pub fn packet_to_firings(
+        &self,
+        packet: &DataPacket,
+        output: &mut Vec<FiringXyzN<LINES, RETURNS>>,
+    ) -> Result<()> {
+        self.check_packet(packet)?;
+        output.extend(
+            self.firing_blocks(packet)
+                .map(|firing| self.firing_block_to_xyz(&firing)),
+        );
+        Ok(())
+    }
+
+    /// Converts the points of a packet and appends them to `output`.
+    ///
+    /// It does not allocate if `output` has enough spare capacity.
+    pub fn packet_to_points(
+        &self,
+        packet: &DataPacket,
+        output: &mut Vec<PointN<RETURNS>>,
+    ) -> Result<()> {
+        self.check_packet(packet)?;
+        let skip = self.prepared.skips_invalid();
+        for firing in self.firing_blocks(packet) {
+            let points = self.firing_block_to_xyz(&firing).points;
+            output.extend(points.into_iter().filter(|point| !skip || point.is_valid()));
+        }
+        Ok(())
+    }
+
+    /// Converts the points of a packet and appends them to a compact
+    /// cloud.
+    ///
+    /// If the cloud is empty, the `start_toh` of its header is set to the
+    /// time of the first firing in the packet. It does not allocate if
+    /// the cloud has enough spare capacity.
+    pub fn packet_to_point_cloud(
+        &self,
+        packet: &DataPacket,
+        output: &mut PointCloud,
+    ) -> Result<()> {
+        self.check_packet(packet)?;
+        let skip = self.prepared.skips_invalid();
+        for firing in self.firing_blocks(packet) {
+            if output.is_empty() {
+                output.header.start_toh = firing.toh;
+            }
+            for point in &self.firing_block_to_xyz(&firing).points {
+                if skip && !point.measurement().valid {
+                    continue;
+                }
+                output.push_point(point);
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts the firings of a packet and pushes them to the batcher
+    /// without buffering them.
+    ///
+    /// Completed frames are appended to `output`. Pass the firings of
+    /// consumed frames to [Batcher::recycle] to reuse their storage.
+    pub fn push_packet<S>(
+        &self,
+        batcher: &mut Batcher<FiringXyzN<LINES, RETURNS>, S>,
+        packet: &DataPacket,
+        output: &mut Vec<Batch<FiringXyzN<LINES, RETURNS>>>,
+    ) -> Result<()>
+    where
+        S: BatchStrategy<FiringXyzN<LINES, RETURNS>>,
+    {
+        self.check_packet(packet)?;
+        let firings = self
+            .firing_blocks(packet)
+            .map(|firing| self.firing_block_to_xyz(&firing));
+        output.extend(batcher.push_packet(firings));
+        Ok(())
+    }
+
+    pub(crate) fn check_packet(&self, packet: &DataPacket) -> Result<()> {
+        let format = packet.try_format();
+        ensure!(
+            format == Some(self.format()),
+            "expect a packet with format {:?}, but received {:?}",
+            self.format(),
+            format
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        batcher::AzimuthWrap,
+        config::AzimuthWindow,
+        packet::ProductID,
+        test_utils::PacketBuilder,
+        types::point::{PointD, PointS},
+        Config16, Config32,
+    };
+
+    fn vlp32_packets(num_packets: u32) -> Vec<DataPacket> {
+        PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest)
+            .with_azimuth(0, 1000)
+            .with_distance(100)
+            .packets(num_packets)
+    }
+
+    #[test]
+    fn converter_bit_compatible_test() {
+        let config = Config32::new_vlp_32c_strongest();
+        let converter = ConverterN::<32, 1>::new(&config);
+        let num_ticks = 36000;
+
+        for count in (0..num_ticks).step_by(997) {
+            let start = 2.0 * PI * count as f64 / num_ticks as f64;
+            let end = 2.0 * PI * (count + 20) as f64 / num_ticks as f64;
+            let mut channels = [Channel {
+                distance: 0,
+                intensity: 0,
+            }; 32];
+            for (index, channel) in channels.iter_mut().enumerate() {
+                channel.distance = (count + index * 131) as u16;
+            }
+            let firing = FiringRawN {
+                toh: Duration::ZERO,
+                azimuth_range: Angle::from_radians(start)..Angle::from_radians(end),
+                channels: [channels],
+            };
+            let output = converter.firing_raw_to_xyz(&firing);
+
+            for (point, laser) in output.points.iter().zip(&config.lasers) {
+                let PointS {
+                    azimuth,
+                    measurements: [ref measurement],
+                    ..
+                } = *point;
+                let expect = spherical_to_xyz(
+                    measurement.distance,
+                    laser.elevation,
+                    azimuth,
+                    laser.vertical_offset,
+                    laser.horizontal_offset,
+                );
+                let bits = |xyz: &[Length; 3]| xyz.map(|v| v.as_meters().to_bits());
+                assert_eq!(bits(&measurement.xyz), bits(&expect));
+            }
+        }
+    }
+
+    #[test]
+    fn converter_azimuth_table_test() {
+        let table = AzimuthTable::new();
+        for count in (0..NUM_AZIMUTH_TICKS).step_by(997) {
+            let radians = 2.0 * PI * count as f64 / (AZIMUTH_COUNT_PER_REV - 1) as f64;
+            let hit = table.get(Angle::from_radians(radians)).unwrap();
+            assert_eq!(
+                hit.map(f64::to_bits),
+                [radians.sin(), radians.cos()].map(f64::to_bits)
+            );
+
+            let between = radians + PI / NUM_AZIMUTH_TICKS as f64;
+            assert_eq!(table.get(Angle::from_radians(between)), None);
+        }
+        assert_eq!(table.get(Angle::from_radians(-1.0)), None);
+        assert_eq!(table.get(Angle::from_radians(2.0 * PI)), None);
+
+        // the first laser of a VLP-16 fires at the block azimuth
+        let config = Config16::new_vlp_16_strongest();
+        let converter = ConverterN::<16, 1>::new(&config);
+        let packet = PacketBuilder::new(ProductID::VLP16, ReturnMode::Strongest)
+            .with_azimuth(1234, 40)
+            .with_distance(300)
+            .packet(0);
+        let firing = packet.firing_block_iter_n::<16, 1>().next().unwrap();
+        let output = converter.firing_block_to_xyz(&firing);
+
+        let PointS {
+            azimuth,
+            measurements: [ref measurement],
+            ..
+        } = output.points[0];
+        assert!(table.get(azimuth).is_some());
+        let expect = spherical_to_xyz(
+            measurement.distance,
+            config.lasers[0].elevation,
+            azimuth,
+            config.lasers[0].vertical_offset,
+            config.lasers[0].horizontal_offset,
+        );
+        assert_eq!(measurement.xyz, expect);
+    }
+
+    #[test]
+    fn converter_reuse_buffer_test() {
+        let packets = vlp32_packets(40);
+
+        let config = Config32::new_vlp_32c_strongest();
+        let converter = ConverterN::<32, 1>::new(&config);
+
+        let mut points = Vec::with_capacity(12 * 32);
+        let capacity = points.capacity();
+        for packet in &packets {
+            points.clear();
+            converter.packet_to_points(packet, &mut points).unwrap();
+            assert_eq!(points.len(), 12 * 32);
+            assert_eq!(points.capacity(), capacity);
+
+            let expect = packet
+                .firing_xyz_iter_n::<32, 1>(&config)
+                .flat_map(|firing| firing.points);
+            assert!(points.iter().cloned().eq(expect));
+        }
+
+        let mut batcher = Batcher::with_strategy(AzimuthWrap);
+        let mut batches = vec![];
+        let mut recycled = vec![];
+        for packet in &packets {
+            converter
+                .push_packet(&mut batcher, packet, &mut batches)
+                .unwrap();
+            for batch in batches.drain(..) {
+                recycled.push(batch.items.as_ptr());
+                batcher.recycle(batch.items);
+            }
+        }
+        let batch = batcher.take_batch().unwrap();
+        assert_eq!(batch.seq, 13);
+        assert!(recycled.contains(&batch.items.as_ptr()));
+
+        let mut packet = packets[0];
+        packet.product_id = ProductID::VLP16;
+        assert!(converter.packet_to_points(&packet, &mut points).is_err());
+    }
+
+    #[test]
+    fn converter_point_cloud_test() {
+        let packets = vlp32_packets(2);
+        let config = Config32::new_vlp_32c_strongest();
+        let converter = ConverterN::<32, 1>::new(&config);
+        let packet = packets.last().unwrap();
+
+        let mut points = vec![];
+        converter.packet_to_points(packet, &mut points).unwrap();
+        let mut cloud = PointCloud::with_capacity(12 * 32);
+        converter.packet_to_point_cloud(packet, &mut cloud).unwrap();
+        assert_eq!(cloud.len(), 12 * 32);
+        assert_eq!(
+            Some(cloud.header.start_toh),
+            packet
+                .firing_block_iter_n::<32, 1>()
+                .next()
+                .map(|firing| firing.toh)
+        );
+        assert_eq!(cloud.time[0], 0.0);
+        assert_eq!(
+            cloud.ring[..2],
+            [config.rings()[0] as u16, config.rings()[1] as u16]
+        );
+        for (index, point) in points.iter().enumerate() {
+            assert_eq!(
+                cloud.x[index],
+                point.measurement().xyz[0].as_meters() as f32
+            );
+        }
+    }
+
+    #[test]
+    fn converter_generic_size_test() {
+        let lasers: [Beam; 64] = array::from_fn(|laser_id| Beam {
+            elevation: Angle::from_degrees(laser_id as f64 * 0.5 - 24.0),
+            azimuth_offset: Angle::default(),
+            vertical_offset: Length::default(),
+            horizontal_offset: Length::default(),
+        });
+        let prepared = PreparedBeams::new(
+            ReturnMode::Strongest,
+            Length::from_millimeters(2.0),
+            &lasers,
+        );
+        let converter = ConverterN::<64, 1>::from_prepared(prepared);
+
+        let firing = FiringRawN {
+            toh: Duration::ZERO,
+            azimuth_range: Angle::default()..Angle::from_degrees(0.2),
+            channels: [[Channel {
+                distance: 500,
+                intensity: 9,
+            }; 64]],
+        };
+        let output = converter.firing_raw_to_xyz(&firing);
+
+        assert_eq!(output.points.len(), 64);
+        assert_eq!(output.points[63].ring, 63);
+        assert_eq!(output.points[0].toh, Duration::ZERO);
+        assert_eq!(output.points[63].toh, Duration::ZERO);
+        assert_eq!(
+            output.points[10].measurement().distance,
+            Length::from_meters(1.0)
+        );
+    }
+
+    #[test]
+    fn converter_convention_test() {
+        use crate::transform::SignedAxis::*;
+
+        let config = Config32::new_vlp_32c_strongest();
+        let firing = FiringRawN {
+            toh: Duration::ZERO,
+            azimuth_range: Angle::from_degrees(30.0)..Angle::from_degrees(30.2),
+            channels: [[Channel {
+                distance: 500,
+                intensity: 0,
+            }; 32]],
+        };
+        let convert = |convention| {
+            let config = config.clone().with_convention(convention);
+            ConverterN::<32, 1>::new(&config)
+                .firing_raw_to_xyz(&firing)
+                .points
+                .map(|point| point.measurement().xyz)
+        };
+
+        let native = convert(CoordinateConvention::Native);
+        let rep103 = convert(CoordinateConvention::Rep103);
+        let custom = convert(CoordinateConvention::permutation([NegZ, PosX, NegY]).unwrap());
+
+        for ((&[x, y, z], rep103), custom) in native.iter().zip(&rep103).zip(&custom) {
+            assert_eq!(*rep103, [y, x * -1.0, z]);
+            assert_eq!(*custom, [z * -1.0, x, y * -1.0]);
+        }
+        assert!(CoordinateConvention::permutation([PosX, NegX, PosZ]).is_none());
+    }
+
+    #[test]
+    fn converter_return_filter_test() {
+        let mut packet = PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest).packet(0);
+        for block in &mut packet.blocks {
+            // 0, 0.5, 1.0, ... 15.5 meters
+            for (distance, channel) in block.channels.iter_mut().enumerate() {
+                channel.distance = (distance * 125) as u16;
+            }
+        }
+        let filter = ReturnFilter::with_range(Length::from_meters(1.0), Length::from_meters(10.0));
+        let convert = |policy| {
+            let config =
+                Config32::new_vlp_32c_strongest().with_return_filter(filter.with_policy(policy));
+            let mut points = vec![];
+            ConverterN::<32, 1>::new(&config)
+                .packet_to_points(&packet, &mut points)
+                .unwrap();
+            points
+        };
+
+        let kept = convert(InvalidPolicy::Keep);
+        assert_eq!(kept.len(), 12 * 32);
+        assert_eq!(
+            kept.iter().filter(|point| point.is_valid()).count(),
+            12 * 19
+        );
+        assert!(kept.iter().all(|point| !point.measurement().is_nan()));
+
+        let nan = convert(InvalidPolicy::Nan);
+        assert_eq!(nan.len(), 12 * 32);
+        assert!(nan
+            .iter()
+            .all(|point| point.is_valid() != point.measurement().is_nan()));
+
+        let skipped = convert(InvalidPolicy::Skip);
+        assert_eq!(skipped.len(), 12 * 19);
+        assert!(skipped.iter().all(|point| {
+            let distance = point.measurement().distance;
+            (Length::from_meters(1.0)..=Length::from_meters(10.0)).contains(&distance)
+        }));
+    }
+
+    #[test]
+    fn converter_azimuth_window_test() {
+        // 355, 356, ... 6 degrees
+        let packet = PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest)
+            .with_azimuth(35500, 100)
+            .with_distance(500)
+            .packet(0);
+        let window = AzimuthWindow::new(Angle::from_degrees(358.5), Angle::from_degrees(2.5));
+        let config = Config32::new_vlp_32c_strongest()
+            .with_azimuth_windows(vec![window])
+            .with_return_filter(ReturnFilter::default().with_policy(InvalidPolicy::Skip));
+        let converter = ConverterN::<32, 1>::new(&config);
+
+        let azimuths: Vec<_> = converter
+            .firing_blocks(&packet)
+            .map(|firing| firing.azimuth_range.start.as_degrees().round() as i32)
+            .collect();
+        assert_eq!(azimuths, [358, 359, 0, 1, 2]);
+
+        let mut points = vec![];
+        converter.packet_to_points(&packet, &mut points).unwrap();
+        assert!(!points.is_empty());
+        assert!(points.iter().all(|point| window.contains(point.azimuth)));
+    }
+
+    #[test]
+    fn converter_polar_test() {
+        let config = Config32::new_vlp_32c_dual()
+            .with_return_filter(ReturnFilter::default().with_policy(InvalidPolicy::Nan));
+        let converter = ConverterN::<32, 2>::new(&config);
+        let mut channels = [
+            [Channel {
+                distance: 0,
+                intensity: 3,
+            }; 32],
+            [Channel {
+                distance: 0,
+                intensity: 5,
+            }; 32],
+        ];
+        for (index, channel) in channels[0].iter_mut().enumerate() {
+            channel.distance = (index * 97) as u16;
+        }
+        for (index, channel) in channels[1].iter_mut().enumerate() {
+            channel.distance = (index * 131) as u16;
+        }
+        let firing = FiringRawN {
+            toh: Duration::from_millis(3),
+            azimuth_range: Angle::from_degrees(120.0)..Angle::from_degrees(120.2),
+            channels,
+        };
+
+        let polar = converter.firing_raw_to_polar(&firing);
+        let direct = converter.firing_raw_to_xyz(&firing);
+        let projected = converter.firing_polar_to_xyz(&polar);
+
+        for ((polar, direct), projected) in polar
+            .points
+            .iter()
+            .zip(&direct.points)
+            .zip(&projected.points)
+        {
+            assert_eq!(polar.elevation, config.lasers[polar.laser_id].elevation);
+            assert_eq!(polar.azimuth, direct.azimuth);
+            let [strongest, last] = &polar.measurements;
+            assert_eq!(last.distance, direct.measurement_last().distance);
+            assert_eq!(strongest.valid, polar.laser_id != 0);
+
+            let bits = |point: &PointD| {
+                point
+                    .measurements
+                    .each_ref()
+                    .map(|measurement| measurement.xyz.map(|v| v.as_meters().to_bits()))
+            };
+            assert_eq!(bits(projected), bits(direct));
+        }
+    }
+}
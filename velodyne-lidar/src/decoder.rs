@@ -0,0 +1,318 @@
+//! Push-based decoding for event loops.
+//!
+//! The decoders take one packet at a time and report the converted
+//! firings and the completed frames through a callback. A packet is
+//! held back until the next packet arrives, so that the azimuth of its
+//! last block can be interpolated towards the next packet.
+
+use crate::{
+    batcher::{AzimuthWrap, Batch, BatchStrategy, BatchStrategyKind, Batcher},
+    config::AzimuthWindow,
+    converter::ConverterN,
+    packet::{Lookahead, LookaheadPacket, Packet},
+    types::{
+        firing_xyz::{FiringXyzFamily, FiringXyzN, FiringXyzRef},
+        format::{dispatch, FormatKind, LayoutFamily, LayoutN, SupportedLayout},
+        frame_header::FrameHeader,
+        frame_xyz::{FrameXyz, FrameXyzN},
+    },
+    Config, ConfigN, DataPacket, ProductID,
+};
+use eyre::{format_err, Result};
+use std::marker::PhantomData;
+
+/// An output of a decoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecoderEvent<F, R> {
+    /// A converted firing, reported before it is batched.
+    Firing(F),
+    /// A completed frame.
+    Frame(R),
+}
+
+/// Selects [DecoderN] with the batching strategy `S` in a [FormatKind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DecoderFamily<S>(PhantomData<S>);
+
+impl<S> LayoutFamily for DecoderFamily<S> {
+    type Of<const LINES: usize, const RETURNS: usize> = DecoderN<LINES, RETURNS, S>;
+}
+
+pub type Decoder<S = AzimuthWrap> = FormatKind<DecoderFamily<S>>;
+
+impl Decoder {
+    /// Creates a decoder that splits frames when the azimuth wraps.
+    pub fn new(config: &Config) -> Result<Self> {
+        Self::with_strategy(config, AzimuthWrap)
+    }
+}
+
+impl<S> Decoder<S>
+where
+    S: BatchStrategyKind<FiringXyzFamily>,
+{
+    /// Creates a decoder where frames are split by the batching
+    /// strategy.
+    pub fn with_strategy(config: &Config, strategy: S) -> Result<Self> {
+        let config_kind = config
+            .clone()
+            .try_into_kind()
+            .map_err(|_| format_err!("invalid configuration"))?;
+
+        Ok(dispatch!(config_kind, <LINES, RETURNS> config => {
+            FormatKind::from_layout::<LINES, RETURNS>(DecoderN::with_strategy(&config, strategy))
+        }))
+    }
+
+    /// Decodes a UDP payload and returns the completed frames.
+    ///
+    /// Position packets are accepted and ignored.
+    pub fn feed(&mut self, payload: &[u8]) -> Result<Vec<FrameXyz>> {
+        let mut frames = vec![];
+        self.feed_with(payload, |event| {
+            if let DecoderEvent::Frame(frame) = event {
+                frames.push(frame);
+            }
+        })?;
+        Ok(frames)
+    }
+
+    /// Decodes a UDP payload and reports the firings and the completed
+    /// frames to the callback.
+    ///
+    /// Position packets are accepted and ignored.
+    pub fn feed_with<F>(&mut self, payload: &[u8], callback: F) -> Result<()>
+    where
+        F: FnMut(DecoderEvent<FiringXyzRef<'_>, FrameXyz>),
+    {
+        match Packet::from_slice(payload)? {
+            Packet::Data(packet) => self.push_packet(*packet, callback),
+            Packet::Position(_) => Ok(()),
+        }
+    }
+
+    /// Pushes a data packet and reports the firings and the completed
+    /// frames to the callback.
+    pub fn push_packet<F>(&mut self, packet: DataPacket, mut callback: F) -> Result<()>
+    where
+        F: FnMut(DecoderEvent<FiringXyzRef<'_>, FrameXyz>),
+    {
+        use DecoderEvent as E;
+
+        dispatch!(self, me => me.push_packet(packet, |event| match event {
+            E::Firing(firing) => callback(E::Firing(firing.into())),
+            E::Frame(frame) => callback(E::Frame(frame.into())),
+        }))
+    }
+
+    /// Decodes the held back packet and returns the remaining frames.
+    pub fn finish(&mut self) -> Vec<FrameXyz> {
+        let mut frames = vec![];
+        self.finish_with(|event| {
+            if let DecoderEvent::Frame(frame) = event {
+                frames.push(frame);
+            }
+        });
+        frames
+    }
+
+    /// Decodes the held back packet and reports the remaining firings
+    /// and frames to the callback.
+    pub fn finish_with<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(DecoderEvent<FiringXyzRef<'_>, FrameXyz>),
+    {
+        use DecoderEvent as E;
+
+        dispatch!(self, me => me.finish_with(|event| match event {
+            E::Firing(firing) => callback(E::Firing(firing.into())),
+            E::Frame(frame) => callback(E::Frame(frame.into())),
+        }))
+    }
+}
+
+/// A push-based decoder of `LINES` lasers with `RETURNS` returns, which
+/// keeps the batching and the azimuth interpolation state across
+/// packets.
+#[derive(Debug, Clone)]
+pub struct DecoderN<const LINES: usize, const RETURNS: usize, S = AzimuthWrap> {
+    converter: ConverterN<LINES, RETURNS>,
+    batcher: Batcher<FiringXyzN<LINES, RETURNS>, S>,
+    lookahead: Lookahead,
+    azimuth_windows: Vec<AzimuthWindow>,
+    product_id: Option<ProductID>,
+}
+
+impl<const LINES: usize, const RETURNS: usize> DecoderN<LINES, RETURNS>
+where
+    LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+{
+    /// Creates a decoder that splits frames when the azimuth wraps.
+    pub fn new(config: &ConfigN<LINES>) -> Self {
+        Self::with_strategy(config, AzimuthWrap)
+    }
+}
+
+impl<const LINES: usize, const RETURNS: usize, S> DecoderN<LINES, RETURNS, S>
+where
+    LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    S: BatchStrategy<FiringXyzN<LINES, RETURNS>>,
+{
+    /// Creates a decoder where frames are split by the batching
+    /// strategy.
+    pub fn with_strategy(config: &ConfigN<LINES>, strategy: S) -> Self {
+        Self {
+            converter: ConverterN::new(config),
+            batcher: Batcher::with_strategy(strategy).with_sensor_id(config.sensor_id),
+            lookahead: Lookahead::new(),
+            azimuth_windows: config.azimuth_windows.clone(),
+            product_id: None,
+        }
+    }
+
+    pub fn converter(&self) -> &ConverterN<LINES, RETURNS> {
+        &self.converter
+    }
+
+    /// The latest measured rotation rate in radians per second.
+    pub fn rotation_rate(&self) -> Option<f64> {
+        self.lookahead.rotation_rate()
+    }
+
+    /// Returns the storage of a consumed frame to the decoder.
+    pub fn recycle(&mut self, frame: FrameXyzN<LINES, RETURNS>) {
+        self.batcher.recycle(frame.firings);
+    }
+
+    /// Pushes a data packet and decodes the previous packet.
+    ///
+    /// The firings of a packet are reported as they are converted, and
+    /// the frames completed by the packet are reported after its
+    /// firings.
+    pub fn push_packet<F>(&mut self, packet: DataPacket, mut callback: F) -> Result<()>
+    where
+        F: FnMut(DecoderEvent<&FiringXyzN<LINES, RETURNS>, FrameXyzN<LINES, RETURNS>>),
+    {
+        self.converter.check_packet(&packet)?;
+        if let Some(packet) = self.lookahead.push(packet) {
+            self.decode(&packet, &mut callback);
+        }
+        Ok(())
+    }
+
+    /// Decodes the held back packet, whose end azimuth is extrapolated,
+    /// and reports the buffered firings as the last frame.
+    pub fn finish_with<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(DecoderEvent<&FiringXyzN<LINES, RETURNS>, FrameXyzN<LINES, RETURNS>>),
+    {
+        if let Some(packet) = self.lookahead.finish() {
+            self.decode(&packet, &mut callback);
+        }
+        if let Some(batch) = self.batcher.take_batch() {
+            callback(DecoderEvent::Frame(self.to_frame(batch)));
+        }
+    }
+
+    fn decode<F>(&mut self, packet: &LookaheadPacket, callback: &mut F)
+    where
+        F: FnMut(DecoderEvent<&FiringXyzN<LINES, RETURNS>, FrameXyzN<LINES, RETURNS>>),
+    {
+        let LookaheadPacket {
+            ref packet,
+            end_azimuth,
+        } = *packet;
+        self.product_id = Some(packet.product_id);
+
+        let converter = &self.converter;
+        let firings = converter
+            .firing_blocks_with_end_azimuth(packet, end_azimuth)
+            .map(|block| converter.firing_block_to_xyz(&block))
+            .inspect(|firing| callback(DecoderEvent::Firing(firing)));
+        let batches: Vec<_> = self.batcher.push_packet(firings).collect();
+
+        for batch in batches {
+            callback(DecoderEvent::Frame(self.to_frame(batch)));
+        }
+    }
+
+    fn to_frame(&self, batch: Batch<FiringXyzN<LINES, RETURNS>>) -> FrameXyzN<LINES, RETURNS> {
+        FrameXyzN {
+            header: FrameHeader::from_batch(&batch, self.product_id)
+                .with_azimuth_windows(&self.azimuth_windows),
+            firings: batch.items,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::PacketBuilder, traits::AzimuthRange as _, ReturnMode};
+    use std::mem;
+
+    #[test]
+    fn decoder_feed_test() {
+        let packets = PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest)
+            .with_azimuth(0, 200)
+            .with_distance(100)
+            .packets(100);
+
+        let config = Config::new_vlp_32c_strongest().with_sensor_id(7);
+        let expect: Vec<_> = crate::iter::data_packet_to_frame_xyz(config.clone(), packets.clone())
+            .unwrap()
+            .collect();
+
+        let mut decoder = Decoder::new(&config).unwrap();
+        let mut frames = vec![];
+        let mut num_firings = 0;
+        for packet in packets {
+            let payload: [u8; mem::size_of::<DataPacket>()] = unsafe { mem::transmute(packet) };
+            decoder
+                .feed_with(&payload, |event| match event {
+                    DecoderEvent::Firing(_) => num_firings += 1,
+                    DecoderEvent::Frame(frame) => frames.push(frame),
+                })
+                .unwrap();
+        }
+
+        // the last packet is held back
+        assert_eq!(num_firings, 99 * 12);
+        assert_eq!(frames.len(), expect.len());
+        for (frame, expect) in frames.iter().zip(&expect) {
+            let (header, expect_header) = (frame.header(), expect.header());
+            assert_eq!(header.seq, expect_header.seq);
+            assert_eq!(header.num_packets, expect_header.num_packets);
+            assert_eq!(header.start_toh, expect_header.start_toh);
+            assert_eq!(header.end_toh, expect_header.end_toh);
+            assert_eq!(header.sensor_id, Some(7));
+            assert_eq!(expect_header.sensor_id, Some(7));
+            assert!(header.missing.is_empty());
+
+            // the last block of a packet is interpolated towards the
+            // next packet, which agrees with the extrapolation for
+            // uniform rotation
+            let firings = frame.clone().into_firing_iter();
+            for (lhs, rhs) in firings.zip(expect.clone().into_firing_iter()) {
+                let lhs = lhs.azimuth_range();
+                let rhs = rhs.azimuth_range();
+                assert!((lhs.end.as_radians() - rhs.end.as_radians()).abs() < 1e-9);
+            }
+        }
+
+        let rest = decoder.finish();
+        assert_eq!(rest.len(), 1);
+        assert!(decoder.finish().is_empty());
+    }
+
+    #[test]
+    fn decoder_unsupported_model_test() {
+        let mut packet = PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest).packet(0);
+        packet.product_id = ProductID::Velarray;
+        let payload: [u8; mem::size_of::<DataPacket>()] = unsafe { mem::transmute(packet) };
+
+        let mut decoder = Decoder::new(&Config::new_vlp_32c_strongest()).unwrap();
+        assert!(decoder.feed(&payload).is_err());
+        assert!(decoder.finish().is_empty());
+    }
+}
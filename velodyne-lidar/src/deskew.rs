@@ -0,0 +1,247 @@
+//! Motion compensation of frames.
+
+use crate::{
+    transform::Transform,
+    types::{
+        format::dispatch,
+        frame_xyz::{FrameXyz, FrameXyzN},
+        measurements::Measurement,
+    },
+    utils::signed_elapsed_toh,
+};
+use eyre::{format_err, Result};
+use measurements::Length;
+use std::time::Duration;
+
+/// Provides the pose of the sensor at arbitrary times.
+///
+/// The points are deskewed in the frame they were output in, which is
+/// after the coordinate convention and the transform of the
+/// configuration. The poses must be given for that frame, e.g. the
+/// vehicle frame if the transform is the sensor-to-vehicle mount.
+pub trait PoseProvider {
+    /// Gets the pose of the output frame in a fixed world frame at the
+    /// time. Returns `None` if the pose is not available.
+    fn pose_at(&self, toh: Duration) -> Option<Transform>;
+}
+
+impl<P> PoseProvider for &P
+where
+    P: PoseProvider + ?Sized,
+{
+    fn pose_at(&self, toh: Duration) -> Option<Transform> {
+        (**self).pose_at(toh)
+    }
+}
+
+/// A sensor moving with constant linear and angular velocities.
+///
+/// The pose is the identity at the reference time. Velocities are
+/// expressed in the output frame at the reference time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstantVelocity {
+    pub reference_toh: Duration,
+    /// Linear velocity in meters per second.
+    pub linear: [f64; 3],
+    /// Angular velocity in radians per second.
+    pub angular: [f64; 3],
+}
+
+impl PoseProvider for ConstantVelocity {
+    fn pose_at(&self, toh: Duration) -> Option<Transform> {
+        let secs = signed_elapsed_toh(self.reference_toh, toh);
+        let translation = self.linear.map(|v| Length::from_meters(v * secs));
+        let rotation = self.angular.map(|v| v * secs);
+        Some(Transform::from_rotation_vector(rotation, translation))
+    }
+}
+
+/// Re-expresses the points of a frame in the output frame at
+/// `reference_toh`, using the pose at the time of every point.
+///
+/// Only the Cartesian coordinates are updated. It fails if a pose is
+/// not available, in which case the frame is left unchanged.
+pub fn deskew_frame<P>(frame: &mut FrameXyz, poses: &P, reference_toh: Duration) -> Result<()>
+where
+    P: PoseProvider + ?Sized,
+{
+    dispatch!(frame, frame => deskew_frame_n(frame, poses, reference_toh))
+}
+
+pub fn deskew_frame_n<P, const LINES: usize, const RETURNS: usize>(
+    frame: &mut FrameXyzN<LINES, RETURNS>,
+    poses: &P,
+    reference_toh: Duration,
+) -> Result<()>
+where
+    P: PoseProvider + ?Sized,
+{
+    let to_reference = reference_pose(poses, reference_toh)?;
+    let transforms: Vec<_> = frame
+        .firings
+        .iter()
+        .flat_map(|firing| &firing.points)
+        .map(|point| point_transform(poses, &to_reference, point.toh))
+        .collect::<Result<_>>()?;
+
+    let points = frame
+        .firings
+        .iter_mut()
+        .flat_map(|firing| &mut firing.points);
+    for (point, transform) in points.zip(&transforms) {
+        for measurement in &mut point.measurements {
+            apply(transform, measurement);
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the transform from the world frame to the sensor frame at the
+/// reference time.
+fn reference_pose<P>(poses: &P, reference_toh: Duration) -> Result<Transform>
+where
+    P: PoseProvider + ?Sized,
+{
+    let pose = poses
+        .pose_at(reference_toh)
+        .ok_or_else(|| format_err!("no pose is available at {reference_toh:?}"))?;
+    Ok(pose.inverse())
+}
+
+fn point_transform<P>(poses: &P, to_reference: &Transform, toh: Duration) -> Result<Transform>
+where
+    P: PoseProvider + ?Sized,
+{
+    let pose = poses
+        .pose_at(toh)
+        .ok_or_else(|| format_err!("no pose is available at {toh:?}"))?;
+    Ok(to_reference.compose(&pose))
+}
+
+fn apply(transform: &Transform, measurement: &mut Measurement) {
+    measurement.xyz = transform.apply(measurement.xyz);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        consts::FIRING_PERIOD,
+        types::{firing_xyz::FiringXyzN, frame_header::FrameHeader, point::PointS},
+        utils::HOUR,
+    };
+    use measurements::Angle;
+    use std::f64::consts::FRAC_PI_2;
+
+    #[test]
+    fn deskew_constant_velocity_test() {
+        let firing = |index: u32| {
+            let toh = FIRING_PERIOD * index * 1000;
+            FiringXyzN {
+                toh,
+                azimuth_range: Angle::default()..Angle::default(),
+                points: [PointS {
+                    laser_id: 0,
+                    ring: 0,
+                    toh,
+                    azimuth: Angle::default(),
+                    measurements: [Measurement {
+                        distance: Length::from_meters(1.0),
+                        intensity: 0,
+                        xyz: [
+                            Length::from_meters(1.0),
+                            Length::default(),
+                            Length::default(),
+                        ],
+                        valid: true,
+                    }],
+                }],
+            }
+        };
+        let mut frame = FrameXyzN::<1, 1> {
+            header: FrameHeader::default(),
+            firings: vec![firing(0), firing(1)],
+        };
+        let end_toh = frame.firings[1].toh;
+        let secs = end_toh.as_secs_f64();
+
+        // move along y while rotating a quarter turn about z
+        let poses = ConstantVelocity {
+            reference_toh: Duration::ZERO,
+            linear: [0.0, 2.0 / secs, 0.0],
+            angular: [0.0, 0.0, FRAC_PI_2 / secs],
+        };
+        deskew_frame_n(&mut frame, &poses, Duration::ZERO).unwrap();
+
+        let xyz = |index: usize| frame.firings[index].points[0].measurement().xyz;
+        assert_eq!(
+            xyz(0),
+            [
+                Length::from_meters(1.0),
+                Length::default(),
+                Length::default()
+            ]
+        );
+
+        let [x, y, z] = xyz(1).map(|v| v.as_meters());
+        assert!(x.abs() < 1e-9);
+        assert!((y - 3.0).abs() < 1e-9);
+        assert!(z.abs() < 1e-9);
+
+        assert!(
+            (signed_elapsed_toh(HOUR - Duration::from_secs(1), Duration::from_secs(1)) - 2.0).abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn deskew_missing_pose_test() {
+        /// Poses available up to a time.
+        struct Until(ConstantVelocity, Duration);
+
+        impl PoseProvider for Until {
+            fn pose_at(&self, toh: Duration) -> Option<Transform> {
+                (toh <= self.1).then(|| self.0.pose_at(toh)).flatten()
+            }
+        }
+
+        let firing = |toh: Duration| FiringXyzN {
+            toh,
+            azimuth_range: Angle::default()..Angle::default(),
+            points: [PointS {
+                laser_id: 0,
+                ring: 0,
+                toh,
+                azimuth: Angle::default(),
+                measurements: [Measurement {
+                    distance: Length::from_meters(1.0),
+                    intensity: 0,
+                    xyz: [Length::from_meters(1.0); 3],
+                    valid: true,
+                }],
+            }],
+        };
+        let mut frame = FrameXyzN::<1, 1> {
+            header: FrameHeader::default(),
+            firings: vec![firing(Duration::ZERO), firing(FIRING_PERIOD)],
+        };
+
+        // the first point would move, but the pose of the second point
+        // is missing
+        let reference_toh = FIRING_PERIOD / 2;
+        let poses = Until(
+            ConstantVelocity {
+                reference_toh: Duration::ZERO,
+                linear: [1000.0, 0.0, 0.0],
+                angular: [0.0; 3],
+            },
+            reference_toh,
+        );
+        assert!(deskew_frame_n(&mut frame, &poses, reference_toh).is_err());
+        assert_eq!(
+            frame.firings[0].points[0].measurement().xyz,
+            [Length::from_meters(1.0); 3]
+        );
+    }
+}
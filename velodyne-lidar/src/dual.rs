@@ -0,0 +1,151 @@
+//! Selection and classification of dual returns.
+
+use crate::types::{
+    frame_xyz::FrameXyzN,
+    measurements::Measurement,
+    point::{PointD, PointS},
+};
+
+/// The returns to output from dual return points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ReturnSelection {
+    Strongest,
+    Last,
+    /// Both returns, where a return reported twice is output once.
+    #[default]
+    Both,
+}
+
+/// The order of a return among the returns of a laser shot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EchoKind {
+    /// The only return of the shot.
+    Single,
+    /// The nearer of two distinct returns.
+    FirstOfTwo,
+    /// The farther of two distinct returns.
+    LastOfTwo,
+}
+
+/// A return of a dual return point labeled with its echo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EchoPoint {
+    pub point: PointS,
+    pub echo: EchoKind,
+}
+
+/// Labels the strongest and the last return of a point, in that order.
+///
+/// Invalid returns are not labeled and do not count as echoes.
+pub fn echo_kinds(point: &PointD) -> [Option<EchoKind>; 2] {
+    use EchoKind::*;
+
+    let [strongest, last] = &point.measurements;
+
+    match (strongest.valid, last.valid) {
+        (true, true) if point.is_duplicate() => [Some(Single), Some(Single)],
+        (true, true) if strongest.distance <= last.distance => [Some(FirstOfTwo), Some(LastOfTwo)],
+        (true, true) => [Some(LastOfTwo), Some(FirstOfTwo)],
+        (true, false) => [Some(Single), None],
+        (false, true) => [None, Some(Single)],
+        (false, false) => [None, None],
+    }
+}
+
+/// Splits a point into the selected valid returns.
+pub fn select_returns(
+    point: &PointD,
+    selection: ReturnSelection,
+) -> impl Iterator<Item = EchoPoint> + '_ {
+    let [strongest_echo, last_echo] = echo_kinds(point);
+    let [strongest, last] = &point.measurements;
+
+    let (strongest_echo, last_echo) = match selection {
+        ReturnSelection::Strongest => (strongest_echo, None),
+        ReturnSelection::Last => (None, last_echo),
+        ReturnSelection::Both if point.is_duplicate() => (strongest_echo, None),
+        ReturnSelection::Both => (strongest_echo, last_echo),
+    };
+
+    [(strongest, strongest_echo), (last, last_echo)]
+        .into_iter()
+        .filter_map(|(measurement, echo)| Some(echo_point(point, measurement, echo?)))
+}
+
+/// Splits the points of a frame into the selected valid returns.
+pub fn select_frame_returns<const LINES: usize>(
+    frame: &FrameXyzN<LINES, 2>,
+    selection: ReturnSelection,
+) -> impl Iterator<Item = EchoPoint> + '_ {
+    frame
+        .firings
+        .iter()
+        .flat_map(|firing| &firing.points)
+        .flat_map(move |point| select_returns(point, selection))
+}
+
+fn echo_point(point: &PointD, measurement: &Measurement, echo: EchoKind) -> EchoPoint {
+    EchoPoint {
+        point: PointS {
+            laser_id: point.laser_id,
+            ring: point.ring,
+            toh: point.toh,
+            azimuth: point.azimuth,
+            measurements: [measurement.clone()],
+        },
+        echo,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use measurements::{Angle, Length};
+    use std::time::Duration;
+
+    #[test]
+    fn select_returns_test() {
+        let measurement = |meters: f64, intensity: u8| Measurement {
+            distance: Length::from_meters(meters),
+            intensity,
+            xyz: [
+                Length::from_meters(meters),
+                Length::default(),
+                Length::default(),
+            ],
+            valid: meters > 0.0,
+        };
+        let point = |strongest: Measurement, last: Measurement| PointD {
+            laser_id: 0,
+            ring: 0,
+            toh: Duration::ZERO,
+            azimuth: Angle::default(),
+            measurements: [strongest, last],
+        };
+        let select = |point: &PointD, selection| -> Vec<_> {
+            select_returns(point, selection)
+                .map(|echo| (echo.point.measurement().distance.as_meters(), echo.echo))
+                .collect()
+        };
+        use EchoKind::*;
+        use ReturnSelection::*;
+
+        let distinct = point(measurement(3.0, 80), measurement(7.0, 20));
+        assert!(!distinct.is_duplicate());
+        assert_eq!(
+            select(&distinct, Both),
+            [(3.0, FirstOfTwo), (7.0, LastOfTwo)]
+        );
+        assert_eq!(select(&distinct, Last), [(7.0, LastOfTwo)]);
+
+        let duplicate = point(measurement(5.0, 40), measurement(5.0, 40));
+        assert!(duplicate.is_duplicate());
+        assert_eq!(select(&duplicate, Both), [(5.0, Single)]);
+        assert_eq!(select(&duplicate, Last), [(5.0, Single)]);
+
+        let partial = point(measurement(0.0, 0), measurement(9.0, 10));
+        assert_eq!(echo_kinds(&partial), [None, Some(Single)]);
+        assert_eq!(select(&partial, Strongest), []);
+        assert_eq!(select(&partial, Both), [(9.0, Single)]);
+    }
+}
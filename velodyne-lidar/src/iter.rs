@@ -7,3 +7,12 @@ pub use convert::*;
 mod pcap;
 #[cfg(feature = "pcap")]
 pub use self::pcap::*;
+
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "mmap")]
+pub use self::mmap::*;
+
+/// Size of the Ethernet, IPv4 and UDP headers preceding a payload.
+#[cfg(any(feature = "pcap", feature = "mmap"))]
+const UDP_HEADER_SIZE: usize = 42;
@@ -1,12 +1,20 @@
 //! Iterator conversion functions.
-use crate::types::{format::Format, frame_xyz::FrameXyz};
+use crate::{
+    packet::{Lookahead, LookaheadPacket},
+    types::{format::Format, frame_raw::FrameRaw, frame_xyz::FrameXyz},
+    DataPacket,
+};
 use log::warn;
+use std::iter;
 
 pub(crate) type FrameXyzIter<'a> = Box<dyn Iterator<Item = FrameXyz> + Send + 'a>;
 pub(crate) type ResultFrameXyzIter<'a, E> =
     Box<dyn Iterator<Item = Result<FrameXyz, E>> + Send + 'a>;
+pub(crate) type FrameRawIter<'a> = Box<dyn Iterator<Item = FrameRaw> + Send + 'a>;
+pub(crate) type ResultFrameRawIter<'a, E> =
+    Box<dyn Iterator<Item = Result<FrameRaw, E>> + Send + 'a>;
 
-fn audit_format(packet_format: Option<Format>, config_format: Format) {
+pub(crate) fn audit_format(packet_format: Option<Format>, config_format: Format) {
     match packet_format {
         Some(packet_format) => {
             if packet_format != config_format {
@@ -23,19 +31,53 @@ fn audit_format(packet_format: Option<Format>, config_format: Format) {
     }
 }
 
+/// The fallible counterpart of [crate::packet::PacketLookahead].
+///
+/// Errors are passed through as they arrive, while the held back packet
+/// waits for the next packet. Errors are not buffered, so they need not
+/// be `Send`.
+pub(crate) fn try_lookahead<E, I>(
+    packets: I,
+) -> impl Iterator<Item = Result<LookaheadPacket, E>> + Send
+where
+    I: IntoIterator<Item = Result<DataPacket, E>>,
+    I::IntoIter: Send,
+{
+    let mut packets = packets.into_iter();
+    let mut lookahead = Lookahead::new();
+
+    iter::from_fn(move || {
+        for packet in packets.by_ref() {
+            match packet {
+                Ok(packet) => {
+                    if let Some(output) = lookahead.push(packet) {
+                        return Some(Ok(output));
+                    }
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        lookahead.finish().map(Ok)
+    })
+}
+
 pub use data_packet_to_frame_xyz::*;
 mod data_packet_to_frame_xyz {
     use super::{audit_format, FrameXyzIter};
     use crate::{
-        batcher::Batcher,
+        batcher::{AzimuthWrap, BatchStrategy, BatchStrategyKind, Batcher, CutAngle},
+        converter::ConverterN,
+        packet::{LookaheadPacket, PacketLookahead},
         types::{
-            firing_xyz::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-            format::FormatKind,
-            frame_xyz::{FrameXyzD16, FrameXyzD32, FrameXyzS16, FrameXyzS32},
+            firing_xyz::{FiringXyzFamily, FiringXyzN},
+            format::{dispatch, LayoutN, SupportedLayout},
+            frame_header::FrameHeader,
+            frame_xyz::{FrameXyz, FrameXyzN},
         },
-        Config, Config16, Config32, DataPacket,
+        Config, ConfigN, DataPacket,
     };
     use eyre::{format_err, Result};
+    use measurements::Angle;
 
     /// Converts an iterator of packets to an iterator of [crate::types::frame_xyz::FrameXyz].
     pub fn data_packet_to_frame_xyz<'a, I>(config: Config, packets: I) -> Result<FrameXyzIter<'a>>
@@ -43,107 +85,132 @@ mod data_packet_to_frame_xyz {
         I: IntoIterator<Item = DataPacket> + 'a,
         I::IntoIter: Send,
     {
-        use FormatKind as K;
+        data_packet_to_frame_xyz_with_strategy(config, AzimuthWrap, packets)
+    }
 
-        let config_kinds = config
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz], where frames are split at
+    /// the cut angle.
+    pub fn data_packet_to_frame_xyz_with_cut_angle<'a, I>(
+        config: Config,
+        cut_angle: Angle,
+        packets: I,
+    ) -> Result<FrameXyzIter<'a>>
+    where
+        I: IntoIterator<Item = DataPacket> + 'a,
+        I::IntoIter: Send,
+    {
+        data_packet_to_frame_xyz_with_strategy(config, CutAngle::new(cut_angle), packets)
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz], where frames are split by
+    /// the batching strategy.
+    pub fn data_packet_to_frame_xyz_with_strategy<'a, I, S>(
+        config: Config,
+        strategy: S,
+        packets: I,
+    ) -> Result<FrameXyzIter<'a>>
+    where
+        I: IntoIterator<Item = DataPacket> + 'a,
+        I::IntoIter: Send,
+        S: BatchStrategyKind<FiringXyzFamily> + Send + 'a,
+    {
+        let config_kind = config
             .try_into_kind()
             .map_err(|_| format_err!("invalid configuration"))?;
 
-        let iter: FrameXyzIter = match config_kinds {
-            K::Single16(config) => {
-                Box::new(data_packet_to_frame_xyz_s16(config, packets).map(K::from_s16))
-            }
-            K::Single32(config) => {
-                Box::new(data_packet_to_frame_xyz_s32(config, packets).map(K::from_s32))
-            }
-            K::Dual16(config) => {
-                Box::new(data_packet_to_frame_xyz_d16(config, packets).map(K::from_d16))
-            }
-            K::Dual32(config) => {
-                Box::new(data_packet_to_frame_xyz_d32(config, packets).map(K::from_d32))
-            }
-        };
+        let iter: FrameXyzIter = dispatch!(config_kind, <LINES, RETURNS> config => Box::new(
+            data_packet_to_frame_xyz_n_with_strategy::<LINES, RETURNS, _, _>(
+                config, strategy, packets,
+            )
+            .map(FrameXyz::from),
+        ));
 
         Ok(iter)
     }
 
-    macro_rules! declare_data_packet_to_frame_xyz_fn {
-        ($name:ident, $config:ident, $firing:ident, $frame:ident, $iter_fn:ident) => {
-            pub fn $name<I>(config: $config, packets: I) -> impl Iterator<Item = $frame> + Send
-            where
-                I: IntoIterator<Item = DataPacket>,
-                I::IntoIter: Send,
-            {
-                let batcher: Batcher<$firing> = Batcher::new();
-
-                packets
-                    .into_iter()
-                    .map(move |packet| {
-                        audit_format(packet.try_format(), config.format());
-
-                        let firings: Vec<$firing> = packet
-                            .$iter_fn()
-                            .map(|block| block.to_firing_xyz(&config))
-                            .collect();
-                        firings
-                    })
-                    .scan(batcher, |batcher, firings| {
-                        let frames: Vec<_> = batcher
-                            .push_many(firings)
-                            .map(|firings| $frame { firings })
-                            .collect();
-                        Some(frames)
-                    })
-                    .flatten()
-            }
-        };
+    /// Converts packets to frames of `LINES` lasers with `RETURNS`
+    /// returns.
+    pub fn data_packet_to_frame_xyz_n<const LINES: usize, const RETURNS: usize, I>(
+        config: ConfigN<LINES>,
+        packets: I,
+    ) -> impl Iterator<Item = FrameXyzN<LINES, RETURNS>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = DataPacket>,
+        I::IntoIter: Send,
+    {
+        data_packet_to_frame_xyz_n_with_strategy(config, AzimuthWrap, packets)
     }
 
-    declare_data_packet_to_frame_xyz_fn!(
-        data_packet_to_frame_xyz_s16,
-        Config16,
-        FiringXyzS16,
-        FrameXyzS16,
-        firing_block_iter_s16
-    );
-    declare_data_packet_to_frame_xyz_fn!(
-        data_packet_to_frame_xyz_s32,
-        Config32,
-        FiringXyzS32,
-        FrameXyzS32,
-        firing_block_iter_s32
-    );
-    declare_data_packet_to_frame_xyz_fn!(
-        data_packet_to_frame_xyz_d16,
-        Config16,
-        FiringXyzD16,
-        FrameXyzD16,
-        firing_block_iter_d16
-    );
-    declare_data_packet_to_frame_xyz_fn!(
-        data_packet_to_frame_xyz_d32,
-        Config32,
-        FiringXyzD32,
-        FrameXyzD32,
-        firing_block_iter_d32
-    );
+    /// Converts packets to frames, where frames are split by the
+    /// batching strategy.
+    pub fn data_packet_to_frame_xyz_n_with_strategy<
+        const LINES: usize,
+        const RETURNS: usize,
+        I,
+        S,
+    >(
+        config: ConfigN<LINES>,
+        strategy: S,
+        packets: I,
+    ) -> impl Iterator<Item = FrameXyzN<LINES, RETURNS>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = DataPacket>,
+        I::IntoIter: Send,
+        S: BatchStrategy<FiringXyzN<LINES, RETURNS>> + Send,
+    {
+        let batcher = Batcher::with_strategy(strategy).with_sensor_id(config.sensor_id);
+        let converter = ConverterN::<LINES, RETURNS>::new(&config);
+        let azimuth_windows = config.azimuth_windows.clone();
+
+        PacketLookahead::new(packets)
+            .scan(batcher, move |batcher, packet| {
+                let LookaheadPacket {
+                    packet,
+                    end_azimuth,
+                } = packet;
+                audit_format(packet.try_format(), converter.format());
+
+                let product_id = packet.product_id;
+                let firings = converter
+                    .firing_blocks_with_end_azimuth(&packet, end_azimuth)
+                    .map(|block| converter.firing_block_to_xyz(&block));
+                let frames: Vec<_> = batcher
+                    .push_packet(firings)
+                    .map(|batch| FrameXyzN {
+                        header: FrameHeader::from_batch(&batch, Some(product_id))
+                            .with_azimuth_windows(&azimuth_windows),
+                        firings: batch.items,
+                    })
+                    .collect();
+                Some(frames)
+            })
+            .flatten()
+    }
 }
 
 pub use try_packet_to_frame_xyz::*;
 mod try_packet_to_frame_xyz {
-    use super::audit_format;
+    use super::{audit_format, try_lookahead};
     use crate::{
-        batcher::Batcher,
+        batcher::{AzimuthWrap, BatchStrategy, BatchStrategyKind, Batcher, CutAngle},
+        converter::ConverterN,
         iter::convert::ResultFrameXyzIter,
+        packet::LookaheadPacket,
         types::{
-            firing_xyz::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-            format::FormatKind,
-            frame_xyz::{FrameXyzD16, FrameXyzD32, FrameXyzS16, FrameXyzS32},
+            firing_xyz::{FiringXyzFamily, FiringXyzN},
+            format::{dispatch, LayoutN, SupportedLayout},
+            frame_header::FrameHeader,
+            frame_xyz::{FrameXyz, FrameXyzN},
         },
-        Config, Config16, Config32, Packet,
+        Config, ConfigN, Packet,
     };
     use eyre::{format_err, Result};
     use itertools::Itertools;
+    use measurements::Angle;
 
     /// Converts an iterator of packets to an iterator of [crate::types::frame_xyz::FrameXyz].
     pub fn try_packet_to_frame_xyz<'a, E, I>(
@@ -155,104 +222,428 @@ mod try_packet_to_frame_xyz {
         I::IntoIter: Send,
         E: 'a,
     {
-        use FormatKind as K;
+        try_packet_to_frame_xyz_with_strategy(config, AzimuthWrap, packets)
+    }
 
-        let config_kinds = config
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz], where frames are split at
+    /// the cut angle.
+    pub fn try_packet_to_frame_xyz_with_cut_angle<'a, E, I>(
+        config: Config,
+        cut_angle: Angle,
+        packets: I,
+    ) -> Result<ResultFrameXyzIter<'a, E>>
+    where
+        I: IntoIterator<Item = Result<Packet, E>> + 'a,
+        I::IntoIter: Send,
+        E: 'a,
+    {
+        try_packet_to_frame_xyz_with_strategy(config, CutAngle::new(cut_angle), packets)
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz], where frames are split by
+    /// the batching strategy.
+    pub fn try_packet_to_frame_xyz_with_strategy<'a, E, I, S>(
+        config: Config,
+        strategy: S,
+        packets: I,
+    ) -> Result<ResultFrameXyzIter<'a, E>>
+    where
+        I: IntoIterator<Item = Result<Packet, E>> + 'a,
+        I::IntoIter: Send,
+        E: 'a,
+        S: BatchStrategyKind<FiringXyzFamily> + Send + 'a,
+    {
+        let config_kind = config
             .try_into_kind()
             .map_err(|_| format_err!("invalid configuration"))?;
 
-        let iter: ResultFrameXyzIter<_> = match config_kinds {
-            K::Single16(config) => {
-                Box::new(try_packet_to_frame_xyz_s16(config, packets).map_ok(K::from_s16))
-            }
-            K::Single32(config) => {
-                Box::new(try_packet_to_frame_xyz_s32(config, packets).map_ok(K::from_s32))
-            }
-            K::Dual16(config) => {
-                Box::new(try_packet_to_frame_xyz_d16(config, packets).map_ok(K::from_d16))
-            }
-            K::Dual32(config) => {
-                Box::new(try_packet_to_frame_xyz_d32(config, packets).map_ok(K::from_d32))
-            }
-        };
+        let iter: ResultFrameXyzIter<_> = dispatch!(config_kind, <LINES, RETURNS> config => {
+            Box::new(
+                try_packet_to_frame_xyz_n_with_strategy::<LINES, RETURNS, _, _, _>(
+                    config, strategy, packets,
+                )
+                .map_ok(FrameXyz::from),
+            )
+        });
 
         Ok(iter)
     }
 
-    macro_rules! declare_try_packet_to_frame_xyz_fn {
-        ($name:ident, $config:ident, $firing:ident, $frame:ident, $iter_fn:ident) => {
-            pub fn $name<E, I>(
-                config: $config,
-                packets: I,
-            ) -> impl Iterator<Item = Result<$frame, E>> + Send
-            where
-                I: IntoIterator<Item = Result<Packet, E>>,
-                I::IntoIter: Send,
-            {
-                let batcher: Batcher<$firing> = Batcher::new();
-
-                packets
-                    .into_iter()
-                    .map_ok(|packet| packet.try_into_data().ok())
-                    .flatten_ok()
-                    .map_ok(move |packet| {
-                        audit_format(packet.try_format(), config.format());
-
-                        let firings: Vec<$firing> = packet
-                            .$iter_fn()
-                            .map(|block| block.to_firing_xyz(&config))
-                            .collect();
-                        firings
+    /// Converts packets to frames of `LINES` lasers with `RETURNS`
+    /// returns.
+    pub fn try_packet_to_frame_xyz_n<const LINES: usize, const RETURNS: usize, E, I>(
+        config: ConfigN<LINES>,
+        packets: I,
+    ) -> impl Iterator<Item = Result<FrameXyzN<LINES, RETURNS>, E>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = Result<Packet, E>>,
+        I::IntoIter: Send,
+    {
+        try_packet_to_frame_xyz_n_with_strategy(config, AzimuthWrap, packets)
+    }
+
+    /// Converts packets to frames, where frames are split by the
+    /// batching strategy.
+    pub fn try_packet_to_frame_xyz_n_with_strategy<
+        const LINES: usize,
+        const RETURNS: usize,
+        E,
+        I,
+        S,
+    >(
+        config: ConfigN<LINES>,
+        strategy: S,
+        packets: I,
+    ) -> impl Iterator<Item = Result<FrameXyzN<LINES, RETURNS>, E>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = Result<Packet, E>>,
+        I::IntoIter: Send,
+        S: BatchStrategy<FiringXyzN<LINES, RETURNS>> + Send,
+    {
+        let batcher = Batcher::with_strategy(strategy).with_sensor_id(config.sensor_id);
+        let converter = ConverterN::<LINES, RETURNS>::new(&config);
+        let azimuth_windows = config.azimuth_windows.clone();
+
+        let packets = packets
+            .into_iter()
+            .map_ok(|packet| packet.try_into_data().ok())
+            .flatten_ok();
+
+        try_lookahead(packets)
+            .map_ok(move |packet| {
+                let LookaheadPacket {
+                    packet,
+                    end_azimuth,
+                } = packet;
+                audit_format(packet.try_format(), converter.format());
+
+                let firings: Vec<FiringXyzN<LINES, RETURNS>> = converter
+                    .firing_blocks_with_end_azimuth(&packet, end_azimuth)
+                    .map(|block| converter.firing_block_to_xyz(&block))
+                    .collect();
+                (packet.product_id, firings)
+            })
+            .scan(Some(batcher), move |batcher, firings| {
+                let (product_id, firings) = match firings {
+                    Ok(item) => item,
+                    Err(err) => {
+                        *batcher = None;
+                        return Some(Err(err));
+                    }
+                };
+                let Some(batcher) = batcher else {
+                    return None;
+                };
+
+                let frames: Vec<_> = batcher
+                    .push_packet(firings)
+                    .map(|batch| FrameXyzN {
+                        header: FrameHeader::from_batch(&batch, Some(product_id))
+                            .with_azimuth_windows(&azimuth_windows),
+                        firings: batch.items,
                     })
-                    .scan(Some(batcher), |batcher, firings| {
-                        let firings = match firings {
-                            Ok(firings) => firings,
-                            Err(err) => {
-                                *batcher = None;
-                                return Some(Err(err));
-                            }
-                        };
-                        let Some(batcher) = batcher else {
-                            return None;
-                        };
-
-                        let frames: Vec<_> = batcher
-                            .push_many(firings)
-                            .map(|firings| $frame { firings })
-                            .collect();
-                        Some(Ok(frames))
+                    .collect();
+                Some(Ok(frames))
+            })
+            .flatten_ok()
+    }
+}
+
+pub use data_packet_to_frame_raw::*;
+mod data_packet_to_frame_raw {
+    use super::{audit_format, FrameRawIter};
+    use crate::{
+        batcher::{AzimuthWrap, BatchStrategy, BatchStrategyKind, Batcher, CutAngle},
+        packet::PacketLookahead,
+        types::{
+            firing_raw::{FiringRawFamily, FiringRawN},
+            format::{dispatch_format, Format, LayoutN, SupportedLayout},
+            frame_header::FrameHeader,
+            frame_raw::{FrameRaw, FrameRawN},
+        },
+        DataPacket,
+    };
+    use measurements::Angle;
+
+    /// Converts an iterator of packets to an iterator of [crate::types::frame_raw::FrameRaw].
+    pub fn data_packet_to_frame_raw<'a, I>(format: Format, packets: I) -> FrameRawIter<'a>
+    where
+        I: IntoIterator<Item = DataPacket> + 'a,
+        I::IntoIter: Send,
+    {
+        data_packet_to_frame_raw_with_strategy(format, AzimuthWrap, packets)
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_raw::FrameRaw], where frames are split at
+    /// the cut angle.
+    pub fn data_packet_to_frame_raw_with_cut_angle<'a, I>(
+        format: Format,
+        cut_angle: Angle,
+        packets: I,
+    ) -> FrameRawIter<'a>
+    where
+        I: IntoIterator<Item = DataPacket> + 'a,
+        I::IntoIter: Send,
+    {
+        data_packet_to_frame_raw_with_strategy(format, CutAngle::new(cut_angle), packets)
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_raw::FrameRaw], where frames are split by
+    /// the batching strategy.
+    pub fn data_packet_to_frame_raw_with_strategy<'a, I, S>(
+        format: Format,
+        strategy: S,
+        packets: I,
+    ) -> FrameRawIter<'a>
+    where
+        I: IntoIterator<Item = DataPacket> + 'a,
+        I::IntoIter: Send,
+        S: BatchStrategyKind<FiringRawFamily> + Send + 'a,
+    {
+        dispatch_format!(format, <LINES, RETURNS> => Box::new(
+            data_packet_to_frame_raw_n_with_strategy::<LINES, RETURNS, _, _>(strategy, packets)
+                .map(FrameRaw::from),
+        ))
+    }
+
+    /// Converts packets to frames of `LINES` lasers with `RETURNS`
+    /// returns.
+    pub fn data_packet_to_frame_raw_n<const LINES: usize, const RETURNS: usize, I>(
+        packets: I,
+    ) -> impl Iterator<Item = FrameRawN<LINES, RETURNS>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = DataPacket>,
+        I::IntoIter: Send,
+    {
+        data_packet_to_frame_raw_n_with_strategy(AzimuthWrap, packets)
+    }
+
+    /// Converts packets to frames, where frames are split by the
+    /// batching strategy.
+    pub fn data_packet_to_frame_raw_n_with_strategy<
+        const LINES: usize,
+        const RETURNS: usize,
+        I,
+        S,
+    >(
+        strategy: S,
+        packets: I,
+    ) -> impl Iterator<Item = FrameRawN<LINES, RETURNS>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = DataPacket>,
+        I::IntoIter: Send,
+        S: BatchStrategy<FiringRawN<LINES, RETURNS>> + Send,
+    {
+        let batcher = Batcher::with_strategy(strategy);
+
+        PacketLookahead::new(packets)
+            .map(move |packet| {
+                audit_format(
+                    packet.packet.try_format(),
+                    LayoutN::<LINES, RETURNS>::FORMAT,
+                );
+
+                let firings: Vec<FiringRawN<LINES, RETURNS>> = packet
+                    .firing_block_iter_n()
+                    .map(|block| block.to_firing_raw())
+                    .collect();
+                (packet.packet.product_id, firings)
+            })
+            .scan(batcher, |batcher, (product_id, firings)| {
+                let frames: Vec<_> = batcher
+                    .push_packet(firings)
+                    .map(|batch| FrameRawN {
+                        header: FrameHeader::from_batch(&batch, Some(product_id)),
+                        firings: batch.items,
                     })
-                    .flatten_ok()
-            }
-        };
+                    .collect();
+                Some(frames)
+            })
+            .flatten()
+    }
+}
+
+pub use try_packet_to_frame_raw::*;
+mod try_packet_to_frame_raw {
+    use super::{audit_format, try_lookahead, ResultFrameRawIter};
+    use crate::{
+        batcher::{AzimuthWrap, BatchStrategy, BatchStrategyKind, Batcher, CutAngle},
+        types::{
+            firing_raw::{FiringRawFamily, FiringRawN},
+            format::{dispatch_format, Format, LayoutN, SupportedLayout},
+            frame_header::FrameHeader,
+            frame_raw::{FrameRaw, FrameRawN},
+        },
+        Packet,
+    };
+    use itertools::Itertools;
+    use measurements::Angle;
+
+    /// Converts an iterator of packets to an iterator of [crate::types::frame_raw::FrameRaw].
+    pub fn try_packet_to_frame_raw<'a, E, I>(
+        format: Format,
+        packets: I,
+    ) -> ResultFrameRawIter<'a, E>
+    where
+        I: IntoIterator<Item = Result<Packet, E>> + 'a,
+        I::IntoIter: Send,
+        E: 'a,
+    {
+        try_packet_to_frame_raw_with_strategy(format, AzimuthWrap, packets)
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_raw::FrameRaw], where frames are split at
+    /// the cut angle.
+    pub fn try_packet_to_frame_raw_with_cut_angle<'a, E, I>(
+        format: Format,
+        cut_angle: Angle,
+        packets: I,
+    ) -> ResultFrameRawIter<'a, E>
+    where
+        I: IntoIterator<Item = Result<Packet, E>> + 'a,
+        I::IntoIter: Send,
+        E: 'a,
+    {
+        try_packet_to_frame_raw_with_strategy(format, CutAngle::new(cut_angle), packets)
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_raw::FrameRaw], where frames are split by
+    /// the batching strategy.
+    pub fn try_packet_to_frame_raw_with_strategy<'a, E, I, S>(
+        format: Format,
+        strategy: S,
+        packets: I,
+    ) -> ResultFrameRawIter<'a, E>
+    where
+        I: IntoIterator<Item = Result<Packet, E>> + 'a,
+        I::IntoIter: Send,
+        E: 'a,
+        S: BatchStrategyKind<FiringRawFamily> + Send + 'a,
+    {
+        dispatch_format!(format, <LINES, RETURNS> => Box::new(
+            try_packet_to_frame_raw_n_with_strategy::<LINES, RETURNS, _, _, _>(strategy, packets)
+                .map_ok(FrameRaw::from),
+        ))
+    }
+
+    /// Converts packets to frames of `LINES` lasers with `RETURNS`
+    /// returns.
+    pub fn try_packet_to_frame_raw_n<const LINES: usize, const RETURNS: usize, E, I>(
+        packets: I,
+    ) -> impl Iterator<Item = Result<FrameRawN<LINES, RETURNS>, E>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = Result<Packet, E>>,
+        I::IntoIter: Send,
+    {
+        try_packet_to_frame_raw_n_with_strategy(AzimuthWrap, packets)
+    }
+
+    /// Converts packets to frames, where frames are split by the
+    /// batching strategy.
+    pub fn try_packet_to_frame_raw_n_with_strategy<
+        const LINES: usize,
+        const RETURNS: usize,
+        E,
+        I,
+        S,
+    >(
+        strategy: S,
+        packets: I,
+    ) -> impl Iterator<Item = Result<FrameRawN<LINES, RETURNS>, E>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = Result<Packet, E>>,
+        I::IntoIter: Send,
+        S: BatchStrategy<FiringRawN<LINES, RETURNS>> + Send,
+    {
+        let batcher = Batcher::with_strategy(strategy);
+
+        let packets = packets
+            .into_iter()
+            .map_ok(|packet| packet.try_into_data().ok())
+            .flatten_ok();
+
+        try_lookahead(packets)
+            .map_ok(move |packet| {
+                audit_format(
+                    packet.packet.try_format(),
+                    LayoutN::<LINES, RETURNS>::FORMAT,
+                );
+
+                let firings: Vec<FiringRawN<LINES, RETURNS>> = packet
+                    .firing_block_iter_n()
+                    .map(|block| block.to_firing_raw())
+                    .collect();
+                (packet.packet.product_id, firings)
+            })
+            .scan(Some(batcher), |batcher, firings| {
+                let (product_id, firings) = match firings {
+                    Ok(item) => item,
+                    Err(err) => {
+                        *batcher = None;
+                        return Some(Err(err));
+                    }
+                };
+                let Some(batcher) = batcher else {
+                    return None;
+                };
+
+                let frames: Vec<_> = batcher
+                    .push_packet(firings)
+                    .map(|batch| FrameRawN {
+                        header: FrameHeader::from_batch(&batch, Some(product_id)),
+                        firings: batch.items,
+                    })
+                    .collect();
+                Some(Ok(frames))
+            })
+            .flatten_ok()
     }
+}
 
-    declare_try_packet_to_frame_xyz_fn!(
-        try_packet_to_frame_xyz_s16,
-        Config16,
-        FiringXyzS16,
-        FrameXyzS16,
-        firing_block_iter_s16
-    );
-    declare_try_packet_to_frame_xyz_fn!(
-        try_packet_to_frame_xyz_s32,
-        Config32,
-        FiringXyzS32,
-        FrameXyzS32,
-        firing_block_iter_s32
-    );
-    declare_try_packet_to_frame_xyz_fn!(
-        try_packet_to_frame_xyz_d16,
-        Config16,
-        FiringXyzD16,
-        FrameXyzD16,
-        firing_block_iter_d16
-    );
-    declare_try_packet_to_frame_xyz_fn!(
-        try_packet_to_frame_xyz_d32,
-        Config32,
-        FiringXyzD32,
-        FrameXyzD32,
-        firing_block_iter_d32
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{packet::ProductID, test_utils::PacketBuilder, Config, Packet, ReturnMode};
+    use std::rc::Rc;
+
+    #[test]
+    fn try_lookahead_test() {
+        let builder = PacketBuilder::new(ProductID::VLP16, ReturnMode::Strongest);
+        let packet = |index: u32| builder.packet(index);
+        // errors need not be Send
+        let packets = (0..4).map(|index| match index {
+            2 => Err(Rc::new(())),
+            3 => Ok(packet(2)),
+            _ => Ok(packet(index)),
+        });
+        let outputs: Vec<_> = try_lookahead(packets).collect();
+
+        // the error passes the packet held back, which still ends where
+        // the next packet starts
+        assert_eq!(outputs.len(), 4);
+        let end = |index: usize| {
+            let output = outputs[index].as_ref().unwrap();
+            output.end_azimuth.unwrap().as_degrees()
+        };
+        assert!((end(0) - 2.4).abs() < 1e-9);
+        assert!(outputs[1].is_err());
+        assert!((end(2) - 4.8).abs() < 1e-9);
+        assert_eq!(outputs[3].as_ref().unwrap().packet, packet(2));
+
+        let packets = iter::empty::<Result<Packet, Rc<()>>>();
+        let frames = try_packet_to_frame_xyz(Config::new_vlp_16_strongest(), packets).unwrap();
+        assert_eq!(frames.count(), 0);
+    }
 }
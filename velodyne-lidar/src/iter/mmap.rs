@@ -0,0 +1,207 @@
+//! Zero-copy reading of memory-mapped capture files.
+
+use super::UDP_HEADER_SIZE;
+use crate::{
+    packet::{PacketRef, ParseError},
+    DataPacket,
+};
+use memmap2::Mmap;
+use std::{fs::File, io, iter, path::Path};
+
+const GLOBAL_HEADER_SIZE: usize = 24;
+const RECORD_HEADER_SIZE: usize = 16;
+const LINKTYPE_ETHERNET: u32 = 1;
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const IPPROTO_UDP: u8 = 17;
+
+/// A pcap capture file mapped into memory.
+///
+/// The packets are borrowed from the mapping without copying. Only the
+/// classic pcap format with Ethernet frames is supported.
+#[derive(Debug)]
+pub struct MmapCapture {
+    mmap: Mmap,
+    big_endian: bool,
+}
+
+impl MmapCapture {
+    /// Maps a capture file into memory.
+    ///
+    /// The file must not be modified while it is mapped.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        Self::from_mmap(mmap)
+    }
+
+    /// Checks the header of a mapped capture file.
+    pub fn from_mmap(mmap: Mmap) -> io::Result<Self> {
+        let header = mmap
+            .get(..GLOBAL_HEADER_SIZE)
+            .ok_or_else(|| invalid_data("truncated pcap header"))?;
+
+        // microsecond and nanosecond timestamp magic numbers
+        let big_endian = match read_u32(header, 0, false) {
+            0xa1b2c3d4 | 0xa1b23c4d => false,
+            0xd4c3b2a1 | 0x4d3cb2a1 => true,
+            _ => return Err(invalid_data("not a pcap file")),
+        };
+        let link_type = read_u32(header, 20, big_endian) & 0xffff;
+        if link_type != LINKTYPE_ETHERNET {
+            return Err(invalid_data("expect Ethernet frames in the capture"));
+        }
+
+        Ok(Self { mmap, big_endian })
+    }
+
+    /// Iterates over the UDP payloads in the capture.
+    ///
+    /// Records other than IPv4 UDP datagrams are skipped. The iterator
+    /// stops after reporting a truncated record.
+    pub fn payload_iter(&self) -> impl Iterator<Item = io::Result<&[u8]>> + Clone + Send + '_ {
+        let big_endian = self.big_endian;
+        let mut remaining = &self.mmap[GLOBAL_HEADER_SIZE..];
+
+        iter::from_fn(move || loop {
+            if remaining.is_empty() {
+                return None;
+            }
+
+            let Some(header) = remaining.get(..RECORD_HEADER_SIZE) else {
+                remaining = &[];
+                return Some(Err(invalid_data("truncated pcap record header")));
+            };
+            let len = read_u32(header, 8, big_endian) as usize;
+            let Some(frame) = remaining.get(RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + len) else {
+                remaining = &[];
+                return Some(Err(invalid_data("truncated pcap record")));
+            };
+            remaining = &remaining[RECORD_HEADER_SIZE + len..];
+
+            if let Some(payload) = udp_payload(frame) {
+                return Some(Ok(payload));
+            }
+        })
+    }
+
+    /// Iterates over the packets in the capture without copying.
+    ///
+    /// Payloads of other sizes are skipped. A payload of a packet size with
+    /// an invalid field is reported as an error.
+    pub fn packet_iter(&self) -> impl Iterator<Item = io::Result<PacketRef<'_>>> + Clone + Send {
+        self.payload_iter().filter_map(|payload| match payload {
+            Ok(payload) => match PacketRef::from_slice(payload) {
+                Ok(packet) => Some(Ok(packet)),
+                Err(ParseError::UnknownPacket) => None,
+                Err(err) => Some(Err(io::Error::new(io::ErrorKind::InvalidData, err))),
+            },
+            Err(err) => Some(Err(err)),
+        })
+    }
+
+    /// Iterates over the data packets in the capture without copying.
+    pub fn data_packet_iter(&self) -> impl Iterator<Item = io::Result<&DataPacket>> + Clone + Send {
+        self.packet_iter().filter_map(|packet| match packet {
+            Ok(packet) => packet.as_data().map(Ok),
+            Err(err) => Some(Err(err)),
+        })
+    }
+}
+
+fn udp_payload(frame: &[u8]) -> Option<&[u8]> {
+    let ethertype = u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]);
+    let protocol = *frame.get(23)?;
+    if ethertype != ETHERTYPE_IPV4 || protocol != IPPROTO_UDP {
+        return None;
+    }
+    frame.get(UDP_HEADER_SIZE..)
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let bytes: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+    if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{converter::ConverterN, Config32, Packet};
+    use std::{fs, mem};
+
+    #[test]
+    fn mmap_capture_test() {
+        let path = "tests/velodyne_vlp32.pcap";
+        let capture = MmapCapture::open(path).unwrap();
+
+        let packets: Vec<_> = capture.packet_iter().collect::<io::Result<_>>().unwrap();
+        let data_packets: Vec<_> = capture
+            .data_packet_iter()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert!(!data_packets.is_empty());
+
+        // the packets borrow the mapped file
+        let range = capture.mmap.as_ptr_range();
+        assert!(data_packets
+            .iter()
+            .all(|&packet| range.contains(&(packet as *const DataPacket as *const u8))));
+        let owned = Packet::from_slice(capture.payload_iter().next().unwrap().unwrap()).unwrap();
+        assert_eq!(packets[0].to_packet(), owned);
+
+        // firings decoded from the borrowed packets
+        let converter = ConverterN::<32, 1>::new(&Config32::new_vlp_32c_strongest());
+        let mut firings = vec![];
+        for &packet in &data_packets {
+            converter.packet_to_firings(packet, &mut firings).unwrap();
+        }
+        assert_eq!(firings.len(), data_packets.len() * 12);
+
+        // a truncated file reports an error after the complete records
+        let bytes = fs::read(path).unwrap();
+        let truncated = std::env::temp_dir().join("velodyne_lidar_mmap_capture_test.pcap");
+        fs::write(&truncated, &bytes[..bytes.len() - 100]).unwrap();
+        let capture = MmapCapture::open(&truncated).unwrap();
+        let results: Vec<_> = capture.packet_iter().collect();
+        fs::remove_file(&truncated).unwrap();
+
+        assert_eq!(results.len(), packets.len());
+        assert!(results.last().unwrap().is_err());
+        assert!(results[..results.len() - 1]
+            .iter()
+            .all(|result| result.is_ok()));
+    }
+
+    #[test]
+    fn mmap_malformed_payload_test() {
+        let path = "tests/velodyne_vlp32.pcap";
+        let capture = MmapCapture::open(path).unwrap();
+        let num_packets = capture.packet_iter().count();
+        let payload = capture.payload_iter().next().unwrap().unwrap();
+        let offset = payload.as_ptr() as usize - capture.mmap.as_ptr() as usize;
+
+        // overwrite the product ID of the first packet
+        let mut bytes = fs::read(path).unwrap();
+        bytes[offset + mem::offset_of!(DataPacket, product_id)] = 0xff;
+        let malformed = std::env::temp_dir().join("velodyne_lidar_mmap_malformed_test.pcap");
+        fs::write(&malformed, &bytes).unwrap();
+        let capture = MmapCapture::open(&malformed).unwrap();
+        let results: Vec<_> = capture.packet_iter().collect();
+        fs::remove_file(&malformed).unwrap();
+
+        assert_eq!(results.len(), num_packets);
+        let err = results[0].as_ref().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(results[1..].iter().all(|result| result.is_ok()));
+    }
+}
@@ -1,13 +1,20 @@
 //! Packet iterator creation functions.
 
-use super::convert::{try_packet_to_frame_xyz, ResultFrameXyzIter};
-use crate::{Config, Packet};
+use super::{
+    convert::{
+        try_packet_to_frame_raw, try_packet_to_frame_xyz, ResultFrameRawIter, ResultFrameXyzIter,
+    },
+    UDP_HEADER_SIZE,
+};
+use crate::{
+    stats::{StreamAnalyzer, StreamReport},
+    types::format::Format,
+    Config, Packet,
+};
 use eyre::Result;
 use pcap::{Capture, Device};
 use std::{iter, path::Path};
 
-const UDP_HEADER_SIZE: usize = 42;
-
 /// Creates a packet iterator from [pcap::Capture].
 pub fn packet_iter_from_capture<A>(
     mut capture: Capture<A>,
@@ -92,3 +99,63 @@ where
     let capture = capture.open()?;
     frame_xyz_iter_from_capture(config, capture)
 }
+
+pub fn frame_raw_iter_from_capture<A>(
+    format: Format,
+    capture: Capture<A>,
+) -> Result<ResultFrameRawIter<'static, pcap::Error>>
+where
+    A: pcap::Activated + 'static,
+{
+    let packets = packet_iter_from_capture(capture)?;
+    let iter = try_packet_to_frame_raw(format, packets);
+    Ok(iter)
+}
+
+pub fn frame_raw_iter_from_file<P>(
+    format: Format,
+    path: P,
+) -> Result<ResultFrameRawIter<'static, pcap::Error>>
+where
+    P: AsRef<Path>,
+{
+    let capture: Capture<pcap::Offline> = Capture::from_file(path)?;
+    frame_raw_iter_from_capture(format, capture)
+}
+
+pub fn frame_raw_iter_from_device<D>(
+    format: Format,
+    device: D,
+) -> Result<ResultFrameRawIter<'static, pcap::Error>>
+where
+    D: Into<Device>,
+{
+    let capture: Capture<pcap::Inactive> = Capture::from_device(device)?;
+    let capture = capture.open()?;
+    frame_raw_iter_from_capture(format, capture)
+}
+
+/// Analyzes the packet stream quality of a capture.
+pub fn stream_report_from_capture<A>(capture: Capture<A>) -> Result<StreamReport, pcap::Error>
+where
+    A: pcap::Activated,
+{
+    let mut analyzer = StreamAnalyzer::new();
+
+    for packet in packet_iter_from_capture(capture)? {
+        if let Some(packet) = packet?.as_data() {
+            analyzer.push(packet);
+        }
+    }
+
+    Ok(analyzer.finish())
+}
+
+/// Analyzes the packet stream quality of a capture file.
+pub fn stream_report_from_file<P>(path: P) -> Result<StreamReport, pcap::Error>
+where
+    P: AsRef<Path>,
+{
+    let capture: Capture<pcap::Offline> = Capture::from_file(path)?;
+    stream_report_from_capture(capture)
+}
@@ -3,6 +3,7 @@
 //! # Example
 //!
 //! ```rust
+//! # #[cfg(feature = "pcap")]
 //! # fn main() -> eyre::Result<()> {
 //! use velodyne_lidar::{types::measurements::Measurement, Config};
 //!
@@ -20,29 +21,64 @@
 //!                 distance,
 //!                 intensity,
 //!                 xyz: [x, y, z],
-//!             } = point.measurement;
+//!                 ..
+//!             } = point.measurement();
 //!             print!("dist: {distance}\t");
-//!             print!("int: {distance}\t");
+//!             print!("int: {intensity}\t");
 //!             println!("xyz: {x} {y} {z}");
 //!         }
 //!     }
 //! }
 //! # Ok(())
 //! # }
+//! # #[cfg(not(feature = "pcap"))]
+//! # fn main() {}
 //! ```
+//!
+//! # `no_std` support
+//!
+//! Disabling the default `std` feature leaves the packet parsing and
+//! firing splitting, which build with `no_std` and `alloc`. The
+//! configuration, conversion and iterator APIs require `std`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "std")]
 pub mod batcher;
+#[cfg(feature = "std")]
 pub mod config;
 pub mod consts;
+#[cfg(feature = "std")]
 mod convert;
+#[cfg(feature = "std")]
+pub mod converter;
+#[cfg(feature = "std")]
+pub mod decoder;
+#[cfg(feature = "std")]
+pub mod deskew;
+#[cfg(feature = "std")]
+pub mod dual;
+#[cfg(feature = "std")]
 pub mod iter;
 pub mod packet;
 #[cfg(feature = "parallel")]
 pub mod par_iter;
+#[cfg(feature = "std")]
+pub mod reorder;
+#[cfg(feature = "std")]
+pub mod stats;
+#[cfg(test)]
+mod test_utils;
+pub mod timing;
 pub mod traits;
+#[cfg(feature = "std")]
+pub mod transform;
 pub mod types;
 mod utils;
 
+#[cfg(feature = "std")]
 pub use config::*;
 pub use packet::{DataPacket, Packet, PositionPacket, ProductID, ReturnMode};
 
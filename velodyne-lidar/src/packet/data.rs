@@ -1,25 +1,35 @@
+use super::ParseError;
 use crate::{
     consts::{AZIMUTH_COUNT_PER_REV, BLOCKS_PER_PACKET, CHANNELS_PER_BLOCK, FIRING_PERIOD},
+    timing::TimingModel,
     types::{
-        channel_array::ChannelArrayDRef,
-        firing_block::{FiringBlockD16, FiringBlockD32, FiringBlockS16, FiringBlockS32},
-        firing_xyz::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-        format::{Format, FormatKind},
+        firing_block::{FiringBlock, FiringBlockN},
+        format::{
+            dispatch, dispatch_format, map_kind, Format, FormatKind, Layout, LayoutFamily, LayoutN,
+            SupportedLayout,
+        },
     },
     utils::AngleExt as _,
-    Config16, Config32,
 };
-use eyre::{ensure, Result};
-use itertools::{chain, izip, Itertools as _};
+use core::{f64::consts::PI, marker::PhantomData, mem, time::Duration};
 use measurements::Angle;
-use std::{f64::consts::PI, iter, mem, time::Duration};
 
-/// Represents the block index in range from 0 to 31, or from 32 to 63.
+/// Represents the bank of lasers that fired the block.
+///
+/// The banks above 63 only exist on VLS-128.
 #[repr(u16)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BlockIdentifier {
     Block0To31 = 0xeeff,
     Block32To63 = 0xddff,
+    Block64To95 = 0xccff,
+    Block96To127 = 0xbbff,
+}
+
+impl BlockIdentifier {
+    fn is_valid(value: u16) -> bool {
+        matches!(value, 0xeeff | 0xddff | 0xccff | 0xbbff)
+    }
 }
 
 /// Represents the way the sensor measures the laser signal.
@@ -39,6 +49,10 @@ impl ReturnMode {
     pub fn is_dual(&self) -> bool {
         *self == Self::Dual
     }
+
+    fn is_valid(value: u8) -> bool {
+        matches!(value, 0x37..=0x39)
+    }
 }
 
 /// Represents the hardware model.
@@ -57,16 +71,22 @@ pub enum ProductID {
 }
 
 impl ProductID {
-    pub fn num_lines(&self) -> usize {
-        match self {
-            Self::HDL32E => 16,
+    /// Number of lasers, or `None` for Velarray whose packet layout is not
+    /// supported.
+    pub fn num_lines(&self) -> Option<usize> {
+        Some(match self {
+            Self::HDL32E => 32,
             Self::VLP16 => 16,
             Self::PuckLite => 16,
             Self::PuckHiRes => 16,
             Self::VLP32C => 32,
-            Self::Velarray => todo!(),
+            Self::Velarray => return None,
             Self::VLS128 => 128,
-        }
+        })
+    }
+
+    fn is_valid(value: u8) -> bool {
+        matches!(value, 0x21..=0x24 | 0x28 | 0x31 | 0xa1)
     }
 }
 
@@ -121,23 +141,60 @@ pub struct DataPacket {
 }
 
 impl DataPacket {
-    /// Construct packet from binary buffer.
-    pub fn from_bytes(buffer: [u8; mem::size_of::<Self>()]) -> Self {
-        unsafe { mem::transmute::<_, Self>(buffer) }
+    /// Construct packet from binary buffer. Fail if an enum field has an
+    /// unknown value.
+    pub fn from_bytes(buffer: [u8; mem::size_of::<Self>()]) -> Result<Self, ParseError> {
+        Self::from_slice(&buffer).copied()
     }
 
-    /// Construct packet from slice of bytes. Fail if the slice size is not correct.
-    pub fn from_slice(buffer: &[u8]) -> Result<&Self> {
-        ensure!(
-            buffer.len() == mem::size_of::<Self>(),
-            "Requre the slice length to be {}, but get {}",
-            mem::size_of::<Self>(),
-            buffer.len(),
-        );
+    /// Construct packet from slice of bytes. Fail if the slice size is not
+    /// correct or an enum field has an unknown value.
+    pub fn from_slice(buffer: &[u8]) -> Result<&Self, ParseError> {
+        if buffer.len() != mem::size_of::<Self>() {
+            return Err(ParseError::SizeMismatch {
+                expect: mem::size_of::<Self>(),
+                found: buffer.len(),
+            });
+        }
+        Self::check_fields(buffer)?;
+
+        // SAFETY: the size matches, the alignment is 1 and every enum
+        // field holds a valid discriminant.
         let packet = unsafe { &*(buffer.as_ptr() as *const Self) };
         Ok(packet)
     }
 
+    fn check_fields(buffer: &[u8]) -> Result<(), ParseError> {
+        for nth in 0..BLOCKS_PER_PACKET {
+            let offset = nth * mem::size_of::<Block>() + mem::offset_of!(Block, block_identifier);
+            let value = u16::from_ne_bytes([buffer[offset], buffer[offset + 1]]);
+            if !BlockIdentifier::is_valid(value) {
+                return Err(ParseError::InvalidField {
+                    field: "block_identifier",
+                    offset,
+                });
+            }
+        }
+
+        let offset = mem::offset_of!(Self, return_mode);
+        if !ReturnMode::is_valid(buffer[offset]) {
+            return Err(ParseError::InvalidField {
+                field: "return_mode",
+                offset,
+            });
+        }
+
+        let offset = mem::offset_of!(Self, product_id);
+        if !ProductID::is_valid(buffer[offset]) {
+            return Err(ParseError::InvalidField {
+                field: "product_id",
+                offset,
+            });
+        }
+
+        Ok(())
+    }
+
     pub fn toh(&self) -> Duration {
         Duration::from_micros(self.toh as u64)
     }
@@ -146,272 +203,224 @@ impl DataPacket {
         Format::try_from_model(self.product_id, self.return_mode)
     }
 
+    /// Duration of a firing sequence of the sensor model.
+    pub fn firing_period(&self) -> Duration {
+        TimingModel::from_product_id(self.product_id)
+            .map_or(FIRING_PERIOD, |model| model.firing_period())
+    }
+
     pub fn format(&self) -> Format {
         Format::from_model(self.product_id, self.return_mode)
     }
 
-    pub fn firing_block_iter(
-        &self,
-    ) -> FormatKind<
-        impl Iterator<Item = FiringBlockS16<'_>> + Clone,
-        impl Iterator<Item = FiringBlockS32<'_>> + Clone,
-        impl Iterator<Item = FiringBlockD16<'_>> + Clone,
-        impl Iterator<Item = FiringBlockD32<'_>> + Clone,
-    > {
-        use Format::*;
-        use FormatKind as F;
-
-        match self.format() {
-            Single16 => F::from_s16(self.firing_block_iter_s16()),
-            Dual16 => F::from_d16(self.firing_block_iter_d16()),
-            Single32 => F::from_s32(self.firing_block_iter_s32()),
-            Dual32 => F::from_d32(self.firing_block_iter_d32()),
-        }
+    pub fn firing_block_iter(&self) -> FiringBlockIter<'_> {
+        dispatch_format!(self.format(), <LINES, RETURNS> => {
+            self.firing_block_iter_n::<LINES, RETURNS>().into()
+        })
     }
 
-    pub fn firing_block_iter_s16(
+    /// Iterates over the firings of `LINES` lasers with `RETURNS`
+    /// returns. The layout is not checked against the packet.
+    pub fn firing_block_iter_n<const LINES: usize, const RETURNS: usize>(
         &self,
-    ) -> impl Iterator<Item = FiringBlockS16<'_>> + Clone + Sync + Send {
-        let block_period = FIRING_PERIOD.mul_f64(2.0);
-        let tohs = iter::successors(Some(self.toh()), move |prev| Some(*prev + block_period));
-        let firing_azimuths = {
-            let block_azimuths: Vec<_> = self.blocks.iter().map(|block| block.azimuth()).collect();
-            let block_azimuth_diffs: Vec<_> = block_azimuths
-                .iter()
-                .cloned()
-                .tuple_windows()
-                .map(|(curr, next)| (next - curr).wrap_to_2pi())
-                .collect();
-            let last_block_azimuth_diff = *block_azimuth_diffs.last().unwrap();
-
-            izip!(
-                block_azimuths,
-                chain!(block_azimuth_diffs, [last_block_azimuth_diff])
-            )
-            .map(|(block_azimuth, block_azimuth_diff)| {
-                let mid_azimuth = block_azimuth + block_azimuth_diff / 2.0;
-                let last_azimuth = block_azimuth + block_azimuth_diff;
-                [block_azimuth..mid_azimuth, mid_azimuth..last_azimuth]
-            })
-        };
-
-        izip!(tohs, firing_azimuths, &self.blocks).flat_map(
-            move |(block_toh, [former_azimuth, latter_azimuth], block)| {
-                let former_toh = block_toh;
-                let latter_toh = former_toh + FIRING_PERIOD;
-
-                let (former_channels, latter_channels) = block.channels.split_at(16);
-
-                let former = FiringBlockS16 {
-                    toh: former_toh,
-                    azimuth_range: former_azimuth,
-                    block,
-                    channels: former_channels
-                        .try_into()
-                        .unwrap_or_else(|_| unreachable!()),
-                };
-                let latter = FiringBlockS16 {
-                    toh: latter_toh,
-                    azimuth_range: latter_azimuth,
-                    block,
-                    channels: latter_channels
-                        .try_into()
-                        .unwrap_or_else(|_| unreachable!()),
-                };
-
-                [former, latter]
-            },
-        )
+    ) -> FiringBlockIterN<'_, LINES, RETURNS>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        self.firing_block_iter_n_with_end_azimuth(None)
     }
 
-    pub fn firing_block_iter_d16(
+    /// Iterates over the firings, where the last block ends at
+    /// `end_azimuth`, typically the first azimuth of the next packet.
+    /// The last block reuses the azimuth difference of the previous
+    /// block if it is missing.
+    pub fn firing_block_iter_n_with_end_azimuth<const LINES: usize, const RETURNS: usize>(
         &self,
-    ) -> impl Iterator<Item = FiringBlockD16<'_>> + Clone + Sync + Send {
-        let block_period = FIRING_PERIOD.mul_f64(2.0);
-        let tohs = iter::successors(Some(self.toh()), move |prev| Some(*prev + block_period));
-        let firing_azimuths = {
-            let block_azimuths: Vec<_> = self
-                .blocks
-                .iter()
-                .step_by(2)
-                .map(|block| block.azimuth())
-                .collect();
-            let block_azimuth_diffs: Vec<_> = block_azimuths
-                .iter()
-                .cloned()
-                .tuple_windows()
-                .map(|(curr, next)| (next - curr).wrap_to_2pi())
-                .collect();
-            let last_block_azimuth_diff = *block_azimuth_diffs.last().unwrap();
-
-            izip!(
-                block_azimuths,
-                chain!(block_azimuth_diffs, [last_block_azimuth_diff])
-            )
-            .map(|(block_azimuth, block_azimuth_diff)| {
-                let mid_azimuth = block_azimuth + block_azimuth_diff / 2.0;
-                let last_azimuth = block_azimuth + block_azimuth_diff;
-                [block_azimuth..mid_azimuth, mid_azimuth..last_azimuth]
-            })
-        };
-
-        izip!(tohs, firing_azimuths, self.blocks.chunks(2)).flat_map(
-            |(block_toh, [former_azimuth, latter_azimuth], block_pair)| {
-                let [block_strongest, block_last] = match block_pair {
-                    [first, second] => [first, second],
-                    _ => unreachable!(),
-                };
-
-                let former_toh = block_toh;
-                let latter_toh = former_toh + FIRING_PERIOD;
-
-                let (former_strongest, latter_strongest) = block_strongest.channels.split_at(16);
-                let (former_last, latter_last) = block_last.channels.split_at(16);
-
-                [
-                    FiringBlockD16 {
-                        toh: former_toh,
-                        azimuth_range: former_azimuth,
-                        block_strongest,
-                        block_last,
-                        channels: ChannelArrayDRef {
-                            strongest: former_strongest
-                                .try_into()
-                                .unwrap_or_else(|_| unreachable!()),
-                            last: former_last.try_into().unwrap_or_else(|_| unreachable!()),
-                        },
-                    },
-                    FiringBlockD16 {
-                        toh: latter_toh,
-                        azimuth_range: latter_azimuth,
-                        block_strongest,
-                        block_last,
-                        channels: ChannelArrayDRef {
-                            strongest: latter_strongest
-                                .try_into()
-                                .unwrap_or_else(|_| unreachable!()),
-                            last: latter_last.try_into().unwrap_or_else(|_| unreachable!()),
-                        },
-                    },
-                ]
-            },
-        )
+        end_azimuth: Option<Angle>,
+    ) -> FiringBlockIterN<'_, LINES, RETURNS>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        FiringBlockIterN {
+            packet: self,
+            end_azimuth,
+            firing_period: self.firing_period(),
+            index: 0,
+        }
     }
+}
 
-    pub fn firing_block_iter_s32(
-        &self,
-    ) -> impl Iterator<Item = FiringBlockS32<'_>> + Clone + Sync + Send {
-        let tohs = iter::successors(Some(self.toh()), move |prev| Some(*prev + FIRING_PERIOD));
-        let azimuths = {
-            let block_azimuths: Vec<_> = self.blocks.iter().map(|block| block.azimuth()).collect();
-            let block_azimuth_diffs: Vec<_> = block_azimuths
-                .iter()
-                .cloned()
-                .tuple_windows()
-                .map(|(curr, next)| (next - curr).wrap_to_2pi())
-                .collect();
-            let last_block_azimuth_diff = *block_azimuth_diffs.last().unwrap();
-
-            izip!(
-                block_azimuths,
-                chain!(block_azimuth_diffs, [last_block_azimuth_diff])
-            )
-            .map(|(former_azimuth, azimuth_diff)| {
-                let latter_azimuth = former_azimuth + azimuth_diff;
-                former_azimuth..latter_azimuth
-            })
-        };
-
-        izip!(tohs, azimuths, &self.blocks).map(move |(block_toh, azimuth_range, block)| {
-            let former_toh = block_toh;
-            let latter_toh = former_toh + FIRING_PERIOD;
-
-            FiringBlockS32 {
-                toh: latter_toh,
-                azimuth_range,
-                block,
-                channels: &block.channels,
-            }
+/// An iterator over the firings of `LINES` lasers with `RETURNS` returns
+/// in a data packet.
+///
+/// The firings of a group of blocks share the azimuth range from the
+/// group azimuth to the azimuth of the next group evenly.
+#[derive(Debug, Clone)]
+pub struct FiringBlockIterN<'a, const LINES: usize, const RETURNS: usize> {
+    packet: &'a DataPacket,
+    end_azimuth: Option<Angle>,
+    firing_period: Duration,
+    index: usize,
+}
+
+impl<'a, const LINES: usize, const RETURNS: usize> FiringBlockIterN<'a, LINES, RETURNS> {
+    const LAYOUT: Layout = LayoutN::<LINES, RETURNS>::LAYOUT;
+
+    fn group_blocks(&self, group: usize) -> &'a [Block] {
+        let blocks_per_group = Self::LAYOUT.blocks_per_group();
+        let start = group * blocks_per_group;
+        &self.packet.blocks[start..start + blocks_per_group]
+    }
+
+    /// Computes the azimuth difference from the group to the next group.
+    /// The last group ends at the end azimuth if given, or reuses the
+    /// difference of the previous group.
+    fn group_azimuth_diff(&self, group: usize) -> Angle {
+        let curr = self.group_blocks(group)[0].azimuth();
+
+        if group + 1 < Self::LAYOUT.num_groups() {
+            let next = self.group_blocks(group + 1)[0].azimuth();
+            (next - curr).wrap_to_2pi()
+        } else if let Some(next) = self.end_azimuth {
+            (next - curr).wrap_to_2pi()
+        } else if group > 0 {
+            let prev = self.group_blocks(group - 1)[0].azimuth();
+            (curr - prev).wrap_to_2pi()
+        } else {
+            // a packet of a single group spans no azimuth by itself
+            Angle::default()
+        }
+    }
+}
+
+impl<'a, const LINES: usize, const RETURNS: usize> Iterator
+    for FiringBlockIterN<'a, LINES, RETURNS>
+{
+    type Item = FiringBlockN<'a, LINES, RETURNS>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let layout = Self::LAYOUT;
+        if self.index >= layout.num_firings_per_packet() {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+
+        let firings_per_group = layout.firings_per_group();
+        let (group, nth) = (index / firings_per_group, index % firings_per_group);
+        let blocks = self.group_blocks(group);
+
+        let azimuth = blocks[0].azimuth();
+        let diff = self.group_azimuth_diff(group);
+        let start = azimuth + diff * nth as f64 / firings_per_group as f64;
+        let end = azimuth + diff * (nth + 1) as f64 / firings_per_group as f64;
+
+        Some(FiringBlockN {
+            toh: self.packet.toh() + self.firing_period * index as u32,
+            azimuth_range: start..end,
+            blocks,
+            offset: nth * LINES,
         })
     }
 
-    pub fn firing_block_iter_d32(
-        &self,
-    ) -> impl Iterator<Item = FiringBlockD32<'_>> + Clone + Sync + Send {
-        let tohs = iter::successors(Some(self.toh()), move |prev| Some(*prev + FIRING_PERIOD));
-        let azimuths = {
-            let azimuths: Vec<_> = self
-                .blocks
-                .iter()
-                .step_by(2)
-                .map(|block| block.azimuth())
-                .collect();
-            let azimuth_diffs: Vec<_> = azimuths
-                .iter()
-                .cloned()
-                .tuple_windows()
-                .map(|(curr, next)| (next - curr).wrap_to_2pi())
-                .collect();
-            let last_azimuth_diff = *azimuth_diffs.last().unwrap();
-
-            izip!(azimuths, chain!(azimuth_diffs, [last_azimuth_diff])).map(
-                |(former_azimuth, azimuth_diff)| {
-                    let latter_azimuth = former_azimuth + azimuth_diff;
-                    former_azimuth..latter_azimuth
-                },
-            )
-        };
-
-        izip!(tohs, azimuths, self.blocks.chunks(2)).map(
-            move |(block_toh, azimuth_range, chunk)| {
-                let [block_strongest, block_last] = match chunk {
-                    [first, second] => [first, second],
-                    _ => unreachable!(),
-                };
-
-                FiringBlockD32 {
-                    toh: block_toh,
-                    azimuth_range,
-                    block_strongest,
-                    block_last,
-                    channels: ChannelArrayDRef {
-                        strongest: &block_strongest.channels,
-                        last: &block_last.channels,
-                    },
-                }
-            },
-        )
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = Self::LAYOUT.num_firings_per_packet() - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'a, const LINES: usize, const RETURNS: usize> ExactSizeIterator
+    for FiringBlockIterN<'a, LINES, RETURNS>
+{
+}
+
+/// Selects [FiringBlockIterN] in a [FormatKind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FiringBlockIterFamily<'a>(PhantomData<&'a ()>);
+
+impl<'a> LayoutFamily for FiringBlockIterFamily<'a> {
+    type Of<const LINES: usize, const RETURNS: usize> = FiringBlockIterN<'a, LINES, RETURNS>;
+}
+
+pub type FiringBlockIter<'a> = FormatKind<FiringBlockIterFamily<'a>>;
+
+impl<'a> Iterator for FiringBlockIter<'a> {
+    type Item = FiringBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(map_kind!(self, iter => iter.next()?))
     }
 
-    pub fn firing_xyz_iter_s16<'a>(
-        &'a self,
-        beams: &'a Config16,
-    ) -> impl Iterator<Item = FiringXyzS16> + Clone + Sync + Send + 'a {
-        self.firing_block_iter_s16()
-            .map(|firing| firing.to_firing_xyz(beams))
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        dispatch!(self, iter => iter.size_hint())
     }
+}
 
-    pub fn firing_xyz_iter_s32<'a>(
-        &'a self,
-        beams: &'a Config32,
-    ) -> impl Iterator<Item = FiringXyzS32> + Clone + Sync + Send + 'a {
-        self.firing_block_iter_s32()
-            .map(|firing| firing.to_firing_xyz(beams))
+impl<'a, const LINES: usize, const RETURNS: usize> From<FiringBlockIterN<'a, LINES, RETURNS>>
+    for FiringBlockIter<'a>
+where
+    LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+{
+    fn from(v: FiringBlockIterN<'a, LINES, RETURNS>) -> Self {
+        Self::from_layout(v)
     }
+}
 
-    pub fn firing_xyz_iter_d16<'a>(
-        &'a self,
-        beams: &'a Config16,
-    ) -> impl Iterator<Item = FiringXyzD16> + Clone + Sync + Send + 'a {
-        self.firing_block_iter_d16()
-            .map(|firing| firing.to_firing_xyz(beams))
+#[cfg(feature = "std")]
+mod xyz {
+    use super::DataPacket;
+    use crate::{
+        converter::ConverterN,
+        types::{
+            firing_xyz::FiringXyzN,
+            format::{LayoutN, SupportedLayout},
+        },
+        ConfigN,
+    };
+
+    impl DataPacket {
+        pub fn firing_xyz_iter_n<'a, const LINES: usize, const RETURNS: usize>(
+            &'a self,
+            beams: &'a ConfigN<LINES>,
+        ) -> impl Iterator<Item = FiringXyzN<LINES, RETURNS>> + Clone + Sync + Send + 'a
+        where
+            LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        {
+            let converter = ConverterN::new(beams);
+            self.firing_block_iter_n()
+                .map(move |firing| converter.firing_block_to_xyz(&firing))
+        }
     }
+}
 
-    pub fn firing_xyz_iter_d32<'a>(
-        &'a self,
-        beams: &'a Config32,
-    ) -> impl Iterator<Item = FiringXyzD32> + Clone + Sync + Send + 'a {
-        self.firing_block_iter_d32()
-            .map(|firing| firing.to_firing_xyz(beams))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::PacketBuilder;
+
+    #[test]
+    fn firing_block_iter_layout_test() {
+        // 0.2 degrees per block
+        let builder = PacketBuilder::new(ProductID::VLS128, ReturnMode::Strongest);
+        let mut packet = builder.packet(0);
+        packet.blocks[3].channels[31].distance = 7;
+        packet.blocks[4].channels[0].distance = 9;
+
+        // four blocks per firing
+        let firings: Vec<_> = packet.firing_block_iter_n::<128, 1>().collect();
+        assert_eq!(firings.len(), 3);
+        assert_eq!({ firings[0].channel(0, 127).distance }, 7);
+        assert_eq!({ firings[1].channel(0, 0).distance }, 9);
+        assert!((firings[1].azimuth_range.start.as_degrees() - 0.8).abs() < 1e-9);
+        assert!((firings[2].azimuth_range.end.as_degrees() - 2.4).abs() < 1e-9);
+
+        // two firings per block and three blocks per group
+        let mut packet = builder.packet(0);
+        packet.blocks[5].channels[17].distance = 7;
+        let firings: Vec<_> = packet.firing_block_iter_n::<16, 3>().collect();
+        assert_eq!(firings.len(), 8);
+        assert_eq!(firings[3].offset, 16);
+        assert_eq!({ firings[3].channel(2, 1).distance }, 7);
+        assert!((firings[3].azimuth_range.start.as_degrees() - 0.9).abs() < 1e-9);
     }
 }
@@ -0,0 +1,30 @@
+use core::fmt;
+
+/// The error of parsing bytes into a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseError {
+    /// The slice length differs from the packet size.
+    SizeMismatch { expect: usize, found: usize },
+    /// The bytes are neither a data packet nor a position packet.
+    UnknownPacket,
+    /// An enum field at the byte offset has an unknown value.
+    InvalidField { field: &'static str, offset: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::SizeMismatch { expect, found } => {
+                write!(f, "Requre the slice length to be {expect}, but get {found}")
+            }
+            Self::UnknownPacket => {
+                write!(f, "unable to parse bytes into a data or a positoin packet")
+            }
+            Self::InvalidField { field, offset } => {
+                write!(f, "invalid value of {field} at byte offset {offset}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ParseError {}
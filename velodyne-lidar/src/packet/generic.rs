@@ -1,5 +1,5 @@
-use super::{DataPacket, PositionPacket};
-use eyre::{bail, Result};
+use super::{DataPacket, ParseError, PositionPacket};
+use alloc::boxed::Box;
 
 /// An enumeration of data packet and position packet.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,14 +9,8 @@ pub enum Packet {
 }
 
 impl Packet {
-    pub fn from_slice(buffer: &[u8]) -> Result<Self> {
-        Ok(if let Ok(packet) = DataPacket::from_slice(buffer) {
-            (*packet).into()
-        } else if let Ok(packet) = PositionPacket::from_slice(buffer) {
-            (*packet).into()
-        } else {
-            bail!("unable to parse bytes into a data or a positoin packet");
-        })
+    pub fn from_slice(buffer: &[u8]) -> Result<Self, ParseError> {
+        Ok(PacketRef::from_slice(buffer)?.to_packet())
     }
 
     pub fn try_into_data(self) -> Result<DataPacket, Self> {
@@ -63,3 +57,64 @@ impl From<DataPacket> for Packet {
         Self::Data(Box::new(v))
     }
 }
+
+/// A borrowed data packet or position packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketRef<'a> {
+    Data(&'a DataPacket),
+    Position(&'a PositionPacket),
+}
+
+impl<'a> PacketRef<'a> {
+    /// Reinterprets the bytes as a packet without copying.
+    pub fn from_slice(buffer: &'a [u8]) -> Result<Self, ParseError> {
+        use ParseError::SizeMismatch;
+
+        match DataPacket::from_slice(buffer) {
+            Ok(packet) => return Ok(Self::Data(packet)),
+            Err(SizeMismatch { .. }) => {}
+            Err(err) => return Err(err),
+        }
+        match PositionPacket::from_slice(buffer) {
+            Ok(packet) => Ok(Self::Position(packet)),
+            Err(SizeMismatch { .. }) => Err(ParseError::UnknownPacket),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn as_data(&self) -> Option<&'a DataPacket> {
+        if let Self::Data(v) = *self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_position(&self) -> Option<&'a PositionPacket> {
+        if let Self::Position(v) = *self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    /// Copies the packet into an owned [Packet].
+    pub fn to_packet(&self) -> Packet {
+        match *self {
+            Self::Data(packet) => (*packet).into(),
+            Self::Position(packet) => (*packet).into(),
+        }
+    }
+}
+
+impl<'a> From<&'a DataPacket> for PacketRef<'a> {
+    fn from(v: &'a DataPacket) -> Self {
+        Self::Data(v)
+    }
+}
+
+impl<'a> From<&'a PositionPacket> for PacketRef<'a> {
+    fn from(v: &'a PositionPacket) -> Self {
+        Self::Position(v)
+    }
+}
@@ -0,0 +1,246 @@
+use super::data::{DataPacket, FiringBlockIter, FiringBlockIterN};
+use crate::{
+    types::format::{dispatch_format, Layout, LayoutN, SupportedLayout},
+    utils::{elapsed_toh, AngleExt as _},
+};
+use core::time::Duration;
+use measurements::Angle;
+
+/// A data packet with the azimuth at the end of its last block.
+#[derive(Debug, Clone)]
+pub struct LookaheadPacket {
+    pub packet: DataPacket,
+    /// The end azimuth of the last block, or `None` if it cannot be
+    /// determined.
+    pub end_azimuth: Option<Angle>,
+}
+
+impl LookaheadPacket {
+    /// Iterates over the firings in the format of the packet, or returns
+    /// `None` if the format of the sensor model is not supported.
+    pub fn firing_block_iter(&self) -> Option<FiringBlockIter<'_>> {
+        Some(
+            dispatch_format!(self.packet.try_format()?, <LINES, RETURNS> => {
+                self.firing_block_iter_n::<LINES, RETURNS>().into()
+            }),
+        )
+    }
+
+    pub fn firing_block_iter_n<const LINES: usize, const RETURNS: usize>(
+        &self,
+    ) -> FiringBlockIterN<'_, LINES, RETURNS>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        self.packet
+            .firing_block_iter_n_with_end_azimuth(self.end_azimuth)
+    }
+}
+
+/// Pairs each data packet with the end azimuth of its last block.
+///
+/// The end azimuth is the first azimuth of the next packet if the
+/// packets are consecutive. At the end of the stream or at a gap of
+/// dropped packets, it is extrapolated from the rotation rate measured
+/// between the latest consecutive packets.
+#[derive(Debug, Clone)]
+pub struct PacketLookahead<I> {
+    packets: I,
+    lookahead: Lookahead,
+}
+
+impl<I> PacketLookahead<I>
+where
+    I: Iterator<Item = DataPacket>,
+{
+    pub fn new<T>(packets: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Self {
+            packets: packets.into_iter(),
+            lookahead: Lookahead::new(),
+        }
+    }
+
+    /// The latest measured rotation rate in radians per second.
+    pub fn rotation_rate(&self) -> Option<f64> {
+        self.lookahead.rotation_rate()
+    }
+}
+
+impl<I> Iterator for PacketLookahead<I>
+where
+    I: Iterator<Item = DataPacket>,
+{
+    type Item = LookaheadPacket;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for packet in self.packets.by_ref() {
+            if let Some(output) = self.lookahead.push(packet) {
+                return Some(output);
+            }
+        }
+        self.lookahead.finish()
+    }
+}
+
+/// The push-based counterpart of [PacketLookahead].
+///
+/// A packet is held back until the next packet arrives or the stream
+/// is finished.
+#[derive(Debug, Clone, Default)]
+pub struct Lookahead {
+    pending: Option<DataPacket>,
+    /// Rotation rate in radians per second.
+    rate: Option<f64>,
+}
+
+impl Lookahead {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The latest measured rotation rate in radians per second.
+    pub fn rotation_rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    /// Returns true if a packet is held back.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Pushes a packet and returns the previous packet if any.
+    pub fn push(&mut self, packet: DataPacket) -> Option<LookaheadPacket> {
+        let prev = self.pending.replace(packet)?;
+        let next = self.pending.as_ref();
+        let end_azimuth = Self::end_azimuth(&mut self.rate, &prev, next);
+        Some(LookaheadPacket {
+            packet: prev,
+            end_azimuth,
+        })
+    }
+
+    /// Returns the held back packet, whose end azimuth is extrapolated.
+    pub fn finish(&mut self) -> Option<LookaheadPacket> {
+        let packet = self.pending.take()?;
+        let end_azimuth = Self::end_azimuth(&mut self.rate, &packet, None);
+        Some(LookaheadPacket {
+            packet,
+            end_azimuth,
+        })
+    }
+
+    fn end_azimuth(
+        rate: &mut Option<f64>,
+        packet: &DataPacket,
+        next: Option<&DataPacket>,
+    ) -> Option<Angle> {
+        let timing = BlockTiming::new(packet)?;
+
+        match next {
+            Some(next) if timing.is_followed_by(packet, next) => {
+                let elapsed = elapsed_toh(packet.toh(), next.toh());
+                let start = packet.blocks[0].azimuth();
+                let end = next.blocks[0].azimuth();
+                *rate = Some((end - start).wrap_to_2pi().as_radians() / elapsed.as_secs_f64());
+                Some(end)
+            }
+            _ => rate.map(|rate| {
+                let last = timing.last_azimuth(packet);
+                let diff = Angle::from_radians(rate * timing.block_period.as_secs_f64());
+                (last + diff).wrap_to_2pi()
+            }),
+        }
+    }
+}
+
+/// The time between azimuth values of a packet.
+#[derive(Debug, Clone, Copy)]
+struct BlockTiming {
+    layout: Layout,
+    block_period: Duration,
+    packet_period: Duration,
+}
+
+impl BlockTiming {
+    fn new(packet: &DataPacket) -> Option<Self> {
+        let layout = packet.try_format()?.layout();
+        let num_firings = layout.num_firings_per_packet() as u32;
+        // a group of blocks shares an azimuth
+        let num_azimuths = layout.num_groups() as u32;
+        let packet_period = packet.firing_period() * num_firings;
+
+        Some(Self {
+            layout,
+            block_period: packet_period / num_azimuths,
+            packet_period,
+        })
+    }
+
+    /// Gets the azimuth of the last group of blocks.
+    fn last_azimuth(&self, packet: &DataPacket) -> Angle {
+        let last_group = self.layout.num_groups() - 1;
+        packet.blocks[last_group * self.layout.blocks_per_group()].azimuth()
+    }
+
+    /// Checks if the next packet starts right after the packet.
+    fn is_followed_by(&self, packet: &DataPacket, next: &DataPacket) -> bool {
+        let elapsed = elapsed_toh(packet.toh(), next.toh());
+        let error = elapsed.abs_diff(self.packet_period);
+        next.try_format() == packet.try_format() && error < self.block_period / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        packet::{ProductID, ReturnMode},
+        test_utils::PacketBuilder,
+    };
+
+    #[test]
+    fn packet_lookahead_test() {
+        // 0.2 degrees per block, where the speed doubles in the third
+        // packet
+        let builder = PacketBuilder::new(ProductID::VLP16, ReturnMode::Strongest);
+        let packet = |index: u32, start: usize, step: usize| {
+            builder
+                .with_start_toh(1327 * index)
+                .with_azimuth(start, step)
+                .packet(0)
+        };
+        let packets = [
+            packet(0, 35800, 20),
+            packet(1, 36040, 20),
+            packet(2, 36280, 40),
+        ];
+
+        let mut lookahead = PacketLookahead::new(packets);
+        let first = lookahead.next().unwrap();
+        let second = lookahead.next().unwrap();
+        let third = lookahead.next().unwrap();
+        assert!(lookahead.next().is_none());
+
+        let degrees = |angle: Option<Angle>| angle.unwrap().as_degrees();
+        assert!((degrees(first.end_azimuth) - 0.4).abs() < 1e-9);
+        assert!((degrees(second.end_azimuth) - 2.8).abs() < 1e-9);
+
+        // extrapolated with the rate of the previous packets
+        let rate = lookahead.rotation_rate().unwrap();
+        assert!((rate.to_degrees() * 1327e-6 - 2.4).abs() < 0.01);
+        assert!((degrees(third.end_azimuth) - 7.4).abs() < 0.01);
+
+        // the last firing of the second packet spans to the third packet
+        let last = second.firing_block_iter_n::<16, 1>().last().unwrap();
+        assert!((last.azimuth_range.end.as_degrees() - 2.8).abs() < 1e-9);
+
+        // unsupported models have no firing format
+        let mut velarray = first.clone();
+        velarray.packet.product_id = ProductID::Velarray;
+        assert!(first.firing_block_iter().is_some());
+        assert!(velarray.firing_block_iter().is_none());
+    }
+}
@@ -6,9 +6,15 @@ pub use data::*;
 mod position;
 pub use position::*;
 
+mod error;
+pub use error::*;
+
 mod generic;
 pub use generic::*;
 
+mod lookahead;
+pub use lookahead::*;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -19,4 +25,44 @@ mod tests {
         assert_eq!(mem::size_of::<DataPacket>(), 1206);
         assert_eq!(mem::size_of::<PositionPacket>(), 512);
     }
+
+    #[test]
+    fn invalid_field_test() {
+        let mut data = [0u8; 1206];
+        for nth in 0..12 {
+            data[nth * 100..nth * 100 + 2].copy_from_slice(&0xbbffu16.to_ne_bytes());
+        }
+        data[1204] = ReturnMode::Strongest as u8;
+        data[1205] = ProductID::VLS128 as u8;
+        let packet = DataPacket::from_slice(&data).unwrap();
+        let block_identifier = packet.blocks[0].block_identifier;
+        assert_eq!(block_identifier, BlockIdentifier::Block96To127);
+
+        let mut bad = data;
+        bad[1100] = 0;
+        assert_eq!(
+            PacketRef::from_slice(&bad),
+            Err(ParseError::InvalidField {
+                field: "block_identifier",
+                offset: 1100
+            })
+        );
+        let mut bad = data;
+        bad[1204] = 0;
+        assert!(DataPacket::from_bytes(bad).is_err());
+        let mut bad = data;
+        bad[1205] = 0xff;
+        assert!(DataPacket::from_slice(&bad).is_err());
+
+        let mut position = [0u8; 512];
+        assert!(PositionPacket::from_slice(&position).is_ok());
+        position[mem::offset_of!(PositionPacket, pps_status)] = 4;
+        assert!(matches!(
+            Packet::from_slice(&position),
+            Err(ParseError::InvalidField {
+                field: "pps_status",
+                ..
+            })
+        ));
+    }
 }
@@ -1,5 +1,5 @@
-use eyre::{ensure, Result};
-use std::mem;
+use super::ParseError;
+use core::mem;
 
 /// Represents a position packet from Velodyne sensor.
 #[repr(C, packed)]
@@ -23,19 +23,47 @@ pub struct PositionPacket {
 }
 
 impl PositionPacket {
-    /// Construct packet from binary buffer.
-    pub fn from_bytes(buffer: [u8; mem::size_of::<Self>()]) -> Self {
-        unsafe { mem::transmute::<_, Self>(buffer) }
+    /// Construct packet from binary buffer. Fail if an enum field has an
+    /// unknown value.
+    pub fn from_bytes(buffer: [u8; mem::size_of::<Self>()]) -> Result<Self, ParseError> {
+        Self::from_slice(&buffer).copied()
     }
 
-    /// Construct packet from slice of bytes. Fail if the slice size is not correct.
-    pub fn from_slice(buffer: &[u8]) -> Result<&Self> {
-        ensure!(
-            buffer.len() == mem::size_of::<Self>(),
-            "Requre the slice length to be {}, but get {}",
-            mem::size_of::<Self>(),
-            buffer.len(),
-        );
+    /// Construct packet from slice of bytes. Fail if the slice size is not
+    /// correct or an enum field has an unknown value.
+    pub fn from_slice(buffer: &[u8]) -> Result<&Self, ParseError> {
+        if buffer.len() != mem::size_of::<Self>() {
+            return Err(ParseError::SizeMismatch {
+                expect: mem::size_of::<Self>(),
+                found: buffer.len(),
+            });
+        }
+
+        let fields = [
+            (
+                "last_adc_calibration_reason",
+                mem::offset_of!(Self, last_adc_calibration_reason),
+                LastAdcCalibrationReason::Periodic as u8,
+            ),
+            (
+                "pps_status",
+                mem::offset_of!(Self, pps_status),
+                PpsStatus::Error as u8,
+            ),
+            (
+                "thermal_status",
+                mem::offset_of!(Self, thermal_status),
+                ThermalStatus::ThermalShutdown as u8,
+            ),
+        ];
+        for (field, offset, max) in fields {
+            if buffer[offset] > max {
+                return Err(ParseError::InvalidField { field, offset });
+            }
+        }
+
+        // SAFETY: the size matches, the alignment is 1 and every enum
+        // field holds a valid discriminant.
         let packet = unsafe { &*(buffer.as_ptr() as *const Self) };
         Ok(packet)
     }
@@ -1,5 +1,7 @@
 //! Parallel iterator combinators.
 
-// pub mod convert;
+mod convert;
+pub use convert::*;
+
 #[cfg(feature = "pcap")]
 pub mod pcap;
@@ -0,0 +1,353 @@
+//! Order-preserving parallel conversion functions.
+//!
+//! Packets are read in chunks. While the packets in a chunk are
+//! converted to firings in parallel, the firings of the previous chunk
+//! are batched into frames in the packet order. At most two chunks of
+//! half of `max_in_flight` packets are in flight.
+
+use rayon::prelude::*;
+use std::{
+    iter,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+/// Gets the default number of packets converted at a time.
+pub fn default_max_in_flight() -> usize {
+    rayon::current_num_threads() * 16
+}
+
+/// Maps items in parallel, preserving the order. The next chunk is
+/// mapped in the background while the outputs of the current chunk are
+/// consumed.
+fn par_map_chunked<T, U, I, F>(items: I, max_in_flight: usize, f: F) -> impl Iterator<Item = U>
+where
+    I: IntoIterator<Item = T>,
+    T: Send + 'static,
+    U: Send + 'static,
+    F: Fn(T) -> U + Sync + Send + 'static,
+{
+    assert!(max_in_flight > 0, "max_in_flight must be positive");
+
+    let chunk_size = (max_in_flight / 2).max(1);
+    let mut items = items.into_iter();
+    let f = Arc::new(f);
+
+    let spawn_chunk = move |items: &mut I::IntoIter| -> Option<Receiver<thread::Result<Vec<U>>>> {
+        let chunk: Vec<T> = items.by_ref().take(chunk_size).collect();
+        if chunk.is_empty() {
+            return None;
+        }
+
+        let f = f.clone();
+        let (tx, rx) = mpsc::sync_channel(1);
+        rayon::spawn(move || {
+            // the panic is resumed on the consuming thread
+            let outputs = panic::catch_unwind(AssertUnwindSafe(|| {
+                chunk.into_par_iter().map(&*f).collect()
+            }));
+            let _ = tx.send(outputs);
+        });
+        Some(rx)
+    };
+    let mut pending = None;
+
+    iter::from_fn(move || {
+        let current = pending.take().or_else(|| spawn_chunk(&mut items))?;
+        pending = spawn_chunk(&mut items);
+
+        let outputs = current
+            .recv()
+            .expect("the conversion task ended without output");
+        Some(outputs.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+    })
+    .flatten()
+}
+
+pub use data_packet_to_frame_xyz::*;
+mod data_packet_to_frame_xyz {
+    use super::{default_max_in_flight, par_map_chunked};
+    use crate::{
+        batcher::{AzimuthWrap, BatchStrategy, BatchStrategyKind, Batcher},
+        converter::ConverterN,
+        iter::{audit_format, FrameXyzIter},
+        packet::{LookaheadPacket, PacketLookahead},
+        types::{
+            firing_xyz::{FiringXyzFamily, FiringXyzN},
+            format::{dispatch, LayoutN, SupportedLayout},
+            frame_header::FrameHeader,
+            frame_xyz::{FrameXyz, FrameXyzN},
+        },
+        Config, ConfigN, DataPacket,
+    };
+    use eyre::{format_err, Result};
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz] in parallel.
+    pub fn data_packet_to_frame_xyz<'a, I>(config: Config, packets: I) -> Result<FrameXyzIter<'a>>
+    where
+        I: IntoIterator<Item = DataPacket> + 'a,
+        I::IntoIter: Send,
+    {
+        data_packet_to_frame_xyz_with_strategy(
+            config,
+            AzimuthWrap,
+            default_max_in_flight(),
+            packets,
+        )
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz] in parallel, where frames are
+    /// split by the batching strategy and at most `max_in_flight`
+    /// packets are converted at a time.
+    pub fn data_packet_to_frame_xyz_with_strategy<'a, I, S>(
+        config: Config,
+        strategy: S,
+        max_in_flight: usize,
+        packets: I,
+    ) -> Result<FrameXyzIter<'a>>
+    where
+        I: IntoIterator<Item = DataPacket> + 'a,
+        I::IntoIter: Send,
+        S: BatchStrategyKind<FiringXyzFamily> + Send + 'a,
+    {
+        let config_kind = config
+            .try_into_kind()
+            .map_err(|_| format_err!("invalid configuration"))?;
+
+        let iter: FrameXyzIter = dispatch!(config_kind, <LINES, RETURNS> config => Box::new(
+            data_packet_to_frame_xyz_n::<LINES, RETURNS, _, _>(
+                config,
+                strategy,
+                max_in_flight,
+                packets,
+            )
+            .map(FrameXyz::from),
+        ));
+
+        Ok(iter)
+    }
+
+    /// Converts packets to frames of `LINES` lasers with `RETURNS`
+    /// returns in parallel, where frames are split by the batching
+    /// strategy.
+    pub fn data_packet_to_frame_xyz_n<const LINES: usize, const RETURNS: usize, I, S>(
+        config: ConfigN<LINES>,
+        strategy: S,
+        max_in_flight: usize,
+        packets: I,
+    ) -> impl Iterator<Item = FrameXyzN<LINES, RETURNS>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = DataPacket>,
+        I::IntoIter: Send,
+        S: BatchStrategy<FiringXyzN<LINES, RETURNS>> + Send,
+    {
+        let batcher = Batcher::with_strategy(strategy).with_sensor_id(config.sensor_id);
+        let converter = ConverterN::<LINES, RETURNS>::new(&config);
+        let azimuth_windows = config.azimuth_windows.clone();
+
+        let packets = PacketLookahead::new(packets);
+
+        par_map_chunked(packets, max_in_flight, move |packet| {
+            let LookaheadPacket {
+                packet,
+                end_azimuth,
+            } = packet;
+            audit_format(packet.try_format(), converter.format());
+
+            let firings: Vec<FiringXyzN<LINES, RETURNS>> = converter
+                .firing_blocks_with_end_azimuth(&packet, end_azimuth)
+                .map(|block| converter.firing_block_to_xyz(&block))
+                .collect();
+            (packet.product_id, firings)
+        })
+        .scan(batcher, move |batcher, (product_id, firings)| {
+            let frames: Vec<_> = batcher
+                .push_packet(firings)
+                .map(|batch| FrameXyzN {
+                    header: FrameHeader::from_batch(&batch, Some(product_id))
+                        .with_azimuth_windows(&azimuth_windows),
+                    firings: batch.items,
+                })
+                .collect();
+            Some(frames)
+        })
+        .flatten()
+    }
+}
+
+pub use try_packet_to_frame_xyz::*;
+mod try_packet_to_frame_xyz {
+    use super::{default_max_in_flight, par_map_chunked};
+    use crate::{
+        batcher::{AzimuthWrap, BatchStrategy, BatchStrategyKind, Batcher},
+        converter::ConverterN,
+        iter::{audit_format, try_lookahead, ResultFrameXyzIter},
+        packet::LookaheadPacket,
+        types::{
+            firing_xyz::{FiringXyzFamily, FiringXyzN},
+            format::{dispatch, LayoutN, SupportedLayout},
+            frame_header::FrameHeader,
+            frame_xyz::{FrameXyz, FrameXyzN},
+        },
+        Config, ConfigN, Packet,
+    };
+    use eyre::{format_err, Result};
+    use itertools::Itertools;
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz] in parallel.
+    pub fn try_packet_to_frame_xyz<'a, E, I>(
+        config: Config,
+        packets: I,
+    ) -> Result<ResultFrameXyzIter<'a, E>>
+    where
+        I: IntoIterator<Item = Result<Packet, E>> + 'a,
+        I::IntoIter: Send,
+        E: Send + 'static,
+    {
+        try_packet_to_frame_xyz_with_strategy(config, AzimuthWrap, default_max_in_flight(), packets)
+    }
+
+    /// Converts an iterator of packets to an iterator of
+    /// [crate::types::frame_xyz::FrameXyz] in parallel, where frames are
+    /// split by the batching strategy and at most `max_in_flight`
+    /// packets are converted at a time.
+    pub fn try_packet_to_frame_xyz_with_strategy<'a, E, I, S>(
+        config: Config,
+        strategy: S,
+        max_in_flight: usize,
+        packets: I,
+    ) -> Result<ResultFrameXyzIter<'a, E>>
+    where
+        I: IntoIterator<Item = Result<Packet, E>> + 'a,
+        I::IntoIter: Send,
+        E: Send + 'static,
+        S: BatchStrategyKind<FiringXyzFamily> + Send + 'a,
+    {
+        let config_kind = config
+            .try_into_kind()
+            .map_err(|_| format_err!("invalid configuration"))?;
+
+        let iter: ResultFrameXyzIter<_> = dispatch!(config_kind, <LINES, RETURNS> config => {
+            Box::new(
+                try_packet_to_frame_xyz_n::<LINES, RETURNS, _, _, _>(
+                    config,
+                    strategy,
+                    max_in_flight,
+                    packets,
+                )
+                .map_ok(FrameXyz::from),
+            )
+        });
+
+        Ok(iter)
+    }
+
+    /// Converts packets to frames of `LINES` lasers with `RETURNS`
+    /// returns in parallel, where frames are split by the batching
+    /// strategy.
+    pub fn try_packet_to_frame_xyz_n<const LINES: usize, const RETURNS: usize, E, I, S>(
+        config: ConfigN<LINES>,
+        strategy: S,
+        max_in_flight: usize,
+        packets: I,
+    ) -> impl Iterator<Item = Result<FrameXyzN<LINES, RETURNS>, E>> + Send
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+        I: IntoIterator<Item = Result<Packet, E>>,
+        I::IntoIter: Send,
+        E: Send + 'static,
+        S: BatchStrategy<FiringXyzN<LINES, RETURNS>> + Send,
+    {
+        let batcher = Batcher::with_strategy(strategy).with_sensor_id(config.sensor_id);
+        let converter = ConverterN::<LINES, RETURNS>::new(&config);
+        let azimuth_windows = config.azimuth_windows.clone();
+
+        let packets = packets
+            .into_iter()
+            .map_ok(|packet| packet.try_into_data().ok())
+            .flatten_ok();
+        let packets = try_lookahead(packets);
+
+        par_map_chunked(packets, max_in_flight, move |packet| {
+            let LookaheadPacket {
+                packet,
+                end_azimuth,
+            } = packet?;
+            audit_format(packet.try_format(), converter.format());
+
+            let firings: Vec<FiringXyzN<LINES, RETURNS>> = converter
+                .firing_blocks_with_end_azimuth(&packet, end_azimuth)
+                .map(|block| converter.firing_block_to_xyz(&block))
+                .collect();
+            Ok((packet.product_id, firings))
+        })
+        .scan(Some(batcher), move |batcher, firings| {
+            let (product_id, firings) = match firings {
+                Ok(item) => item,
+                Err(err) => {
+                    *batcher = None;
+                    return Some(Err(err));
+                }
+            };
+            let Some(batcher) = batcher else {
+                return None;
+            };
+
+            let frames: Vec<_> = batcher
+                .push_packet(firings)
+                .map(|batch| FrameXyzN {
+                    header: FrameHeader::from_batch(&batch, Some(product_id))
+                        .with_azimuth_windows(&azimuth_windows),
+                    firings: batch.items,
+                })
+                .collect();
+            Some(Ok(frames))
+        })
+        .flatten_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{packet::ProductID, test_utils::PacketBuilder, Config, ReturnMode};
+
+    #[test]
+    fn par_data_packet_to_frame_xyz_test() {
+        let packets = PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest)
+            .with_azimuth(0, 200)
+            .with_distance(100)
+            .packets(100);
+
+        let config = Config::new_vlp_32c_strongest();
+        let expect: Vec<_> = crate::iter::data_packet_to_frame_xyz(config.clone(), packets.clone())
+            .unwrap()
+            .collect();
+        let frames: Vec<_> = super::data_packet_to_frame_xyz_with_strategy(
+            config,
+            crate::batcher::AzimuthWrap,
+            7,
+            packets,
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(frames.len(), 6);
+        assert_eq!(frames.len(), expect.len());
+
+        for (frame, expect) in frames.iter().zip(&expect) {
+            assert_eq!(frame.header(), expect.header());
+            assert!(frame
+                .firing_iter()
+                .zip(expect.firing_iter())
+                .all(|(lhs, rhs)| lhs == rhs));
+        }
+    }
+}
@@ -1,6 +1,7 @@
 //! Packet parallel iterator creation functions.
 
-use crate::Packet;
+use super::convert::try_packet_to_frame_xyz;
+use crate::{iter::ResultFrameXyzIter, Config, Packet};
 use eyre::Result;
 use pcap::{Capture, Device};
 use rayon::{iter::IterBridge, prelude::*};
@@ -38,3 +39,44 @@ where
     let capture = capture.open()?;
     from_capture(capture)
 }
+
+/// Creates a frame iterator from [pcap::Capture], where packets are
+/// converted in parallel.
+pub fn frame_xyz_iter_from_capture<A>(
+    config: Config,
+    capture: Capture<A>,
+) -> Result<ResultFrameXyzIter<'static, pcap::Error>>
+where
+    A: pcap::Activated + 'static,
+{
+    let packets = crate::iter::packet_iter_from_capture(capture)?;
+    let iter = try_packet_to_frame_xyz(config, packets)?;
+    Ok(iter)
+}
+
+/// Creates a frame iterator by loading from a file, where packets are
+/// converted in parallel.
+pub fn frame_xyz_iter_from_file<P>(
+    config: Config,
+    path: P,
+) -> Result<ResultFrameXyzIter<'static, pcap::Error>>
+where
+    P: AsRef<Path>,
+{
+    let capture: Capture<pcap::Offline> = Capture::from_file(path)?;
+    frame_xyz_iter_from_capture(config, capture)
+}
+
+/// Creates a frame iterator by reading a device, where packets are
+/// converted in parallel.
+pub fn frame_xyz_iter_from_device<D>(
+    config: Config,
+    device: D,
+) -> Result<ResultFrameXyzIter<'static, pcap::Error>>
+where
+    D: Into<Device>,
+{
+    let capture: Capture<pcap::Inactive> = Capture::from_device(device)?;
+    let capture = capture.open()?;
+    frame_xyz_iter_from_capture(config, capture)
+}
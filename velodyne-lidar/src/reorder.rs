@@ -0,0 +1,203 @@
+//! Restore the packet order of a packet stream.
+
+use crate::{packet::DataPacket, utils::unwrap_toh};
+use std::{collections::BTreeMap, iter, time::Duration};
+
+/// The outcome of pushing a packet to a [ReorderBuffer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReorderStatus {
+    /// The packet is newer than all buffered packets.
+    Accepted,
+    /// The packet is accepted and placed before newer buffered packets.
+    Reordered,
+    /// The packet is discarded because a newer packet was already released.
+    Late,
+    /// The packet is discarded because a packet with the same timestamp
+    /// is already buffered.
+    Duplicate,
+}
+
+/// Counters of a [ReorderBuffer].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ReorderStats {
+    /// Number of pushed packets.
+    pub num_pushed: usize,
+    /// Number of released packets.
+    pub num_released: usize,
+    /// Number of packets put back into order.
+    pub num_reordered: usize,
+    /// Number of packets discarded for arriving too late.
+    pub num_late: usize,
+    /// Number of packets discarded as duplicates.
+    pub num_duplicated: usize,
+}
+
+impl ReorderStats {
+    /// Number of discarded packets.
+    pub fn num_discarded(&self) -> usize {
+        self.num_late + self.num_duplicated
+    }
+}
+
+/// A bounded jitter buffer that releases data packets in `toh` order.
+///
+/// A packet is held until a packet newer by at least the configured
+/// latency arrives, or until the buffer exceeds the configured depth.
+/// The latency is measured on the packet clock rather than the wall
+/// clock, so the result is the same for live and recorded streams.
+/// Packets older than the last released packet are discarded.
+#[derive(Debug, Clone)]
+pub struct ReorderBuffer {
+    max_latency: Duration,
+    max_depth: usize,
+    buffer: BTreeMap<i64, DataPacket>,
+    newest: Option<i64>,
+    released: Option<i64>,
+    stats: ReorderStats,
+}
+
+impl ReorderBuffer {
+    /// Create a new instance with the latency and the maximum number of
+    /// buffered packets.
+    pub fn new(max_latency: Duration, max_depth: usize) -> Self {
+        assert!(max_depth > 0, "max_depth must be positive");
+
+        Self {
+            max_latency,
+            max_depth,
+            buffer: BTreeMap::new(),
+            newest: None,
+            released: None,
+            stats: ReorderStats::default(),
+        }
+    }
+
+    /// Gets the counters.
+    pub fn stats(&self) -> &ReorderStats {
+        &self.stats
+    }
+
+    /// Number of buffered packets.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns true if no packet is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Pushes one packet into the buffer.
+    pub fn push(&mut self, packet: DataPacket) -> ReorderStatus {
+        self.stats.num_pushed += 1;
+
+        let reference = self.newest.or(self.released).unwrap_or(packet.toh as i64);
+        let time = unwrap_toh(packet.toh, reference);
+
+        if matches!(self.released, Some(released) if time <= released) {
+            self.stats.num_late += 1;
+            return ReorderStatus::Late;
+        }
+        if self.buffer.contains_key(&time) {
+            self.stats.num_duplicated += 1;
+            return ReorderStatus::Duplicate;
+        }
+
+        self.buffer.insert(time, packet);
+
+        match self.newest {
+            Some(newest) if time < newest => {
+                self.stats.num_reordered += 1;
+                ReorderStatus::Reordered
+            }
+            _ => {
+                self.newest = Some(time);
+                ReorderStatus::Accepted
+            }
+        }
+    }
+
+    /// Pops the oldest packet if it has stayed long enough or the buffer
+    /// is over its depth.
+    pub fn pop(&mut self) -> Option<DataPacket> {
+        let (&oldest, _) = self.buffer.first_key_value()?;
+        let newest = self.newest?;
+
+        let is_expired = newest - oldest >= self.max_latency.as_micros() as i64;
+        let is_overflow = self.buffer.len() > self.max_depth;
+
+        (is_expired || is_overflow).then(|| self.take()).flatten()
+    }
+
+    /// Takes the oldest packet regardless of its latency.
+    pub fn take(&mut self) -> Option<DataPacket> {
+        let (time, packet) = self.buffer.pop_first()?;
+        self.released = Some(time);
+        self.stats.num_released += 1;
+        Some(packet)
+    }
+
+    /// Pushes one packet and returns an iterator of released packets.
+    pub fn push_one(&mut self, packet: DataPacket) -> impl Iterator<Item = DataPacket> + '_ {
+        self.push(packet);
+        iter::from_fn(|| self.pop())
+    }
+
+    /// Converts an iterator of packets to an iterator of packets in
+    /// order. The buffered packets are flushed at the end.
+    pub fn with_iter<I>(self, packets: I) -> impl Iterator<Item = DataPacket>
+    where
+        I: IntoIterator<Item = DataPacket>,
+    {
+        let mut packets = packets.into_iter();
+        let mut buffer = self;
+
+        iter::from_fn(move || loop {
+            if let Some(packet) = buffer.pop() {
+                return Some(packet);
+            }
+
+            match packets.next() {
+                Some(packet) => {
+                    buffer.push(packet);
+                }
+                None => return buffer.take(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{packet::ProductID, test_utils::PacketBuilder, ReturnMode};
+
+    fn packet(toh: u32) -> DataPacket {
+        PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest)
+            .with_start_toh(toh)
+            .packet(0)
+    }
+
+    #[test]
+    fn reorder_buffer_test() {
+        let mut buffer = ReorderBuffer::new(Duration::from_micros(2000), 16);
+        let mut tohs = vec![];
+
+        for toh in [0, 2000, 1000, 3000, 3000, 6000, 500, 5000] {
+            tohs.extend(buffer.push_one(packet(toh)).map(|packet| packet.toh));
+        }
+        tohs.extend(iter::from_fn(|| buffer.take()).map(|packet| packet.toh));
+
+        assert_eq!(tohs, [0, 1000, 2000, 3000, 5000, 6000]);
+        assert_eq!(
+            buffer.stats(),
+            &ReorderStats {
+                num_pushed: 8,
+                num_released: 6,
+                num_reordered: 2,
+                num_late: 1,
+                num_duplicated: 1,
+            }
+        );
+    }
+}
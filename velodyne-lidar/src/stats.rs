@@ -0,0 +1,465 @@
+//! Packet stream quality analysis.
+
+use crate::{consts::AZIMUTH_COUNT_PER_REV, packet::DataPacket, utils::unwrap_toh};
+use std::{collections::VecDeque, f64::consts::PI, time::Duration};
+
+/// Number of recently seen packets kept for duplicate detection.
+const HISTORY_LEN: usize = 64;
+
+/// The classification of a data packet relative to the packets seen before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketStatus {
+    /// The packet follows the previous one without a gap.
+    InOrder,
+    /// The packet is in order, but `count` packets are missing before it.
+    AfterGap { count: usize },
+    /// The packet repeats a recently received packet.
+    Duplicate,
+    /// The packet is older than the latest received packet.
+    OutOfOrder,
+}
+
+/// Statistics over a span of a packet stream.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamStats {
+    /// The timestamp of the first in-order packet.
+    pub start_toh: Duration,
+    /// The timestamp of the last in-order packet.
+    pub end_toh: Duration,
+    /// Number of received data packets, including duplicated ones.
+    pub num_packets: usize,
+    /// Number of packets estimated to be lost.
+    pub num_dropped: usize,
+    /// Number of duplicated packets.
+    pub num_duplicated: usize,
+    /// Number of packets arriving later than their successors.
+    pub num_out_of_order: usize,
+    /// Number of jumps in block azimuths that the timestamps do not explain.
+    pub num_azimuth_gaps: usize,
+    rotation_radians: f64,
+    rotation_secs: f64,
+}
+
+impl StreamStats {
+    /// Number of packets the sensor is estimated to have sent.
+    pub fn num_expected(&self) -> usize {
+        self.num_packets - self.num_duplicated + self.num_dropped
+    }
+
+    /// The ratio of lost packets to expected packets.
+    pub fn loss_ratio(&self) -> f64 {
+        match self.num_expected() {
+            0 => 0.0,
+            expected => self.num_dropped as f64 / expected as f64,
+        }
+    }
+
+    /// The estimated rotation rate in revolutions per second.
+    pub fn rotation_rate(&self) -> Option<f64> {
+        (self.rotation_secs > 0.0).then(|| self.rotation_radians / (PI * 2.0) / self.rotation_secs)
+    }
+
+    /// The estimated rotation rate in revolutions per minute.
+    pub fn rpm(&self) -> Option<f64> {
+        Some(self.rotation_rate()? * 60.0)
+    }
+
+    /// Returns true if no packet loss, duplication or reordering is detected.
+    pub fn is_clean(&self) -> bool {
+        self.num_dropped == 0
+            && self.num_duplicated == 0
+            && self.num_out_of_order == 0
+            && self.num_azimuth_gaps == 0
+    }
+
+    fn merge(&mut self, other: &Self) {
+        if self.num_packets == 0 {
+            self.start_toh = other.start_toh;
+        }
+        if other.num_packets > 0 {
+            self.end_toh = other.end_toh;
+        }
+        self.num_packets += other.num_packets;
+        self.num_dropped += other.num_dropped;
+        self.num_duplicated += other.num_duplicated;
+        self.num_out_of_order += other.num_out_of_order;
+        self.num_azimuth_gaps += other.num_azimuth_gaps;
+        self.rotation_radians += other.rotation_radians;
+        self.rotation_secs += other.rotation_secs;
+    }
+}
+
+/// Statistics of a whole capture.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamReport {
+    /// Statistics of consecutive time windows, one second long by default.
+    pub windows: Vec<StreamStats>,
+    /// Statistics of the whole stream.
+    pub total: StreamStats,
+}
+
+/// Packets estimated to be lost between two received packets.
+#[derive(Debug, Clone, Copy)]
+struct Gap {
+    /// The window that counted the loss.
+    window_index: i64,
+    /// Unwrapped timestamps of the packets around the gap.
+    start_time: i64,
+    end_time: i64,
+    /// Number of lost packets not received late yet.
+    missing: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PacketMark {
+    /// Timestamp on the unwrapped time line in microseconds.
+    time: i64,
+    /// Encoder count of the first block.
+    azimuth_count: u16,
+    /// Azimuth advance over the packet in encoder counts.
+    azimuth_span: f64,
+}
+
+/// Detects dropped, duplicated and out-of-order data packets in a
+/// packet stream.
+///
+/// Packet loss is inferred from `toh` deltas, using the packet period
+/// implied by the packet format. Block azimuths are checked for
+/// continuity between consecutive packets and are used to estimate the
+/// rotation rate.
+#[derive(Debug, Clone)]
+pub struct StreamAnalyzer {
+    window_period: Duration,
+    latest: Option<PacketMark>,
+    history: VecDeque<(i64, u16)>,
+    gaps: VecDeque<Gap>,
+    window_index: i64,
+    window: StreamStats,
+    completed: Vec<(i64, StreamStats)>,
+    total: StreamStats,
+}
+
+impl StreamAnalyzer {
+    /// Create a new instance reporting statistics per second.
+    pub fn new() -> Self {
+        Self::with_window_period(Duration::from_secs(1))
+    }
+
+    /// Create a new instance reporting statistics per window of the
+    /// given period.
+    pub fn with_window_period(window_period: Duration) -> Self {
+        assert!(!window_period.is_zero(), "window period must be non-zero");
+
+        Self {
+            window_period,
+            latest: None,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            gaps: VecDeque::new(),
+            window_index: 0,
+            window: StreamStats::default(),
+            completed: vec![],
+            total: StreamStats::default(),
+        }
+    }
+
+    /// Analyzes all packets from an iterator and reports the statistics.
+    pub fn analyze<I>(packets: I) -> StreamReport
+    where
+        I: IntoIterator<Item = DataPacket>,
+    {
+        let mut analyzer = Self::new();
+        packets.into_iter().for_each(|packet| {
+            analyzer.push(&packet);
+        });
+        analyzer.finish()
+    }
+
+    /// Feeds one data packet and classifies it.
+    pub fn push(&mut self, packet: &DataPacket) -> PacketStatus {
+        let toh = packet.toh as i64;
+        let azimuth_count = packet.blocks[0].azimuth_count;
+        let azimuth_span = packet_azimuth_span(packet);
+
+        let Some(latest) = self.latest else {
+            let mark = PacketMark {
+                time: toh,
+                azimuth_count,
+                azimuth_span,
+            };
+            self.window_index = toh.div_euclid(self.window_micros());
+            self.window.start_toh = packet.toh();
+            self.advance(mark, packet.toh());
+            return PacketStatus::InOrder;
+        };
+
+        let time = unwrap_toh(packet.toh, latest.time);
+        let delta = time - latest.time;
+
+        let is_duplicate = self
+            .history
+            .iter()
+            .any(|&(prev_time, prev_azimuth)| prev_time == time && prev_azimuth == azimuth_count);
+        if is_duplicate {
+            self.window.num_packets += 1;
+            self.window.num_duplicated += 1;
+            return PacketStatus::Duplicate;
+        }
+
+        if delta <= 0 {
+            self.window.num_packets += 1;
+            self.window.num_out_of_order += 1;
+            self.fill_gap(time);
+            self.remember(time, azimuth_count);
+            return PacketStatus::OutOfOrder;
+        }
+
+        let period = match packet.try_format() {
+            Some(format) => format.packet_period(),
+            None => Duration::from_micros(delta as u64),
+        };
+        let num_periods = (delta as f64 * 1e-6 / period.as_secs_f64())
+            .round()
+            .max(1.0);
+        let missing = num_periods as usize - 1;
+
+        // Check that the azimuth advance agrees with the elapsed time.
+        let azimuth_delta = (azimuth_count as i64 - latest.azimuth_count as i64)
+            .rem_euclid(AZIMUTH_COUNT_PER_REV as i64 - 1) as f64;
+        let expected_delta =
+            (latest.azimuth_span * num_periods).rem_euclid((AZIMUTH_COUNT_PER_REV - 1) as f64);
+        let azimuth_error = {
+            let error = (azimuth_delta - expected_delta).abs();
+            error.min((AZIMUTH_COUNT_PER_REV - 1) as f64 - error)
+        };
+        let is_azimuth_gap = azimuth_error > latest.azimuth_span / 2.0;
+
+        let mark = PacketMark {
+            time,
+            azimuth_count,
+            azimuth_span,
+        };
+        self.advance(mark, packet.toh());
+
+        self.window.num_dropped += missing;
+        if missing > 0 {
+            if self.gaps.len() == HISTORY_LEN {
+                self.gaps.pop_front();
+            }
+            self.gaps.push_back(Gap {
+                window_index: self.window_index,
+                start_time: latest.time,
+                end_time: time,
+                missing,
+            });
+        }
+        if is_azimuth_gap {
+            self.window.num_azimuth_gaps += 1;
+        } else if missing == 0 {
+            self.window.rotation_radians +=
+                azimuth_delta / (AZIMUTH_COUNT_PER_REV - 1) as f64 * PI * 2.0;
+            self.window.rotation_secs += delta as f64 * 1e-6;
+        }
+
+        if missing == 0 {
+            PacketStatus::InOrder
+        } else {
+            PacketStatus::AfterGap { count: missing }
+        }
+    }
+
+    /// Takes the statistics of the windows completed so far.
+    ///
+    /// Late packets filling gaps of taken windows no longer reduce the
+    /// loss, which keeps the taken windows consistent with the total.
+    pub fn take_window_stats(&mut self) -> Vec<StreamStats> {
+        let completed: Vec<_> = std::mem::take(&mut self.completed)
+            .into_iter()
+            .map(|(_, stats)| stats)
+            .collect();
+        completed.iter().for_each(|stats| self.total.merge(stats));
+
+        let window_index = self.window_index;
+        self.gaps.retain(|gap| gap.window_index == window_index);
+        completed
+    }
+
+    /// Gets the statistics of the whole stream seen so far.
+    pub fn total_stats(&self) -> StreamStats {
+        let mut total = self.total.clone();
+        self.completed
+            .iter()
+            .map(|(_, stats)| stats)
+            .chain([&self.window])
+            .for_each(|stats| total.merge(stats));
+        total
+    }
+
+    /// Finishes the analysis and reports the statistics.
+    pub fn finish(mut self) -> StreamReport {
+        let total = self.total_stats();
+        if self.window.num_packets > 0 {
+            self.completed.push((self.window_index, self.window));
+        }
+
+        StreamReport {
+            windows: self.completed.into_iter().map(|(_, stats)| stats).collect(),
+            total,
+        }
+    }
+
+    fn window_micros(&self) -> i64 {
+        self.window_period.as_micros() as i64
+    }
+
+    fn advance(&mut self, mark: PacketMark, toh: Duration) {
+        let window_index = mark.time.div_euclid(self.window_micros());
+
+        if window_index != self.window_index {
+            let window = std::mem::take(&mut self.window);
+            let prev_index = std::mem::replace(&mut self.window_index, window_index);
+            self.window.start_toh = toh;
+
+            if window.num_packets > 0 {
+                self.completed.push((prev_index, window));
+            }
+        }
+
+        self.window.num_packets += 1;
+        self.window.end_toh = toh;
+        self.latest = Some(mark);
+        self.remember(mark.time, mark.azimuth_count);
+    }
+
+    /// Credits a late packet to the gap it fills, in the window that
+    /// counted the gap as loss.
+    fn fill_gap(&mut self, time: i64) {
+        let Some(gap) = self
+            .gaps
+            .iter_mut()
+            .find(|gap| gap.missing > 0 && gap.start_time < time && time < gap.end_time)
+        else {
+            return;
+        };
+        gap.missing -= 1;
+
+        let window_index = gap.window_index;
+        let stats = if window_index == self.window_index {
+            Some(&mut self.window)
+        } else {
+            self.completed
+                .iter_mut()
+                .find(|(index, _)| *index == window_index)
+                .map(|(_, stats)| stats)
+        };
+        if let Some(stats) = stats {
+            stats.num_dropped -= 1;
+        }
+    }
+
+    fn remember(&mut self, time: i64, azimuth_count: u16) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back((time, azimuth_count));
+    }
+}
+
+impl Default for StreamAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Estimates the azimuth advance over a whole packet in encoder counts.
+fn packet_azimuth_span(packet: &DataPacket) -> f64 {
+    let step = match packet.return_mode.is_dual() {
+        true => 2,
+        false => 1,
+    };
+    let azimuths: Vec<_> = packet
+        .blocks
+        .iter()
+        .step_by(step)
+        .map(|block| block.azimuth_count as i64)
+        .collect();
+    let first = azimuths[0];
+    let last = *azimuths.last().unwrap();
+    let span = (last - first).rem_euclid(AZIMUTH_COUNT_PER_REV as i64 - 1) as f64;
+    span * azimuths.len() as f64 / (azimuths.len() - 1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{packet::ProductID, test_utils::PacketBuilder, ReturnMode};
+
+    fn packet(index: u32) -> DataPacket {
+        // 12 firings of 55.296 us per packet, 20 encoder counts per block.
+        PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest)
+            .with_start_toh(1_000_000)
+            .packet(index)
+    }
+
+    #[test]
+    fn stream_analyzer_test() {
+        let mut analyzer = StreamAnalyzer::new();
+
+        assert_eq!(analyzer.push(&packet(0)), PacketStatus::InOrder);
+        assert_eq!(analyzer.push(&packet(1)), PacketStatus::InOrder);
+        assert_eq!(analyzer.push(&packet(1)), PacketStatus::Duplicate);
+        assert_eq!(
+            analyzer.push(&packet(4)),
+            PacketStatus::AfterGap { count: 2 }
+        );
+        assert_eq!(analyzer.push(&packet(3)), PacketStatus::OutOfOrder);
+        assert_eq!(analyzer.push(&packet(5)), PacketStatus::InOrder);
+
+        let report = analyzer.finish();
+        let total = &report.total;
+        assert_eq!(total.num_packets, 6);
+        assert_eq!(total.num_duplicated, 1);
+        assert_eq!(total.num_out_of_order, 1);
+        assert_eq!(total.num_dropped, 1);
+        assert_eq!(total.num_azimuth_gaps, 0);
+
+        // 240 counts per 663.552 us
+        let expected_rate = 240.0 / 36000.0 / 663.552e-6;
+        let rate = total.rotation_rate().unwrap();
+        assert!((rate - expected_rate).abs() < 0.1);
+    }
+
+    #[test]
+    fn stream_analyzer_window_test() {
+        // windows of 2 ms hold packets 0-3, 4-6 and 7-9
+        let mut analyzer = StreamAnalyzer::with_window_period(Duration::from_millis(2));
+        for index in [0, 1, 3, 4, 6] {
+            analyzer.push(&packet(index));
+        }
+
+        // the late packet fills the gap of the completed window
+        assert_eq!(analyzer.push(&packet(2)), PacketStatus::OutOfOrder);
+        let windows = analyzer.take_window_stats();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].num_dropped, 0);
+        assert_eq!(analyzer.total_stats().num_dropped, 1);
+
+        for index in [7, 9] {
+            analyzer.push(&packet(index));
+        }
+        let windows = analyzer.take_window_stats();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].num_dropped, 1);
+
+        // the gap of a taken window stays counted as loss
+        assert_eq!(analyzer.push(&packet(5)), PacketStatus::OutOfOrder);
+        assert_eq!(analyzer.total_stats().num_dropped, 2);
+        assert_eq!(analyzer.push(&packet(8)), PacketStatus::OutOfOrder);
+        assert_eq!(analyzer.total_stats().num_dropped, 1);
+
+        let report = analyzer.finish();
+        assert_eq!(report.windows.len(), 1);
+        assert_eq!(report.windows[0].num_dropped, 0);
+        assert_eq!(report.windows[0].num_out_of_order, 2);
+        assert_eq!(report.total.num_out_of_order, 3);
+    }
+}
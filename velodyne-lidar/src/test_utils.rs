@@ -0,0 +1,85 @@
+//! Fixtures shared by the unit tests.
+
+use crate::{
+    consts::{BLOCKS_PER_PACKET, CHANNELS_PER_BLOCK},
+    packet::{Block, BlockIdentifier, Channel, DataPacket, ProductID, ReturnMode},
+    types::format::Format,
+};
+use std::{array, time::Duration};
+
+/// Builds the data packets of a sensor rotating at a constant rate.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PacketBuilder {
+    product_id: ProductID,
+    return_mode: ReturnMode,
+    start_toh: u32,
+    packet_period: Duration,
+    start_azimuth: usize,
+    azimuth_step: usize,
+    distance: u16,
+}
+
+impl PacketBuilder {
+    /// Starts at zero `toh` and azimuth, and advances 20 azimuth counts
+    /// per block. The packets are one packet period apart and all
+    /// channels have zero distance.
+    pub fn new(product_id: ProductID, return_mode: ReturnMode) -> Self {
+        Self {
+            product_id,
+            return_mode,
+            start_toh: 0,
+            packet_period: Format::from_model(product_id, return_mode).packet_period(),
+            start_azimuth: 0,
+            azimuth_step: 20,
+            distance: 0,
+        }
+    }
+
+    /// Sets the `toh` of the first packet in microseconds.
+    pub fn with_start_toh(self, start_toh: u32) -> Self {
+        Self { start_toh, ..self }
+    }
+
+    /// Sets the azimuth count of the first block and the step between
+    /// consecutive blocks.
+    pub fn with_azimuth(self, start_azimuth: usize, azimuth_step: usize) -> Self {
+        Self {
+            start_azimuth,
+            azimuth_step,
+            ..self
+        }
+    }
+
+    /// Sets the distance of all channels.
+    pub fn with_distance(self, distance: u16) -> Self {
+        Self { distance, ..self }
+    }
+
+    /// Builds the packet at `index` in the stream.
+    pub fn packet(&self, index: u32) -> DataPacket {
+        let toh = self.start_toh + (self.packet_period * index).as_micros() as u32;
+        let blocks = array::from_fn(|nth| {
+            let count = index as usize * BLOCKS_PER_PACKET + nth;
+            Block {
+                block_identifier: BlockIdentifier::Block0To31,
+                azimuth_count: ((self.start_azimuth + count * self.azimuth_step) % 36000) as u16,
+                channels: [Channel {
+                    distance: self.distance,
+                    intensity: 0,
+                }; CHANNELS_PER_BLOCK],
+            }
+        });
+
+        DataPacket {
+            blocks,
+            toh,
+            return_mode: self.return_mode,
+            product_id: self.product_id,
+        }
+    }
+
+    /// Builds the first `num_packets` packets of the stream.
+    pub fn packets(&self, num_packets: u32) -> Vec<DataPacket> {
+        (0..num_packets).map(|index| self.packet(index)).collect()
+    }
+}
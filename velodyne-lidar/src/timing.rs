@@ -0,0 +1,210 @@
+//! Per-model firing timing.
+//!
+//! The offsets follow the timing tables in the user manuals. They are
+//! measured from the start of the first firing sequence in a data
+//! packet.
+
+use crate::{
+    consts::{BLOCKS_PER_PACKET, CHANNELS_PER_BLOCK},
+    packet::{ProductID, ReturnMode},
+    types::format::Layout,
+};
+use core::{array, time::Duration};
+
+/// The firing timing of a sensor model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimingModel {
+    /// VLP-16 and Puck LITE.
+    Vlp16,
+    PuckHiRes,
+    Vlp32c,
+    Hdl32e,
+    Vls128,
+}
+
+impl TimingModel {
+    pub fn from_product_id(product_id: ProductID) -> Option<Self> {
+        use ProductID::*;
+
+        Some(match product_id {
+            VLP16 | PuckLite => Self::Vlp16,
+            PuckHiRes => Self::PuckHiRes,
+            VLP32C => Self::Vlp32c,
+            HDL32E => Self::Hdl32e,
+            VLS128 => Self::Vls128,
+            Velarray => return None,
+        })
+    }
+
+    pub fn num_lasers(&self) -> usize {
+        use TimingModel::*;
+
+        match self {
+            Vlp16 | PuckHiRes => 16,
+            Vlp32c | Hdl32e => 32,
+            Vls128 => 128,
+        }
+    }
+
+    /// Duration of a firing sequence, in which every laser fires once,
+    /// including the recharge time.
+    pub fn firing_period(&self) -> Duration {
+        use TimingModel::*;
+
+        match self {
+            Vlp16 | PuckHiRes | Vlp32c => Duration::from_nanos(55_296),
+            Hdl32e => Duration::from_nanos(46_080),
+            Vls128 => Duration::from_nanos(53_300),
+        }
+    }
+
+    /// Time of a laser since the start of the firing sequence.
+    pub fn laser_offset(&self, laser_id: usize) -> Duration {
+        use TimingModel::*;
+
+        match self {
+            // one laser every 2.304 µs
+            Vlp16 | PuckHiRes => Duration::from_nanos(2_304) * laser_id as u32,
+            // two lasers every 2.304 µs
+            Vlp32c => Duration::from_nanos(2_304) * (laser_id / 2) as u32,
+            // one laser every 1.152 µs
+            Hdl32e => Duration::from_nanos(1_152) * laser_id as u32,
+            // eight lasers every 2.665 µs, with a maintenance slot after
+            // the eighth group
+            Vls128 => Duration::from_nanos(2_665) * (laser_id / 8 + laser_id / 64) as u32,
+        }
+    }
+
+    /// Number of firing sequences carried by a data packet.
+    pub fn num_firings_per_packet(&self, return_mode: ReturnMode) -> usize {
+        self.layout(return_mode).num_firings_per_packet()
+    }
+
+    /// Gets the arrangement of firings in a data packet.
+    pub fn layout(&self, return_mode: ReturnMode) -> Layout {
+        Layout::new(self.num_lasers(), return_mode)
+    }
+
+    /// Builds the time offsets of every channel in a data packet.
+    ///
+    /// The returns of the same firings share the offsets, since they
+    /// are measured at the same time.
+    pub fn table(&self, return_mode: ReturnMode) -> TimingTable {
+        let layout = self.layout(return_mode);
+        let num_lasers = self.num_lasers();
+
+        let offsets = array::from_fn(|block| {
+            let group = block / layout.blocks_per_group();
+            let nth_block = block % layout.blocks_per_return();
+
+            array::from_fn(|channel| {
+                // position of the channel among the channels of the
+                // firings in the group
+                let index = nth_block * CHANNELS_PER_BLOCK + channel;
+                let firing = group * layout.firings_per_group() + index / num_lasers;
+                let laser_id = index % num_lasers;
+                self.firing_period() * firing as u32 + self.laser_offset(laser_id)
+            })
+        });
+
+        TimingTable {
+            model: *self,
+            return_mode,
+            offsets,
+        }
+    }
+}
+
+/// The time offsets of the channels in a data packet since the start of
+/// the first firing sequence.
+///
+/// The table is informational. The converters derive the same point
+/// times from [TimingModel::firing_period] and
+/// [TimingModel::laser_offset].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingTable {
+    pub model: TimingModel,
+    pub return_mode: ReturnMode,
+    offsets: [[Duration; CHANNELS_PER_BLOCK]; BLOCKS_PER_PACKET],
+}
+
+impl TimingTable {
+    pub fn offset(&self, block: usize, channel: usize) -> Option<Duration> {
+        self.offsets.get(block)?.get(channel).copied()
+    }
+
+    /// Gets the offsets of the channels in the block.
+    pub fn block_offsets(&self, block: usize) -> Option<&[Duration; CHANNELS_PER_BLOCK]> {
+        self.offsets.get(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timing_table_test() {
+        let micros = |value: f64| Duration::from_nanos((value * 1e3).round() as u64);
+
+        let vlp_16 = TimingModel::Vlp16.table(ReturnMode::Strongest);
+        assert_eq!(vlp_16.offset(0, 15), Some(micros(2.304 * 15.0)));
+        assert_eq!(vlp_16.offset(1, 17), Some(micros(55.296 * 3.0 + 2.304)));
+        assert_eq!(vlp_16.offset(12, 0), None);
+
+        let vlp_16_dual = TimingModel::Vlp16.table(ReturnMode::Dual);
+        assert_eq!(vlp_16_dual.offset(3, 16), Some(micros(55.296 * 3.0)));
+        assert_eq!(vlp_16_dual.offset(2, 16), vlp_16_dual.offset(3, 16));
+
+        let vlp_32c = TimingModel::Vlp32c.table(ReturnMode::Dual);
+        assert_eq!(
+            vlp_32c.offset(11, 31),
+            Some(micros(55.296 * 5.0 + 2.304 * 15.0))
+        );
+
+        let hdl_32e = TimingModel::Hdl32e.table(ReturnMode::Last);
+        assert_eq!(
+            hdl_32e.offset(2, 31),
+            Some(micros(46.08 * 2.0 + 1.152 * 31.0))
+        );
+
+        let vls_128 = TimingModel::Vls128.table(ReturnMode::Strongest);
+        assert_eq!(vls_128.offset(3, 31), Some(micros(2.665 * 16.0)));
+        assert_eq!(vls_128.offset(4, 0), Some(micros(53.3)));
+        assert_eq!(
+            TimingModel::Vls128.num_firings_per_packet(ReturnMode::Strongest),
+            3
+        );
+    }
+
+    #[test]
+    fn packet_firing_timing_test() {
+        use crate::test_utils::PacketBuilder;
+
+        let packet = |product_id, return_mode| {
+            PacketBuilder::new(product_id, return_mode)
+                .with_start_toh(1000)
+                .packet(0)
+        };
+
+        // the firings start at the packet timestamp in both modes
+        let single = packet(ProductID::VLP32C, ReturnMode::Strongest);
+        let dual = packet(ProductID::VLP32C, ReturnMode::Dual);
+        let table = TimingModel::Vlp32c.table(ReturnMode::Strongest);
+        let single_tohs: Vec<_> = single
+            .firing_block_iter_n::<32, 1>()
+            .map(|f| f.toh)
+            .collect();
+        let dual_tohs: Vec<_> = dual.firing_block_iter_n::<32, 2>().map(|f| f.toh).collect();
+        assert_eq!(single_tohs[0], single.toh());
+        assert_eq!(single_tohs[..6], dual_tohs[..]);
+        assert_eq!(single_tohs[5], single.toh() + table.offset(5, 0).unwrap());
+
+        let hdl_32e = packet(ProductID::HDL32E, ReturnMode::Strongest);
+        let tohs: Vec<_> = hdl_32e
+            .firing_block_iter_n::<32, 1>()
+            .map(|f| f.toh)
+            .collect();
+        assert_eq!(tohs[1] - tohs[0], TimingModel::Hdl32e.firing_period());
+    }
+}
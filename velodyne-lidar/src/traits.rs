@@ -1,5 +1,7 @@
 //! Trait definitions.
 
+use alloc::boxed::Box;
+
 pub(crate) type BoxIterator<'a, T> = Box<dyn Iterator<Item = T> + Sync + Send + 'a>;
 
 pub use azimuth_range::*;
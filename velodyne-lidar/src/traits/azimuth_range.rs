@@ -1,10 +1,8 @@
-use crate::types::{
-    firing_block::{FiringBlockD16, FiringBlockD32, FiringBlockS16, FiringBlockS32},
-    firing_xyz::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-    frame_xyz::{FrameXyzD16, FrameXyzD32, FrameXyzS16, FrameXyzS32},
-};
+use crate::types::{firing_block::FiringBlockN, firing_raw::FiringRawN};
+#[cfg(feature = "std")]
+use crate::types::{firing_xyz::FiringXyzN, frame_raw::FrameRawN, frame_xyz::FrameXyzN};
+use core::ops::Range;
 use measurements::Angle;
-use std::ops::Range;
 
 /// Provides an azimuth range value.
 pub trait AzimuthRange {
@@ -19,82 +17,44 @@ pub trait AzimuthRange {
     }
 }
 
-impl<'a> AzimuthRange for FiringBlockS16<'a> {
+impl<'a, const LINES: usize, const RETURNS: usize> AzimuthRange
+    for FiringBlockN<'a, LINES, RETURNS>
+{
     fn azimuth_range(&self) -> Range<Angle> {
         self.azimuth_range.clone()
     }
 }
 
-impl<'a> AzimuthRange for FiringBlockS32<'a> {
-    fn azimuth_range(&self) -> Range<Angle> {
-        self.azimuth_range.clone()
-    }
+macro_rules! impl_firing_azimuth_range {
+    ($name:ident) => {
+        impl<const LINES: usize, const RETURNS: usize> AzimuthRange for $name<LINES, RETURNS> {
+            fn azimuth_range(&self) -> Range<Angle> {
+                self.azimuth_range.clone()
+            }
+        }
+    };
 }
 
-impl<'a> AzimuthRange for FiringBlockD16<'a> {
-    fn azimuth_range(&self) -> Range<Angle> {
-        self.azimuth_range.clone()
-    }
-}
+#[cfg(feature = "std")]
+impl_firing_azimuth_range!(FiringXyzN);
+impl_firing_azimuth_range!(FiringRawN);
 
-impl<'a> AzimuthRange for FiringBlockD32<'a> {
-    fn azimuth_range(&self) -> Range<Angle> {
-        self.azimuth_range.clone()
-    }
+#[cfg(feature = "std")]
+macro_rules! impl_frame_azimuth_range {
+    ($name:ident) => {
+        /// Falls back to the range in the header if the frame is empty.
+        impl<const LINES: usize, const RETURNS: usize> AzimuthRange for $name<LINES, RETURNS> {
+            fn azimuth_range(&self) -> Range<Angle> {
+                match (self.firings.first(), self.firings.last()) {
+                    (Some(first), Some(last)) => first.start_azimuth()..last.end_azimuth(),
+                    _ => self.header.azimuth_range.clone(),
+                }
+            }
+        }
+    };
 }
 
-impl AzimuthRange for FiringXyzS16 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        self.azimuth_range.clone()
-    }
-}
-
-impl AzimuthRange for FiringXyzS32 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        self.azimuth_range.clone()
-    }
-}
-
-impl AzimuthRange for FiringXyzD16 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        self.azimuth_range.clone()
-    }
-}
-
-impl AzimuthRange for FiringXyzD32 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        self.azimuth_range.clone()
-    }
-}
-
-impl AzimuthRange for FrameXyzS16 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        let start = self.firings[0].azimuth_range().start;
-        let end = self.firings.last().unwrap().azimuth_range().end;
-        start..end
-    }
-}
-
-impl AzimuthRange for FrameXyzS32 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        let start = self.firings[0].azimuth_range().start;
-        let end = self.firings.last().unwrap().azimuth_range().end;
-        start..end
-    }
-}
-
-impl AzimuthRange for FrameXyzD16 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        let start = self.firings[0].azimuth_range().start;
-        let end = self.firings.last().unwrap().azimuth_range().end;
-        start..end
-    }
-}
-
-impl AzimuthRange for FrameXyzD32 {
-    fn azimuth_range(&self) -> Range<Angle> {
-        let start = self.firings[0].azimuth_range().start;
-        let end = self.firings.last().unwrap().azimuth_range().end;
-        start..end
-    }
-}
+#[cfg(feature = "std")]
+impl_frame_azimuth_range!(FrameXyzN);
+#[cfg(feature = "std")]
+impl_frame_azimuth_range!(FrameRawN);
@@ -1,5 +1,5 @@
 use crate::consts::CHANNEL_PERIOD;
-use std::time::Duration;
+use core::time::Duration;
 
 pub trait FiringLike {
     type Point<'a>
@@ -1,4 +1,5 @@
 use super::BoxIterator;
+use alloc::boxed::Box;
 use itertools::iproduct;
 
 /// Rectangular random accessible point array.
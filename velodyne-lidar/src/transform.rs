@@ -0,0 +1,203 @@
+//! Rigid transforms applied to points.
+
+use measurements::Length;
+use std::array;
+
+/// A rigid transform from the sensor frame to another frame, such as
+/// the vehicle frame.
+///
+/// In conversion, the coordinate convention is applied before the
+/// transform, so the transform must be given in the axes of the
+/// convention.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// Row-major rotation matrix.
+    pub rotation: [[f64; 3]; 3],
+    pub translation: [Length; 3],
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            rotation: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            translation: [Length::default(); 3],
+        }
+    }
+
+    /// Creates a transform from a unit quaternion in `[x, y, z, w]`
+    /// order and a translation. The quaternion is normalized.
+    pub fn from_quaternion(quaternion: [f64; 4], translation: [Length; 3]) -> Self {
+        Self {
+            rotation: velodyne_params::quaternion_to_matrix(quaternion),
+            translation,
+        }
+    }
+
+    /// Creates a transform from a rotation vector, whose direction is the
+    /// rotation axis and whose norm is the angle in radians, and a
+    /// translation.
+    pub fn from_rotation_vector(vector: [f64; 3], translation: [Length; 3]) -> Self {
+        let angle = vector.iter().map(|v| v * v).sum::<f64>().sqrt();
+        if angle == 0.0 {
+            return Self {
+                translation,
+                ..Self::identity()
+            };
+        }
+
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        let [x, y, z] = vector.map(|v| v / angle * sin);
+        Self::from_quaternion([x, y, z, cos], translation)
+    }
+
+    /// Gets the inverse transform.
+    pub fn inverse(&self) -> Self {
+        let r = self.rotation;
+        let rotation = [
+            [r[0][0], r[1][0], r[2][0]],
+            [r[0][1], r[1][1], r[2][1]],
+            [r[0][2], r[1][2], r[2][2]],
+        ];
+        let [tx, ty, tz] = self.translation.map(|v| -v.as_meters());
+        let translation =
+            rotation.map(|row| Length::from_meters(row[0] * tx + row[1] * ty + row[2] * tz));
+
+        Self {
+            rotation,
+            translation,
+        }
+    }
+
+    /// Composes two transforms, where `other` is applied first.
+    pub fn compose(&self, other: &Self) -> Self {
+        let a = self.rotation;
+        let b = other.rotation;
+        let rotation: [[f64; 3]; 3] =
+            array::from_fn(|i| array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()));
+
+        Self {
+            rotation,
+            translation: self.apply(other.translation),
+        }
+    }
+
+    /// Maps a point to the target frame.
+    pub fn apply(&self, point: [Length; 3]) -> [Length; 3] {
+        let [x, y, z] = point.map(|v| v.as_meters());
+        let [tx, ty, tz] = self.translation.map(|v| v.as_meters());
+        let [r0, r1, r2] = self.rotation;
+
+        [
+            r0[0] * x + r0[1] * y + r0[2] * z + tx,
+            r1[0] * x + r1[1] * y + r1[2] * z + ty,
+            r2[0] * x + r2[1] * y + r2[2] * z + tz,
+        ]
+        .map(Length::from_meters)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl From<velodyne_params::Transform> for Transform {
+    fn from(from: velodyne_params::Transform) -> Self {
+        let velodyne_params::Transform {
+            rotation,
+            translation,
+        } = from;
+
+        Self {
+            rotation,
+            translation: translation.map(Length::from_meters),
+        }
+    }
+}
+
+/// A signed axis of the sensor-native frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignedAxis {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl SignedAxis {
+    fn index(&self) -> usize {
+        use SignedAxis::*;
+
+        match self {
+            PosX | NegX => 0,
+            PosY | NegY => 1,
+            PosZ | NegZ => 2,
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        use SignedAxis::*;
+
+        matches!(self, NegX | NegY | NegZ)
+    }
+}
+
+/// The axes of the output coordinates.
+///
+/// The sensor-native frame has x pointing to the right, y to the front
+/// and z upwards, where the azimuth goes clockwise from +y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CoordinateConvention {
+    /// The sensor-native frame.
+    #[default]
+    Native,
+    /// ROS REP-103, with x pointing to the front, y to the left and z
+    /// upwards.
+    Rep103,
+    /// Takes each output axis from a signed native axis.
+    Permutation([SignedAxis; 3]),
+}
+
+impl CoordinateConvention {
+    /// Creates a permutation convention. Returns `None` unless every
+    /// native axis is used exactly once.
+    pub fn permutation(axes: [SignedAxis; 3]) -> Option<Self> {
+        let mut used = [false; 3];
+        for axis in axes {
+            if std::mem::replace(&mut used[axis.index()], true) {
+                return None;
+            }
+        }
+        Some(Self::Permutation(axes))
+    }
+
+    /// Gets the native axis of each output axis.
+    pub fn axes(&self) -> [SignedAxis; 3] {
+        use SignedAxis::*;
+
+        match *self {
+            Self::Native => [PosX, PosY, PosZ],
+            Self::Rep103 => [PosY, NegX, PosZ],
+            Self::Permutation(axes) => axes,
+        }
+    }
+
+    /// Maps a point in the sensor-native frame to this convention.
+    pub fn apply(&self, point: [Length; 3]) -> [Length; 3] {
+        if *self == Self::Native {
+            return point;
+        }
+
+        self.axes().map(|axis| {
+            let value = point[axis.index()];
+            if axis.is_negative() {
+                value * -1.0
+            } else {
+                value
+            }
+        })
+    }
+}
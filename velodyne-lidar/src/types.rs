@@ -1,10 +1,22 @@
 pub mod channel;
-pub mod channel_array;
 pub mod firing_block;
+#[cfg(feature = "std")]
+pub mod firing_polar;
 pub mod firing_raw;
+#[cfg(feature = "std")]
 pub mod firing_xyz;
 pub mod format;
+#[cfg(feature = "std")]
+pub mod frame_header;
+#[cfg(feature = "std")]
 pub mod frame_raw;
+#[cfg(feature = "std")]
 pub mod frame_xyz;
+#[cfg(feature = "std")]
 pub mod measurements;
+#[cfg(feature = "std")]
 pub mod point;
+#[cfg(feature = "std")]
+pub mod point_cloud;
+#[cfg(feature = "std")]
+pub mod range_image;
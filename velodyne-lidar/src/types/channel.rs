@@ -1,24 +1,23 @@
 use crate::packet::Channel;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct ChannelD {
-    pub strongest: Channel,
-    pub last: Channel,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct ChannelRefD<'a> {
-    pub strongest: &'a Channel,
-    pub last: &'a Channel,
-}
-
+/// The channels of a laser in every return.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChannelKind {
     Single(Channel),
-    Dual(ChannelD),
+    Dual([Channel; 2]),
+    Triple([Channel; 3]),
 }
 
 impl ChannelKind {
+    /// Gets the channels of the returns.
+    pub fn channels(&self) -> &[Channel] {
+        match self {
+            Self::Single(v) => core::slice::from_ref(v),
+            Self::Dual(v) => v,
+            Self::Triple(v) => v,
+        }
+    }
+
     pub fn try_into_single(self) -> Result<Channel, Self> {
         if let Self::Single(v) = self {
             Ok(v)
@@ -27,7 +26,7 @@ impl ChannelKind {
         }
     }
 
-    pub fn try_into_dual(self) -> Result<ChannelD, Self> {
+    pub fn try_into_dual(self) -> Result<[Channel; 2], Self> {
         if let Self::Dual(v) = self {
             Ok(v)
         } else {
@@ -35,6 +34,14 @@ impl ChannelKind {
         }
     }
 
+    pub fn try_into_triple(self) -> Result<[Channel; 3], Self> {
+        if let Self::Triple(v) = self {
+            Ok(v)
+        } else {
+            Err(self)
+        }
+    }
+
     pub fn as_single(&self) -> Option<&Channel> {
         if let Self::Single(v) = self {
             Some(v)
@@ -43,18 +50,20 @@ impl ChannelKind {
         }
     }
 
-    pub fn as_dual(&self) -> Option<&ChannelD> {
+    pub fn as_dual(&self) -> Option<&[Channel; 2]> {
         if let Self::Dual(v) = self {
             Some(v)
         } else {
             None
         }
     }
-}
 
-impl From<ChannelD> for ChannelKind {
-    fn from(v: ChannelD) -> Self {
-        Self::Dual(v)
+    pub fn as_triple(&self) -> Option<&[Channel; 3]> {
+        if let Self::Triple(v) = self {
+            Some(v)
+        } else {
+            None
+        }
     }
 }
 
@@ -63,3 +72,21 @@ impl From<Channel> for ChannelKind {
         Self::Single(v)
     }
 }
+
+impl From<[Channel; 1]> for ChannelKind {
+    fn from([v]: [Channel; 1]) -> Self {
+        Self::Single(v)
+    }
+}
+
+impl From<[Channel; 2]> for ChannelKind {
+    fn from(v: [Channel; 2]) -> Self {
+        Self::Dual(v)
+    }
+}
+
+impl From<[Channel; 3]> for ChannelKind {
+    fn from(v: [Channel; 3]) -> Self {
+        Self::Triple(v)
+    }
+}
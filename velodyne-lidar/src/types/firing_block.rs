@@ -1,175 +1,72 @@
 //! Firings of block references.
 
 use crate::{
-    convert::{
-        firing_block_to_xyz_d16, firing_block_to_xyz_d32, firing_block_to_xyz_s16,
-        firing_block_to_xyz_s32,
-    },
+    consts::CHANNELS_PER_BLOCK,
     packet::{Block, Channel},
     traits::FiringLike,
     types::{
-        channel::ChannelRefD,
-        firing_raw::{FiringRawD16, FiringRawD32, FiringRawS16, FiringRawS32},
-        firing_xyz::{FiringXyz, FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-        format::FormatKind,
+        firing_raw::FiringRawN,
+        format::{FormatKind, LayoutFamily, LayoutN, SupportedLayout},
     },
-    Config, Config16, Config32,
 };
-use eyre::{format_err, Result};
+use core::{array, marker::PhantomData, ops::Range, time::Duration};
 use measurements::Angle;
-use std::{ops::Range, time::Duration};
-
-use super::channel_array::{ChannelArrayD, ChannelArrayDRef, ChannelArraySRef};
 
+/// A firing of `LINES` lasers with `RETURNS` returns each, which borrows
+/// the channels from the blocks of a packet.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringBlockS16<'a> {
+pub struct FiringBlockN<'a, const LINES: usize, const RETURNS: usize> {
     pub toh: Duration,
     pub azimuth_range: Range<Angle>,
-    pub block: &'a Block,
-    pub channels: ChannelArraySRef<'a, 16>,
+    /// The group of blocks holding the returns of the firing.
+    pub blocks: &'a [Block],
+    /// Index of the first channel of the firing among the channels of a
+    /// return.
+    pub offset: usize,
 }
 
-impl<'a> FiringBlockS16<'a> {
-    pub fn to_firing_raw(&self) -> FiringRawS16 {
-        FiringRawS16 {
-            toh: self.toh,
-            azimuth_range: self.azimuth_range.clone(),
-            channels: *self.channels,
-        }
-    }
-
-    pub fn to_firing_xyz(&self, beams: &Config16) -> FiringXyzS16 {
-        firing_block_to_xyz_s16(self, beams)
-    }
-}
-
-impl<'a> FiringLike for FiringBlockS16<'a> {
-    type Point<'p>
-        = &'p Channel
-    where
-        Self: 'p;
-
-    fn start_toh(&self) -> Duration {
-        self.toh
-    }
+impl<'a, const LINES: usize, const RETURNS: usize> FiringBlockN<'a, LINES, RETURNS> {
+    const BLOCKS_PER_RETURN: usize = LayoutN::<LINES, RETURNS>::LAYOUT.blocks_per_return();
 
-    fn num_points(&self) -> usize {
-        self.channels.len()
-    }
+    /// Gets the channel of the laser in the return. The returns follow
+    /// the block order, where the strongest return precedes the last
+    /// return in dual return mode.
+    pub fn channel(&self, return_index: usize, laser_id: usize) -> &'a Channel {
+        assert!(return_index < RETURNS && laser_id < LINES);
 
-    fn point_at(&self, index: usize) -> Option<Self::Point<'_>> {
-        self.channels.get(index)
+        let index = self.offset + laser_id;
+        let block =
+            &self.blocks[return_index * Self::BLOCKS_PER_RETURN + index / CHANNELS_PER_BLOCK];
+        &block.channels[index % CHANNELS_PER_BLOCK]
     }
-}
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringBlockS32<'a> {
-    pub toh: Duration,
-    pub azimuth_range: Range<Angle>,
-    pub block: &'a Block,
-    pub channels: ChannelArraySRef<'a, 32>,
-}
+    /// Gets the firing of one return.
+    pub fn return_part(&self, return_index: usize) -> FiringBlockN<'a, LINES, 1> {
+        assert!(return_index < RETURNS);
 
-impl<'a> FiringBlockS32<'a> {
-    pub fn to_firing_raw(&self) -> FiringRawS32 {
-        FiringRawS32 {
+        let start = return_index * Self::BLOCKS_PER_RETURN;
+        FiringBlockN {
             toh: self.toh,
             azimuth_range: self.azimuth_range.clone(),
-            channels: *self.channels,
+            blocks: &self.blocks[start..start + Self::BLOCKS_PER_RETURN],
+            offset: self.offset,
         }
     }
 
-    pub fn to_firing_xyz(&self, beams: &Config32) -> FiringXyzS32 {
-        firing_block_to_xyz_s32(self, beams)
-    }
-}
-
-impl<'a> FiringLike for FiringBlockS32<'a> {
-    type Point<'p>
-        = &'p Channel
-    where
-        Self: 'p;
-
-    fn start_toh(&self) -> Duration {
-        self.toh
-    }
-
-    fn num_points(&self) -> usize {
-        self.channels.len()
-    }
-
-    fn point_at(&self, index: usize) -> Option<Self::Point<'_>> {
-        self.channels.get(index)
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringBlockD16<'a> {
-    pub toh: Duration,
-    pub azimuth_range: Range<Angle>,
-    pub block_strongest: &'a Block,
-    pub block_last: &'a Block,
-    pub channels: ChannelArrayDRef<'a, 16>,
-}
-
-impl<'a> FiringBlockD16<'a> {
-    pub fn to_firing_raw(&self) -> FiringRawD16 {
-        FiringRawD16 {
+    pub fn to_firing_raw(&self) -> FiringRawN<LINES, RETURNS> {
+        FiringRawN {
             toh: self.toh,
             azimuth_range: self.azimuth_range.clone(),
-            channels: ChannelArrayD {
-                strongest: *self.channels.strongest,
-                last: *self.channels.last,
-            },
-        }
-    }
-
-    pub fn to_firing_xyz(&self, beams: &Config16) -> FiringXyzD16 {
-        firing_block_to_xyz_d16(self, beams)
-    }
-
-    pub fn strongest_part(&self) -> FiringBlockS16<'a> {
-        let Self {
-            toh,
-            ref azimuth_range,
-            block_strongest: block,
-            channels:
-                ChannelArrayDRef {
-                    strongest: channels,
-                    ..
-                },
-            ..
-        } = *self;
-
-        FiringBlockS16 {
-            toh,
-            azimuth_range: azimuth_range.clone(),
-            block,
-            channels,
-        }
-    }
-
-    pub fn last_part(&self) -> FiringBlockS16<'a> {
-        let Self {
-            toh,
-            ref azimuth_range,
-            block_last: block,
-            channels: ChannelArrayDRef { last: channels, .. },
-            ..
-        } = *self;
-
-        FiringBlockS16 {
-            toh,
-            azimuth_range: azimuth_range.clone(),
-            block,
-            channels,
+            channels: array::from_fn(|return_index| {
+                array::from_fn(|laser_id| *self.channel(return_index, laser_id))
+            }),
         }
     }
 }
 
-impl<'a> FiringLike for FiringBlockD16<'a> {
+impl<'a, const LINES: usize, const RETURNS: usize> FiringLike for FiringBlockN<'a, LINES, RETURNS> {
     type Point<'p>
-        = ChannelRefD<'p>
+        = [&'p Channel; RETURNS]
     where
         Self: 'p;
 
@@ -178,153 +75,64 @@ impl<'a> FiringLike for FiringBlockD16<'a> {
     }
 
     fn num_points(&self) -> usize {
-        self.channels.strongest.len()
+        LINES
     }
 
     fn point_at(&self, index: usize) -> Option<Self::Point<'_>> {
-        let strongest = self.channels.strongest.get(index)?;
-        let last = self.channels.last.get(index)?;
-        Some(ChannelRefD { strongest, last })
+        (index < LINES).then(|| array::from_fn(|return_index| self.channel(return_index, index)))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringBlockD32<'a> {
-    pub toh: Duration,
-    pub azimuth_range: Range<Angle>,
-    pub block_strongest: &'a Block,
-    pub block_last: &'a Block,
-    pub channels: ChannelArrayDRef<'a, 32>,
-}
-
-impl<'a> FiringBlockD32<'a> {
-    pub fn to_firing_raw(&self) -> FiringRawD32 {
-        FiringRawD32 {
-            toh: self.toh,
-            azimuth_range: self.azimuth_range.clone(),
-            channels: ChannelArrayD {
-                strongest: *self.channels.strongest,
-                last: *self.channels.last,
-            },
-        }
-    }
-
-    pub fn to_firing_xyz(&self, beams: &Config32) -> FiringXyzD32 {
-        firing_block_to_xyz_d32(self, beams)
-    }
-
-    pub fn strongest_part(&self) -> FiringBlockS32<'a> {
-        let Self {
-            toh,
-            ref azimuth_range,
-            block_strongest: block,
-            channels:
-                ChannelArrayDRef {
-                    strongest: channels,
-                    ..
-                },
-            ..
-        } = *self;
+/// Selects [FiringBlockN] in a [FormatKind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FiringBlockFamily<'a>(PhantomData<&'a ()>);
 
-        FiringBlockS32 {
-            toh,
-            azimuth_range: azimuth_range.clone(),
-            block,
-            channels,
-        }
-    }
-
-    pub fn last_part(&self) -> FiringBlockS32<'a> {
-        let Self {
-            toh,
-            ref azimuth_range,
-            block_last: block,
-            channels: ChannelArrayDRef { last: channels, .. },
-            ..
-        } = *self;
-
-        FiringBlockS32 {
-            toh,
-            azimuth_range: azimuth_range.clone(),
-            block,
-            channels,
-        }
-    }
+impl<'a> LayoutFamily for FiringBlockFamily<'a> {
+    type Of<const LINES: usize, const RETURNS: usize> = FiringBlockN<'a, LINES, RETURNS>;
 }
 
-impl<'a> FiringLike for FiringBlockD32<'a> {
-    type Point<'p>
-        = ChannelRefD<'p>
-    where
-        Self: 'p;
+pub type FiringBlock<'a> = FormatKind<FiringBlockFamily<'a>>;
 
-    fn start_toh(&self) -> Duration {
-        self.toh
-    }
-
-    fn num_points(&self) -> usize {
-        self.channels.strongest.len()
-    }
-
-    fn point_at(&self, index: usize) -> Option<Self::Point<'_>> {
-        let strongest = self.channels.strongest.get(index)?;
-        let last = self.channels.last.get(index)?;
-        Some(ChannelRefD { strongest, last })
+impl<'a, const LINES: usize, const RETURNS: usize> From<FiringBlockN<'a, LINES, RETURNS>>
+    for FiringBlock<'a>
+where
+    LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+{
+    fn from(v: FiringBlockN<'a, LINES, RETURNS>) -> Self {
+        Self::from_layout(v)
     }
 }
 
-pub type FiringBlock<'a> =
-    FormatKind<FiringBlockS16<'a>, FiringBlockS32<'a>, FiringBlockD16<'a>, FiringBlockD32<'a>>;
-
-impl<'a> FiringBlock<'a> {
-    pub fn to_firing_xyz(&self, beams: &Config) -> Result<FiringXyz> {
-        let err = || format_err!("TODO");
-
-        use FormatKind as F;
-
-        let output = match self {
-            F::Single16(inner) => {
-                let beams: Config16 = beams.clone().try_into().map_err(|_| err())?;
-                inner.to_firing_xyz(&beams).into()
-            }
-            F::Dual16(inner) => {
-                let beams: Config16 = beams.clone().try_into().map_err(|_| err())?;
-                inner.to_firing_xyz(&beams).into()
-            }
-            F::Single32(inner) => {
-                let beams: Config32 = beams.clone().try_into().map_err(|_| err())?;
-                inner.to_firing_xyz(&beams).into()
-            }
-            F::Dual32(inner) => {
-                let beams: Config32 = beams.clone().try_into().map_err(|_| err())?;
-                inner.to_firing_xyz(&beams).into()
-            }
-        };
+#[cfg(feature = "std")]
+mod xyz {
+    use super::{FiringBlock, FiringBlockN};
+    use crate::{
+        converter::{Converter, ConverterN},
+        types::{firing_polar::FiringPolarN, firing_xyz::FiringXyz, firing_xyz::FiringXyzN},
+        Config, ConfigN,
+    };
+    use eyre::Result;
 
-        Ok(output)
-    }
-}
-
-impl<'a> From<FiringBlockD32<'a>> for FiringBlock<'a> {
-    fn from(v: FiringBlockD32<'a>) -> Self {
-        Self::Dual32(v)
-    }
-}
-
-impl<'a> From<FiringBlockD16<'a>> for FiringBlock<'a> {
-    fn from(v: FiringBlockD16<'a>) -> Self {
-        Self::Dual16(v)
-    }
-}
+    impl<'a, const LINES: usize, const RETURNS: usize> FiringBlockN<'a, LINES, RETURNS> {
+        #[deprecated(note = "prepares the beams on every call, use `ConverterN` instead")]
+        pub fn to_firing_xyz(&self, beams: &ConfigN<LINES>) -> FiringXyzN<LINES, RETURNS> {
+            ConverterN::new(beams).firing_block_to_xyz(self)
+        }
 
-impl<'a> From<FiringBlockS32<'a>> for FiringBlock<'a> {
-    fn from(v: FiringBlockS32<'a>) -> Self {
-        Self::Single32(v)
+        /// Converts to polar coordinates without computing the Cartesian
+        /// coordinates. The converter is meant to be reused across firings.
+        pub fn to_firing_polar(
+            &self,
+            converter: &ConverterN<LINES, RETURNS>,
+        ) -> FiringPolarN<LINES, RETURNS> {
+            converter.firing_block_to_polar(self)
+        }
     }
-}
 
-impl<'a> From<FiringBlockS16<'a>> for FiringBlock<'a> {
-    fn from(v: FiringBlockS16<'a>) -> Self {
-        Self::Single16(v)
+    impl<'a> FiringBlock<'a> {
+        #[deprecated(note = "prepares the beams on every call, use `Converter` instead")]
+        pub fn to_firing_xyz(&self, beams: &Config) -> Result<FiringXyz> {
+            Converter::new(beams)?.firing_block_to_xyz(self)
+        }
     }
 }
@@ -0,0 +1,42 @@
+//! Firings of returns in polar coordinates.
+
+use measurements::{Angle, Length};
+use std::{ops::Range, time::Duration};
+
+/// A return without Cartesian coordinates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolarMeasurement {
+    pub distance: Length,
+    pub intensity: u8,
+    /// False if the laser reported no return or the return was rejected
+    /// by the range gate.
+    pub valid: bool,
+}
+
+/// A point with `RETURNS` returns in polar coordinates.
+///
+/// The azimuth and the elevation are those of the beam. They differ
+/// slightly from the direction of the point seen from the sensor origin
+/// due to the beam offsets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointPolarN<const RETURNS: usize> {
+    pub laser_id: usize,
+    pub ring: usize,
+    pub toh: Duration,
+    pub azimuth: Angle,
+    pub elevation: Angle,
+    /// The returns in the block order, where the strongest return
+    /// precedes the last return in dual return mode.
+    pub measurements: [PolarMeasurement; RETURNS],
+}
+
+pub type PointPolarS = PointPolarN<1>;
+pub type PointPolarD = PointPolarN<2>;
+pub type PointPolarT = PointPolarN<3>;
+
+#[derive(Debug, Clone)]
+pub struct FiringPolarN<const LINES: usize, const RETURNS: usize> {
+    pub toh: Duration,
+    pub azimuth_range: Range<Angle>,
+    pub points: [PointPolarN<RETURNS>; LINES],
+}
@@ -1,120 +1,117 @@
 //! Firings of blocks.
 
-use crate::types::format::FormatKind;
+use crate::{
+    packet::Channel,
+    traits::FiringLike,
+    types::format::{dispatch, FormatKind, LayoutFamily, LayoutN, SupportedLayout},
+};
+use core::{array, marker::PhantomData, ops::Range, time::Duration};
 use measurements::Angle;
-use std::{ops::Range, time::Duration};
 
+/// A firing of `LINES` lasers with `RETURNS` returns each, which owns
+/// the channels.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringRawS16 {
+pub struct FiringRawN<const LINES: usize, const RETURNS: usize> {
     pub toh: Duration,
     pub azimuth_range: Range<Angle>,
-    pub channels: ChannelArrayS<16>,
+    /// The channels of every return, where the strongest return
+    /// precedes the last return in dual return mode.
+    pub channels: [[Channel; LINES]; RETURNS],
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringRawS32 {
-    pub toh: Duration,
-    pub azimuth_range: Range<Angle>,
-    pub channels: ChannelArrayS<32>,
+impl<const LINES: usize, const RETURNS: usize> FiringRawN<LINES, RETURNS> {
+    /// Gets the channel of the laser in the return.
+    pub fn channel(&self, return_index: usize, laser_id: usize) -> &Channel {
+        &self.channels[return_index][laser_id]
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringRawD16 {
-    pub toh: Duration,
-    pub azimuth_range: Range<Angle>,
-    pub channels: ChannelArrayD<16>,
-}
+impl<const LINES: usize, const RETURNS: usize> FiringLike for FiringRawN<LINES, RETURNS> {
+    type Point<'p> = [&'p Channel; RETURNS];
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct FiringRawD32 {
-    pub toh: Duration,
-    pub azimuth_range: Range<Angle>,
-    pub channels: ChannelArrayD<32>,
+    fn start_toh(&self) -> Duration {
+        self.toh
+    }
+
+    fn num_points(&self) -> usize {
+        LINES
+    }
+
+    fn point_at(&self, index: usize) -> Option<Self::Point<'_>> {
+        (index < LINES).then(|| array::from_fn(|return_index| self.channel(return_index, index)))
+    }
 }
 
 pub use kind::*;
 mod kind {
     use super::*;
 
-    pub type FiringRaw = FormatKind<FiringRawS16, FiringRawS32, FiringRawD16, FiringRawD32>;
-
-    impl FiringRaw {
-        pub fn time(&self) -> Duration {
-            match self {
-                FiringRaw::Single16(me) => me.toh,
-                FiringRaw::Single32(me) => me.toh,
-                FiringRaw::Dual16(me) => me.toh,
-                FiringRaw::Dual32(me) => me.toh,
-            }
-        }
-    }
+    /// Selects [FiringRawN] in a [FormatKind].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FiringRawFamily;
 
-    impl From<FiringRawD32> for FiringRaw {
-        fn from(v: FiringRawD32) -> Self {
-            Self::Dual32(v)
-        }
+    impl LayoutFamily for FiringRawFamily {
+        type Of<const LINES: usize, const RETURNS: usize> = FiringRawN<LINES, RETURNS>;
     }
 
-    impl From<FiringRawD16> for FiringRaw {
-        fn from(v: FiringRawD16) -> Self {
-            Self::Dual16(v)
-        }
-    }
+    pub type FiringRaw = FormatKind<FiringRawFamily>;
 
-    impl From<FiringRawS32> for FiringRaw {
-        fn from(v: FiringRawS32) -> Self {
-            Self::Single32(v)
+    impl FiringRaw {
+        pub fn time(&self) -> Duration {
+            dispatch!(self, me => me.toh)
         }
     }
 
-    impl From<FiringRawS16> for FiringRaw {
-        fn from(v: FiringRawS16) -> Self {
-            Self::Single16(v)
+    impl<const LINES: usize, const RETURNS: usize> From<FiringRawN<LINES, RETURNS>> for FiringRaw
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        fn from(v: FiringRawN<LINES, RETURNS>) -> Self {
+            Self::from_layout(v)
         }
     }
 }
 
 pub use ref_kind::*;
-
-use super::channel_array::{ChannelArrayD, ChannelArrayS};
 mod ref_kind {
     use super::*;
 
-    pub type FiringRawRef<'a> =
-        FormatKind<&'a FiringRawS16, &'a FiringRawS32, &'a FiringRawD16, &'a FiringRawD32>;
+    /// Selects references to [FiringRawN] in a [FormatKind].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FiringRawRefFamily<'a>(PhantomData<&'a ()>);
 
-    impl<'a> FiringRawRef<'a> {
-        pub fn time(&self) -> Duration {
-            match self {
-                FiringRawRef::Single16(me) => me.toh,
-                FiringRawRef::Single32(me) => me.toh,
-                FiringRawRef::Dual16(me) => me.toh,
-                FiringRawRef::Dual32(me) => me.toh,
-            }
-        }
+    impl<'a> LayoutFamily for FiringRawRefFamily<'a> {
+        type Of<const LINES: usize, const RETURNS: usize> = &'a FiringRawN<LINES, RETURNS>;
     }
 
-    impl<'a> From<&'a FiringRawD32> for FiringRawRef<'a> {
-        fn from(v: &'a FiringRawD32) -> Self {
-            Self::Dual32(v)
-        }
-    }
+    pub type FiringRawRef<'a> = FormatKind<FiringRawRefFamily<'a>>;
 
-    impl<'a> From<&'a FiringRawD16> for FiringRawRef<'a> {
-        fn from(v: &'a FiringRawD16) -> Self {
-            Self::Dual16(v)
+    impl<'a> FiringRawRef<'a> {
+        pub fn time(&self) -> Duration {
+            dispatch!(self, me => me.toh)
         }
     }
 
-    impl<'a> From<&'a FiringRawS32> for FiringRawRef<'a> {
-        fn from(v: &'a FiringRawS32) -> Self {
-            Self::Single32(v)
+    impl<'a, const LINES: usize, const RETURNS: usize> From<&'a FiringRawN<LINES, RETURNS>>
+        for FiringRawRef<'a>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        fn from(v: &'a FiringRawN<LINES, RETURNS>) -> Self {
+            Self::from_layout(v)
         }
     }
+}
+
+#[cfg(feature = "std")]
+mod xyz {
+    use super::FiringRawN;
+    use crate::{converter::ConverterN, types::firing_xyz::FiringXyzN, ConfigN};
 
-    impl<'a> From<&'a FiringRawS16> for FiringRawRef<'a> {
-        fn from(v: &'a FiringRawS16) -> Self {
-            Self::Single16(v)
+    impl<const LINES: usize, const RETURNS: usize> FiringRawN<LINES, RETURNS> {
+        #[deprecated(note = "prepares the beams on every call, use `ConverterN` instead")]
+        pub fn to_firing_xyz(&self, beams: &ConfigN<LINES>) -> FiringXyzN<LINES, RETURNS> {
+            ConverterN::new(beams).firing_raw_to_xyz(self)
         }
     }
 }
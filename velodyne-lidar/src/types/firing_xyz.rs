@@ -1,145 +1,128 @@
 //! Firings in 3D Cartesian coordinates.
 
-use crate::types::{
-    format::FormatKind,
-    point::{PointD, PointS},
-};
+use crate::{traits::FiringLike, types::point::PointN};
 use measurements::Angle;
 use std::{ops::Range, time::Duration};
 
-macro_rules! declare_firing_xyz {
-    ($name:ident, $size:expr, $point:path) => {
-        #[derive(Debug, Clone, PartialEq, Eq)]
-        pub struct $name {
-            pub toh: Duration,
-            pub azimuth_range: Range<Angle>,
-            pub points: [$point; $size],
-        }
-    };
+/// A firing of `LINES` lasers with `RETURNS` returns each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiringXyzN<const LINES: usize, const RETURNS: usize> {
+    pub toh: Duration,
+    pub azimuth_range: Range<Angle>,
+    pub points: [PointN<RETURNS>; LINES],
+}
+
+impl<const LINES: usize, const RETURNS: usize> FiringXyzN<LINES, RETURNS> {
+    /// Gets the point of the laser on the ring.
+    pub fn point_at_ring(&self, ring: usize) -> Option<&PointN<RETURNS>> {
+        self.points.iter().find(|point| point.ring == ring)
+    }
 }
 
-declare_firing_xyz!(FiringXyzS16, 16, PointS);
-declare_firing_xyz!(FiringXyzS32, 32, PointS);
-declare_firing_xyz!(FiringXyzD16, 16, PointD);
-declare_firing_xyz!(FiringXyzD32, 32, PointD);
+impl<const LINES: usize, const RETURNS: usize> FiringLike for FiringXyzN<LINES, RETURNS> {
+    type Point<'p>
+        = &'p PointN<RETURNS>
+    where
+        Self: 'p;
+
+    fn start_toh(&self) -> Duration {
+        self.toh
+    }
+
+    fn num_points(&self) -> usize {
+        self.points.len()
+    }
+
+    fn point_at(&self, index: usize) -> Option<Self::Point<'_>> {
+        self.points.get(index)
+    }
+}
 
 pub use kind::*;
 mod kind {
-    use super::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32};
+    use super::FiringXyzN;
     use crate::{
         traits::BoxIterator,
         types::{
-            format::FormatKind,
+            format::{dispatch, FormatKind, LayoutFamily, LayoutN, SupportedLayout},
             point::{Point, PointRef},
         },
     };
     use std::time::Duration;
 
-    pub type FiringXyz = FormatKind<FiringXyzS16, FiringXyzS32, FiringXyzD16, FiringXyzD32>;
+    /// Selects [FiringXyzN] in a [FormatKind].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FiringXyzFamily;
+
+    impl LayoutFamily for FiringXyzFamily {
+        type Of<const LINES: usize, const RETURNS: usize> = FiringXyzN<LINES, RETURNS>;
+    }
+
+    pub type FiringXyz = FormatKind<FiringXyzFamily>;
 
     impl FiringXyz {
         pub fn time(&self) -> Duration {
-            match self {
-                FiringXyz::Single16(me) => me.toh,
-                FiringXyz::Single32(me) => me.toh,
-                FiringXyz::Dual16(me) => me.toh,
-                FiringXyz::Dual32(me) => me.toh,
-            }
+            dispatch!(self, me => me.toh)
         }
 
         pub fn point_iter(&self) -> BoxIterator<'_, PointRef<'_>> {
-            match self {
-                FiringXyz::Single16(me) => Box::new(me.points.iter().map(PointRef::from)),
-                FiringXyz::Single32(me) => Box::new(me.points.iter().map(PointRef::from)),
-                FiringXyz::Dual16(me) => Box::new(me.points.iter().map(PointRef::from)),
-                FiringXyz::Dual32(me) => Box::new(me.points.iter().map(PointRef::from)),
-            }
+            dispatch!(self, me => Box::new(me.points.iter().map(PointRef::from)))
         }
 
         pub fn into_point_iter(self) -> BoxIterator<'static, Point> {
-            match self {
-                FiringXyz::Single16(me) => Box::new(me.points.into_iter().map(Point::from)),
-                FiringXyz::Single32(me) => Box::new(me.points.into_iter().map(Point::from)),
-                FiringXyz::Dual16(me) => Box::new(me.points.into_iter().map(Point::from)),
-                FiringXyz::Dual32(me) => Box::new(me.points.into_iter().map(Point::from)),
-            }
-        }
-    }
-
-    impl From<FiringXyzD32> for FiringXyz {
-        fn from(v: FiringXyzD32) -> Self {
-            Self::Dual32(v)
-        }
-    }
-
-    impl From<FiringXyzD16> for FiringXyz {
-        fn from(v: FiringXyzD16) -> Self {
-            Self::Dual16(v)
+            dispatch!(self, me => Box::new(me.points.into_iter().map(Point::from)))
         }
     }
 
-    impl From<FiringXyzS32> for FiringXyz {
-        fn from(v: FiringXyzS32) -> Self {
-            Self::Single32(v)
-        }
-    }
-
-    impl From<FiringXyzS16> for FiringXyz {
-        fn from(v: FiringXyzS16) -> Self {
-            Self::Single16(v)
+    impl<const LINES: usize, const RETURNS: usize> From<FiringXyzN<LINES, RETURNS>> for FiringXyz
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        fn from(v: FiringXyzN<LINES, RETURNS>) -> Self {
+            Self::from_layout(v)
         }
     }
 }
 
 pub use ref_kind::*;
 mod ref_kind {
-    use super::*;
-    use crate::{traits::BoxIterator, types::point::PointRef};
-
-    pub type FiringXyzRef<'a> =
-        FormatKind<&'a FiringXyzS16, &'a FiringXyzS32, &'a FiringXyzD16, &'a FiringXyzD32>;
+    use super::FiringXyzN;
+    use crate::{
+        traits::BoxIterator,
+        types::{
+            format::{dispatch, FormatKind, LayoutFamily, LayoutN, SupportedLayout},
+            point::PointRef,
+        },
+    };
+    use std::{marker::PhantomData, time::Duration};
 
-    impl<'a> FiringXyzRef<'a> {
-        pub fn time(&self) -> Duration {
-            match self {
-                FiringXyzRef::Single16(me) => me.toh,
-                FiringXyzRef::Single32(me) => me.toh,
-                FiringXyzRef::Dual16(me) => me.toh,
-                FiringXyzRef::Dual32(me) => me.toh,
-            }
-        }
+    /// Selects references to [FiringXyzN] in a [FormatKind].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FiringXyzRefFamily<'a>(PhantomData<&'a ()>);
 
-        pub fn point_iter(&self) -> BoxIterator<'_, PointRef<'_>> {
-            match self {
-                FiringXyzRef::Single16(me) => Box::new(me.points.iter().map(PointRef::from)),
-                FiringXyzRef::Single32(me) => Box::new(me.points.iter().map(PointRef::from)),
-                FiringXyzRef::Dual16(me) => Box::new(me.points.iter().map(PointRef::from)),
-                FiringXyzRef::Dual32(me) => Box::new(me.points.iter().map(PointRef::from)),
-            }
-        }
+    impl<'a> LayoutFamily for FiringXyzRefFamily<'a> {
+        type Of<const LINES: usize, const RETURNS: usize> = &'a FiringXyzN<LINES, RETURNS>;
     }
 
-    impl<'a> From<&'a FiringXyzD32> for FiringXyzRef<'a> {
-        fn from(v: &'a FiringXyzD32) -> Self {
-            Self::Dual32(v)
-        }
-    }
+    pub type FiringXyzRef<'a> = FormatKind<FiringXyzRefFamily<'a>>;
 
-    impl<'a> From<&'a FiringXyzD16> for FiringXyzRef<'a> {
-        fn from(v: &'a FiringXyzD16) -> Self {
-            Self::Dual16(v)
+    impl<'a> FiringXyzRef<'a> {
+        pub fn time(&self) -> Duration {
+            dispatch!(self, me => me.toh)
         }
-    }
 
-    impl<'a> From<&'a FiringXyzS32> for FiringXyzRef<'a> {
-        fn from(v: &'a FiringXyzS32) -> Self {
-            Self::Single32(v)
+        pub fn point_iter(&self) -> BoxIterator<'_, PointRef<'_>> {
+            dispatch!(self, me => Box::new(me.points.iter().map(PointRef::from)))
         }
     }
 
-    impl<'a> From<&'a FiringXyzS16> for FiringXyzRef<'a> {
-        fn from(v: &'a FiringXyzS16) -> Self {
-            Self::Single16(v)
+    impl<'a, const LINES: usize, const RETURNS: usize> From<&'a FiringXyzN<LINES, RETURNS>>
+        for FiringXyzRef<'a>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        fn from(v: &'a FiringXyzN<LINES, RETURNS>) -> Self {
+            Self::from_layout(v)
         }
     }
 }
@@ -1,261 +1,431 @@
 //! Define the formats of point arrangements.
+//!
+//! A format is keyed by its [Layout], the number of lasers and the
+//! number of returns per firing. The firing, frame, converter and
+//! decoder types are generic over both numbers, and the runtime kinds
+//! are [FormatKind]s selecting one of them per supported layout.
+//!
+//! The supported layouts are listed once in this module. A new sensor
+//! model needs a line in the list, whose firings must be packable into
+//! a data packet, and its entry in [crate::timing::TimingModel].
 
 use crate::{
+    consts::{BLOCKS_PER_PACKET, CHANNELS_PER_BLOCK, FIRING_PERIOD},
     packet::{ProductID, ReturnMode},
-    traits::{AzimuthRange, PointField},
+    traits::AzimuthRange,
 };
-use eyre::Result;
+use core::{ops::Range, time::Duration};
 use measurements::Angle;
-use std::ops::Range;
 
-/// An enumeration of point arrangement formats.
+/// The arrangement of firings in a data packet, given by the number of
+/// lasers and returns.
+///
+/// A firing fills `num_lines` channels for each return. The channels of
+/// a return lie in consecutive blocks, or share a block with the other
+/// firings of the block if there are fewer lasers than channels. The
+/// returns of the same firings lie in consecutive blocks, which form a
+/// group of blocks with the same azimuth.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Format {
-    Single16,
-    Single32,
-    Dual16,
-    Dual32,
+pub struct Layout {
+    /// Number of lasers.
+    pub num_lines: usize,
+    /// Number of returns of each laser per firing.
+    pub num_returns: usize,
 }
 
-impl Format {
-    pub fn try_new(num_lines: usize, return_mode: ReturnMode) -> Option<Format> {
-        use Format::*;
-        use ReturnMode::*;
-
-        Some(match (num_lines, return_mode) {
-            (16, Strongest | Last) => Single16,
-            (16, Dual) => Dual16,
-            (32, Strongest | Last) => Single32,
-            (32, Dual) => Dual32,
-            _ => return None,
-        })
-    }
+impl Layout {
+    pub fn new(num_lines: usize, return_mode: ReturnMode) -> Self {
+        let num_returns = match return_mode {
+            ReturnMode::Strongest | ReturnMode::Last => 1,
+            ReturnMode::Dual => 2,
+        };
 
-    pub fn new(num_lines: usize, return_mode: ReturnMode) -> Format {
-        Self::try_new(num_lines, return_mode).unwrap()
+        Self {
+            num_lines,
+            num_returns,
+        }
     }
 
-    pub fn try_from_model(product_id: ProductID, return_mode: ReturnMode) -> Option<Format> {
-        Self::try_new(product_id.num_lines(), return_mode)
+    /// Checks if the firings fill whole blocks and the groups of blocks
+    /// fill the packet.
+    pub const fn is_packable(&self) -> bool {
+        let Self {
+            num_lines,
+            num_returns,
+        } = *self;
+
+        num_lines > 0
+            && num_returns > 0
+            && (CHANNELS_PER_BLOCK.is_multiple_of(num_lines)
+                || num_lines.is_multiple_of(CHANNELS_PER_BLOCK))
+            && BLOCKS_PER_PACKET.is_multiple_of(self.blocks_per_group())
     }
 
-    pub fn from_model(product_id: ProductID, return_mode: ReturnMode) -> Format {
-        Self::try_from_model(product_id, return_mode).unwrap()
+    /// Number of blocks holding one return of a firing.
+    pub const fn blocks_per_return(&self) -> usize {
+        self.num_lines.div_ceil(CHANNELS_PER_BLOCK)
     }
-}
 
-/// An enumeration of point arrangement formats with additional generics.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum FormatKind<S16, S32, D16, D32> {
-    Single16(S16),
-    Single32(S32),
-    Dual16(D16),
-    Dual32(D32),
-}
-
-impl<S16, S32, D16, D32> FormatKind<S16, S32, D16, D32> {
-    pub fn from_format_default(format: Format) -> Self
-    where
-        S16: Default,
-        S32: Default,
-        D16: Default,
-        D32: Default,
-    {
-        match format {
-            Format::Single16 => Self::Single16(S16::default()),
-            Format::Single32 => Self::Single32(S32::default()),
-            Format::Dual16 => Self::Dual16(D16::default()),
-            Format::Dual32 => Self::Dual32(D32::default()),
-        }
+    /// Number of blocks holding all returns of the firings in a group.
+    pub const fn blocks_per_group(&self) -> usize {
+        self.blocks_per_return() * self.num_returns
     }
 
-    pub fn format(&self) -> Format {
-        match self {
-            FormatKind::Single16(_) => Format::Single16,
-            FormatKind::Single32(_) => Format::Single32,
-            FormatKind::Dual16(_) => Format::Dual16,
-            FormatKind::Dual32(_) => Format::Dual32,
+    /// Number of firings sharing a group of blocks.
+    pub const fn firings_per_group(&self) -> usize {
+        if self.num_lines < CHANNELS_PER_BLOCK {
+            CHANNELS_PER_BLOCK / self.num_lines
+        } else {
+            1
         }
     }
 
-    pub fn from_s16(from: S16) -> Self {
-        Self::Single16(from)
+    /// Number of groups of blocks in a data packet.
+    pub const fn num_groups(&self) -> usize {
+        BLOCKS_PER_PACKET / self.blocks_per_group()
     }
 
-    pub fn from_s32(from: S32) -> Self {
-        Self::Single32(from)
+    /// Number of firings carried by one data packet.
+    pub const fn num_firings_per_packet(&self) -> usize {
+        self.num_groups() * self.firings_per_group()
     }
+}
 
-    pub fn from_d16(from: D16) -> Self {
-        Self::Dual16(from)
-    }
+/// The layout of `LINES` lasers with `RETURNS` returns as a type.
+///
+/// It implements [SupportedLayout] for the supported layouts, which
+/// bounds the generic code converting to and from the runtime kinds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LayoutN<const LINES: usize, const RETURNS: usize>;
+
+impl<const LINES: usize, const RETURNS: usize> LayoutN<LINES, RETURNS> {
+    pub const LAYOUT: Layout = Layout {
+        num_lines: LINES,
+        num_returns: RETURNS,
+    };
+}
 
-    pub fn from_d32(from: D32) -> Self {
-        Self::Dual32(from)
-    }
+/// A family of types generic over the layout, which is selected per
+/// variant of a [FormatKind].
+pub trait LayoutFamily {
+    type Of<const LINES: usize, const RETURNS: usize>;
+}
+
+/// A supported layout with its variant in the runtime kinds.
+pub trait SupportedLayout<const LINES: usize, const RETURNS: usize> {
+    const FORMAT: Format;
+
+    fn wrap<F: LayoutFamily>(inner: F::Of<LINES, RETURNS>) -> FormatKind<F>;
+
+    fn unwrap<F: LayoutFamily>(kind: FormatKind<F>)
+        -> Result<F::Of<LINES, RETURNS>, FormatKind<F>>;
+
+    fn get<F: LayoutFamily>(kind: &FormatKind<F>) -> Option<&F::Of<LINES, RETURNS>>;
 
-    pub fn try_into_s16(self) -> Result<S16, Self> {
-        match self {
-            Self::Single16(s16) => Ok(s16),
-            _ => Err(self),
+    fn get_mut<F: LayoutFamily>(kind: &mut FormatKind<F>) -> Option<&mut F::Of<LINES, RETURNS>>;
+}
+
+/// Declares the formats and the runtime kinds from the list of
+/// supported layouts.
+///
+/// It also declares the `dispatch!`, `map_kind!` and
+/// `dispatch_format!` macros, which expand an expression for every
+/// variant of a [FormatKind] or a [Format], and the `with_layouts!`
+/// macro, which passes the list to another macro.
+macro_rules! declare_layouts {
+    ($d:tt; $(($variant:ident, $lines:literal, $returns:literal)),* $(,)?) => {
+        /// An enumeration of point arrangement formats.
+        ///
+        /// Each format is a supported [Layout].
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Format {
+            $($variant,)*
         }
-    }
 
-    pub fn try_into_s32(self) -> Result<S32, Self> {
-        match self {
-            Self::Single32(s16) => Ok(s16),
-            _ => Err(self),
+        impl Format {
+            /// All supported formats.
+            pub const ALL: &'static [Format] = &[$(Format::$variant),*];
+
+            /// The arrangement of the firings in a data packet.
+            pub const fn layout(&self) -> Layout {
+                match self {
+                    $(Format::$variant => LayoutN::<$lines, $returns>::LAYOUT,)*
+                }
+            }
         }
-    }
 
-    pub fn try_into_d16(self) -> Result<D16, Self> {
-        match self {
-            Self::Dual16(s16) => Ok(s16),
-            _ => Err(self),
+        /// An enumeration of point arrangement formats with a type of
+        /// the family per format.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum FormatKind<F: LayoutFamily> {
+            $($variant(F::Of<$lines, $returns>),)*
         }
-    }
 
-    pub fn try_into_d32(self) -> Result<D32, Self> {
-        match self {
-            Self::Dual32(s16) => Ok(s16),
-            _ => Err(self),
+        impl<F: LayoutFamily> FormatKind<F> {
+            pub fn format(&self) -> Format {
+                match self {
+                    $(FormatKind::$variant(_) => Format::$variant,)*
+                }
+            }
         }
-    }
 
-    pub fn as_s16(&self) -> Option<&S16> {
-        match self {
-            Self::Single16(s16) => Some(s16),
-            _ => None,
+        impl<F> AzimuthRange for FormatKind<F>
+        where
+            F: LayoutFamily,
+            $(F::Of<$lines, $returns>: AzimuthRange,)*
+        {
+            fn azimuth_range(&self) -> Range<Angle> {
+                match self {
+                    $(FormatKind::$variant(inner) => inner.azimuth_range(),)*
+                }
+            }
         }
-    }
 
-    pub fn as_s32(&self) -> Option<&S32> {
-        match self {
-            Self::Single32(s32) => Some(s32),
-            _ => None,
+        $(
+            const _: () = assert!(LayoutN::<$lines, $returns>::LAYOUT.is_packable());
+
+            impl SupportedLayout<$lines, $returns> for LayoutN<$lines, $returns> {
+                const FORMAT: Format = Format::$variant;
+
+                fn wrap<F: LayoutFamily>(inner: F::Of<$lines, $returns>) -> FormatKind<F> {
+                    FormatKind::$variant(inner)
+                }
+
+                fn unwrap<F: LayoutFamily>(
+                    kind: FormatKind<F>,
+                ) -> Result<F::Of<$lines, $returns>, FormatKind<F>> {
+                    match kind {
+                        FormatKind::$variant(inner) => Ok(inner),
+                        kind => Err(kind),
+                    }
+                }
+
+                fn get<F: LayoutFamily>(kind: &FormatKind<F>) -> Option<&F::Of<$lines, $returns>> {
+                    match kind {
+                        FormatKind::$variant(inner) => Some(inner),
+                        _ => None,
+                    }
+                }
+
+                fn get_mut<F: LayoutFamily>(
+                    kind: &mut FormatKind<F>,
+                ) -> Option<&mut F::Of<$lines, $returns>> {
+                    match kind {
+                        FormatKind::$variant(inner) => Some(inner),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+
+        /// Evaluates the expression on the inner value of any variant.
+        ///
+        /// The `<LINES, RETURNS>` form also declares the numbers of the
+        /// variant as constants of the given names.
+        #[allow(unused_macros)]
+        macro_rules! dispatch {
+            ($d kind:expr, <$d lines:ident, $d returns:ident> $d inner:pat => $d body:expr) => {
+                match $d kind {
+                    $(
+                        $crate::types::format::FormatKind::$variant($d inner) => {
+                            #[allow(dead_code)]
+                            const $d lines: usize = $lines;
+                            #[allow(dead_code)]
+                            const $d returns: usize = $returns;
+                            $d body
+                        }
+                    )*
+                }
+            };
+            ($d kind:expr, $d inner:pat => $d body:expr) => {
+                match $d kind {
+                    $($crate::types::format::FormatKind::$variant($d inner) => $d body,)*
+                }
+            };
         }
-    }
 
-    pub fn as_d16(&self) -> Option<&D16> {
-        match self {
-            Self::Dual16(d16) => Some(d16),
-            _ => None,
+        /// Maps the inner value of any variant to the same variant of
+        /// another kind.
+        #[allow(unused_macros)]
+        macro_rules! map_kind {
+            ($d kind:expr, $d inner:pat => $d body:expr) => {
+                match $d kind {
+                    $(
+                        $crate::types::format::FormatKind::$variant($d inner) => {
+                            $crate::types::format::FormatKind::$variant($d body)
+                        }
+                    )*
+                }
+            };
         }
-    }
 
-    pub fn as_d32(&self) -> Option<&D32> {
-        match self {
-            Self::Dual32(d32) => Some(d32),
-            _ => None,
+        /// Evaluates the expression for the format, where the numbers
+        /// of the format are declared as constants of the given names.
+        #[allow(unused_macros)]
+        macro_rules! dispatch_format {
+            ($d format:expr, <$d lines:ident, $d returns:ident> => $d body:expr) => {
+                match $d format {
+                    $(
+                        $crate::types::format::Format::$variant => {
+                            #[allow(dead_code)]
+                            const $d lines: usize = $lines;
+                            #[allow(dead_code)]
+                            const $d returns: usize = $returns;
+                            $d body
+                        }
+                    )*
+                }
+            };
         }
-    }
 
-    pub fn as_s16_mut(&mut self) -> Option<&mut S16> {
-        match self {
-            Self::Single16(s16) => Some(s16),
-            _ => None,
+        /// Passes the list of `(variant, lines, returns)` to the macro.
+        #[allow(unused_macros)]
+        macro_rules! with_layouts {
+            ($d callback:ident) => {
+                $d callback! { $(($variant, $lines, $returns)),* }
+            };
         }
+
+        #[allow(unused_imports)]
+        pub(crate) use {dispatch, dispatch_format, map_kind, with_layouts};
+    };
+}
+
+// The dual return mode of VLS-128 spans eight blocks per firing, which
+// does not divide the packet.
+declare_layouts! {$;
+    (Single16, 16, 1),
+    (Dual16, 16, 2),
+    (Triple16, 16, 3),
+    (Single32, 32, 1),
+    (Dual32, 32, 2),
+    (Triple32, 32, 3),
+    (Single64, 64, 1),
+    (Dual64, 64, 2),
+    (Triple64, 64, 3),
+    (Single128, 128, 1),
+    (Triple128, 128, 3),
+}
+
+impl Format {
+    pub fn try_new(num_lines: usize, return_mode: ReturnMode) -> Option<Format> {
+        Self::try_from_layout(Layout::new(num_lines, return_mode))
     }
 
-    pub fn as_s32_mut(&mut self) -> Option<&mut S32> {
-        match self {
-            Self::Single32(s32) => Some(s32),
-            _ => None,
-        }
+    pub fn new(num_lines: usize, return_mode: ReturnMode) -> Format {
+        Self::try_new(num_lines, return_mode).unwrap()
     }
 
-    pub fn as_d16_mut(&mut self) -> Option<&mut D16> {
-        match self {
-            Self::Dual16(d16) => Some(d16),
-            _ => None,
-        }
+    /// Finds the format of the layout.
+    pub fn try_from_layout(layout: Layout) -> Option<Format> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.layout() == layout)
     }
 
-    pub fn as_d32_mut(&mut self) -> Option<&mut D32> {
-        match self {
-            Self::Dual32(d32) => Some(d32),
-            _ => None,
-        }
+    pub fn try_from_model(product_id: ProductID, return_mode: ReturnMode) -> Option<Format> {
+        Self::try_new(product_id.num_lines()?, return_mode)
     }
-}
 
-impl<S16, S32, D16, D32> AzimuthRange for FormatKind<S16, S32, D16, D32>
-where
-    S16: AzimuthRange,
-    S32: AzimuthRange,
-    D16: AzimuthRange,
-    D32: AzimuthRange,
-{
-    fn azimuth_range(&self) -> Range<Angle> {
-        match self {
-            FormatKind::Single16(inner) => inner.azimuth_range(),
-            FormatKind::Single32(inner) => inner.azimuth_range(),
-            FormatKind::Dual16(inner) => inner.azimuth_range(),
-            FormatKind::Dual32(inner) => inner.azimuth_range(),
-        }
+    pub fn from_model(product_id: ProductID, return_mode: ReturnMode) -> Format {
+        Self::try_from_model(product_id, return_mode).unwrap()
     }
-}
 
-impl<S16, S32, D16, D32> Iterator for FormatKind<S16, S32, D16, D32>
-where
-    S16: Iterator,
-    S32: Iterator,
-    D16: Iterator,
-    D32: Iterator,
-{
-    type Item = FormatKind<S16::Item, S32::Item, D16::Item, D32::Item>;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let item = match self {
-            FormatKind::Single16(iter) => FormatKind::from_s16(iter.next()?),
-            FormatKind::Single32(iter) => FormatKind::from_s32(iter.next()?),
-            FormatKind::Dual16(iter) => FormatKind::from_d16(iter.next()?),
-            FormatKind::Dual32(iter) => FormatKind::from_d32(iter.next()?),
-        };
-        Some(item)
+    /// Number of lasers.
+    pub fn num_lines(&self) -> usize {
+        self.layout().num_lines
+    }
+
+    /// Number of returns of each laser per firing.
+    pub fn num_returns(&self) -> usize {
+        self.layout().num_returns
+    }
+
+    /// Number of firings carried by one data packet.
+    pub fn num_firings_per_packet(&self) -> usize {
+        self.layout().num_firings_per_packet()
+    }
+
+    /// Time span covered by one data packet.
+    pub fn packet_period(&self) -> Duration {
+        FIRING_PERIOD * self.num_firings_per_packet() as u32
     }
 }
 
-impl<S16, S32, D16, D32> PointField for FormatKind<S16, S32, D16, D32>
-where
-    S16: PointField,
-    S32: PointField,
-    D16: PointField,
-    D32: PointField,
-{
-    type Point<'a>
-        = FormatKind<S16::Point<'a>, S32::Point<'a>, D16::Point<'a>, D32::Point<'a>>
+impl<F: LayoutFamily> FormatKind<F> {
+    pub fn layout(&self) -> Layout {
+        self.format().layout()
+    }
+
+    pub fn from_layout<const LINES: usize, const RETURNS: usize>(
+        inner: F::Of<LINES, RETURNS>,
+    ) -> Self
     where
-        S16: 'a,
-        S32: 'a,
-        D16: 'a,
-        D32: 'a;
-
-    fn nrows(&self) -> usize {
-        match self {
-            FormatKind::Single16(inner) => inner.nrows(),
-            FormatKind::Single32(inner) => inner.nrows(),
-            FormatKind::Dual16(inner) => inner.nrows(),
-            FormatKind::Dual32(inner) => inner.nrows(),
-        }
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        LayoutN::wrap(inner)
     }
 
-    fn ncols(&self) -> usize {
-        match self {
-            FormatKind::Single16(inner) => inner.ncols(),
-            FormatKind::Single32(inner) => inner.ncols(),
-            FormatKind::Dual16(inner) => inner.ncols(),
-            FormatKind::Dual32(inner) => inner.ncols(),
-        }
+    pub fn try_into_layout<const LINES: usize, const RETURNS: usize>(
+        self,
+    ) -> Result<F::Of<LINES, RETURNS>, Self>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        LayoutN::unwrap(self)
     }
 
-    fn point_at(&self, row: usize, col: usize) -> Option<Self::Point<'_>> {
-        let point = match self {
-            FormatKind::Single16(inner) => FormatKind::from_s16(inner.point_at(row, col)?),
-            FormatKind::Single32(inner) => FormatKind::from_s32(inner.point_at(row, col)?),
-            FormatKind::Dual16(inner) => FormatKind::from_d16(inner.point_at(row, col)?),
-            FormatKind::Dual32(inner) => FormatKind::from_d32(inner.point_at(row, col)?),
-        };
-        Some(point)
+    pub fn as_layout<const LINES: usize, const RETURNS: usize>(
+        &self,
+    ) -> Option<&F::Of<LINES, RETURNS>>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        LayoutN::get(self)
+    }
+
+    pub fn as_layout_mut<const LINES: usize, const RETURNS: usize>(
+        &mut self,
+    ) -> Option<&mut F::Of<LINES, RETURNS>>
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        LayoutN::get_mut(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_layout_test() {
+        for &format in Format::ALL {
+            assert_eq!(Format::try_from_layout(format.layout()), Some(format));
+        }
+
+        assert_eq!(Format::Single16.num_firings_per_packet(), 24);
+        assert_eq!(Format::Single32.num_firings_per_packet(), 12);
+        assert_eq!(Format::Dual16.num_firings_per_packet(), 12);
+        assert_eq!(Format::Dual32.num_firings_per_packet(), 6);
+        assert_eq!(Format::Triple16.num_firings_per_packet(), 8);
+        assert_eq!(Format::Single64.num_firings_per_packet(), 6);
+        assert_eq!(Format::Dual64.num_firings_per_packet(), 3);
+        assert_eq!(Format::Single128.num_firings_per_packet(), 3);
+        assert_eq!(Format::Triple128.num_firings_per_packet(), 1);
+        assert_eq!(
+            Format::try_new(64, ReturnMode::Strongest),
+            Some(Format::Single64)
+        );
+
+        let vls_128_dual = Layout::new(128, ReturnMode::Dual);
+        assert!(!vls_128_dual.is_packable());
+        assert_eq!(Format::try_from_layout(vls_128_dual), None);
+        assert_eq!(
+            Format::try_from_model(ProductID::VLS128, ReturnMode::Strongest),
+            Some(Format::Single128)
+        );
+        assert!(!Layout::new(24, ReturnMode::Strongest).is_packable());
     }
 }
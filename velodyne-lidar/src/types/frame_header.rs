@@ -0,0 +1,236 @@
+//! Frame metadata.
+
+use crate::{
+    batcher::Batch,
+    config::AzimuthWindow,
+    consts::FIRING_PERIOD,
+    packet::ProductID,
+    timing::TimingModel,
+    traits::{AzimuthRange, FiringLike},
+    utils::{elapsed_toh, AngleExt as _, DurationExt as _},
+};
+use measurements::Angle;
+use std::{f64::consts::PI, ops::Range, time::Duration};
+
+/// Metadata of a frame.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameHeader {
+    /// The sequence number of the frame in the stream.
+    pub seq: u64,
+    /// The sensor model reported by the source packets.
+    pub product_id: Option<ProductID>,
+    /// The caller-supplied ID of the sensor.
+    pub sensor_id: Option<u32>,
+    /// Number of packets the firings are decoded from.
+    pub num_packets: usize,
+    /// Timestamp of the first firing.
+    pub start_toh: Duration,
+    /// Timestamp of the last firing.
+    pub end_toh: Duration,
+    /// From the start azimuth of the first firing to the end azimuth of
+    /// the last firing.
+    pub azimuth_range: Range<Angle>,
+    /// The total azimuth covered by the firings, excluding gaps.
+    pub azimuth_span: Angle,
+    /// Gaps of missing firings between consecutive firings.
+    pub missing: Vec<MissingFirings>,
+    /// The azimuth sectors the frame is cropped to. Empty if the frame
+    /// is not cropped.
+    pub azimuth_windows: Vec<AzimuthWindow>,
+}
+
+/// A gap of missing firings in a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingFirings {
+    /// Number of missing firings.
+    pub num_firings: usize,
+    /// From the timestamp of the firing before the gap to that of the
+    /// firing after the gap.
+    pub toh_range: Range<Duration>,
+    /// From the end azimuth of the firing before the gap to the start
+    /// azimuth of the firing after the gap.
+    pub azimuth_range: Range<Angle>,
+}
+
+impl FrameHeader {
+    /// Builds the header from a batch of firings, including the sensor
+    /// ID of the batch.
+    pub fn from_batch<F>(batch: &Batch<F>, product_id: Option<ProductID>) -> Self
+    where
+        F: FiringLike + AzimuthRange,
+    {
+        let Batch {
+            seq,
+            num_packets,
+            sensor_id,
+            ref items,
+        } = *batch;
+        Self {
+            sensor_id,
+            ..Self::from_firings(seq, num_packets, product_id, items)
+        }
+    }
+
+    /// Builds the header from a series of consecutive firings.
+    pub fn from_firings<F>(
+        seq: u64,
+        num_packets: usize,
+        product_id: Option<ProductID>,
+        firings: &[F],
+    ) -> Self
+    where
+        F: FiringLike + AzimuthRange,
+    {
+        let (Some(first), Some(last)) = (firings.first(), firings.last()) else {
+            return Self {
+                seq,
+                product_id,
+                num_packets,
+                ..Default::default()
+            };
+        };
+
+        let azimuth_span: Angle = firings
+            .iter()
+            .map(|firing| {
+                let Range { start, end } = firing.azimuth_range();
+                end - start
+            })
+            .fold(Angle::default(), |sum, span| sum + span);
+
+        let firing_period = product_id
+            .and_then(TimingModel::from_product_id)
+            .map_or(FIRING_PERIOD, |model| model.firing_period());
+        let missing: Vec<_> = firings
+            .windows(2)
+            .filter_map(|pair| {
+                let [prev, next] = pair else { unreachable!() };
+                let prev_toh = prev.start_toh();
+                let next_toh = next.start_toh();
+
+                let elapsed = elapsed_toh(prev_toh, next_toh);
+                let num_periods = elapsed.div_duration(firing_period).round() as usize;
+                let num_firings = num_periods.checked_sub(1).filter(|&num| num > 0)?;
+
+                Some(MissingFirings {
+                    num_firings,
+                    toh_range: prev_toh..next_toh,
+                    azimuth_range: prev.end_azimuth()..next.start_azimuth(),
+                })
+            })
+            .collect();
+
+        Self {
+            seq,
+            product_id,
+            num_packets,
+            start_toh: first.start_toh(),
+            end_toh: last.start_toh(),
+            azimuth_range: first.start_azimuth()..last.end_azimuth(),
+            azimuth_span,
+            missing,
+            azimuth_windows: vec![],
+            sensor_id: None,
+        }
+    }
+
+    /// Marks the frame as cropped to the azimuth sectors.
+    ///
+    /// Gaps lying outside the sectors are cropped out rather than
+    /// missing, and are removed from `missing`.
+    pub fn with_azimuth_windows(mut self, windows: &[AzimuthWindow]) -> Self {
+        if windows.is_empty() {
+            return self;
+        }
+
+        self.missing.retain(|gap| {
+            let Range { start, end } = gap.azimuth_range;
+            let middle = start + (end - start).wrap_to_2pi() * 0.5;
+            windows.iter().any(|window| window.contains(middle))
+        });
+        self.azimuth_windows = windows.to_vec();
+        self
+    }
+
+    /// The azimuth the frame is expected to cover, which is the total
+    /// span of the azimuth windows or a full revolution.
+    pub fn fov_span(&self) -> Angle {
+        if self.azimuth_windows.is_empty() {
+            Angle::from_radians(PI * 2.0)
+        } else {
+            self.azimuth_windows
+                .iter()
+                .fold(Angle::default(), |sum, window| sum + window.span)
+        }
+    }
+
+    /// Total number of missing firings.
+    pub fn num_missing_firings(&self) -> usize {
+        self.missing.iter().map(|gap| gap.num_firings).sum()
+    }
+
+    /// The ratio of the covered azimuth to [Self::fov_span].
+    pub fn coverage(&self) -> f64 {
+        self.azimuth_span.as_radians() / self.fov_span().as_radians()
+    }
+
+    /// Returns true if the frame covers [Self::fov_span] within one
+    /// degree and no firing is missing.
+    ///
+    /// Partial frames at the start or the end of a stream are not
+    /// complete.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+            && self.azimuth_span.as_degrees() >= self.fov_span().as_degrees() - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{packet::Channel, types::firing_raw::FiringRawN};
+
+    #[test]
+    fn frame_header_missing_firings_test() {
+        let firing = |index: u32| {
+            let start = Angle::from_degrees(index as f64 * 0.2);
+            let end = Angle::from_degrees((index + 1) as f64 * 0.2);
+
+            FiringRawN::<16, 1> {
+                toh: FIRING_PERIOD * index,
+                azimuth_range: start..end,
+                channels: [[Channel {
+                    distance: 0,
+                    intensity: 0,
+                }; 16]],
+            }
+        };
+        let firings: Vec<_> = [0, 1, 2, 5, 6, 8].into_iter().map(firing).collect();
+        let header = FrameHeader::from_firings(3, 1, None, &firings);
+
+        assert_eq!(header.seq, 3);
+        assert_eq!(header.start_toh, Duration::ZERO);
+        assert_eq!(header.end_toh, FIRING_PERIOD * 8);
+        assert_eq!(header.num_missing_firings(), 3);
+        assert_eq!(
+            header
+                .missing
+                .iter()
+                .map(|gap| gap.num_firings)
+                .collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert!((header.azimuth_span.as_degrees() - 1.2).abs() < 1e-9);
+        assert!(!header.is_complete());
+
+        // firings 3 and 4 are cropped out, but firing 7 is missing
+        let windows = [
+            AzimuthWindow::new(Angle::default(), Angle::from_degrees(0.6)),
+            AzimuthWindow::new(Angle::from_degrees(1.0), Angle::from_degrees(1.8)),
+        ];
+        let header = header.with_azimuth_windows(&windows);
+        assert_eq!(header.num_missing_firings(), 1);
+        assert!((header.fov_span().as_degrees() - 1.4).abs() < 1e-9);
+        assert!(!header.is_complete());
+    }
+}
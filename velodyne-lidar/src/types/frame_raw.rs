@@ -1,201 +1,254 @@
 //! Frames with raw sensor values.
 
-use super::{
-    channel_array::ChannelArrayD,
-    firing_raw::{FiringRaw, FiringRawRef},
-};
 use crate::{
+    converter::ConverterN,
     packet::Channel,
     traits::{BoxIterator, PointField},
     types::{
-        channel::{ChannelD, ChannelKind, ChannelRefD},
-        firing_raw::{FiringRawD16, FiringRawD32, FiringRawS16, FiringRawS32},
-        format::FormatKind,
+        channel::ChannelKind,
+        firing_raw::{FiringRaw, FiringRawN, FiringRawRef},
+        format::{dispatch, FormatKind, LayoutFamily, LayoutN, SupportedLayout},
+        frame_header::FrameHeader,
+        frame_xyz::{FrameXyz, FrameXyzN},
     },
+    Config, ConfigN,
 };
-use itertools::izip;
+use eyre::{format_err, Result};
+use std::array;
+
+/// Selects [FrameRawN] in a [FormatKind].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrameRawFamily;
 
-pub type FrameRaw = FormatKind<FrameRawS16, FrameRawS32, FrameRawD16, FrameRawD32>;
+impl LayoutFamily for FrameRawFamily {
+    type Of<const LINES: usize, const RETURNS: usize> = FrameRawN<LINES, RETURNS>;
+}
+
+pub type FrameRaw = FormatKind<FrameRawFamily>;
 
 impl FrameRaw {
-    pub fn firing_iter(&self) -> impl Iterator<Item = FiringRawRef<'_>> + Clone + Sync + Send {
-        match self {
-            FrameRaw::Single16(me) => FormatKind::from_s16(me.firings.iter()),
-            FrameRaw::Single32(me) => FormatKind::from_s32(me.firings.iter()),
-            FrameRaw::Dual16(me) => FormatKind::from_d16(me.firings.iter()),
-            FrameRaw::Dual32(me) => FormatKind::from_d32(me.firings.iter()),
-        }
+    pub fn header(&self) -> &FrameHeader {
+        dispatch!(self, me => &me.header)
     }
 
-    pub fn into_firing_iter(self) -> impl Iterator<Item = FiringRaw> + Clone + Sync + Send {
-        match self {
-            FrameRaw::Single16(me) => FormatKind::from_s16(me.firings.into_iter()),
-            FrameRaw::Single32(me) => FormatKind::from_s32(me.firings.into_iter()),
-            FrameRaw::Dual16(me) => FormatKind::from_d16(me.firings.into_iter()),
-            FrameRaw::Dual32(me) => FormatKind::from_d32(me.firings.into_iter()),
-        }
+    /// Projects the raw channels to points using the calibration.
+    ///
+    /// It fails if the format of the configuration does not match that
+    /// of the frame.
+    pub fn to_frame_xyz(&self, config: &Config) -> Result<FrameXyz> {
+        dispatch!(self, <LINES, RETURNS> frame => {
+            let config = config.clone().try_into_layout::<LINES, RETURNS>().map_err(|config| {
+                format_err!(
+                    "format mismatch: the frame has format {:?}, but the configuration has format {:?}",
+                    self.format(),
+                    config.try_format()
+                )
+            })?;
+            Ok(frame.to_frame_xyz(&config).into())
+        })
+    }
+
+    pub fn firing_iter(&self) -> BoxIterator<'_, FiringRawRef<'_>> {
+        dispatch!(self, me => Box::new(me.firings.iter().map(FiringRawRef::from)))
+    }
+
+    pub fn into_firing_iter(self) -> BoxIterator<'static, FiringRaw> {
+        dispatch!(self, me => Box::new(me.firings.into_iter().map(FiringRaw::from)))
     }
 
     pub fn into_channel_iter(self) -> BoxIterator<'static, ChannelKind> {
-        match self {
-            Self::Single16(frame) => Box::new(frame.into_channel_iter().map(ChannelKind::from)),
-            Self::Single32(frame) => Box::new(frame.into_channel_iter().map(ChannelKind::from)),
-            Self::Dual16(frame) => Box::new(frame.into_channel_iter().map(ChannelKind::from)),
-            Self::Dual32(frame) => Box::new(frame.into_channel_iter().map(ChannelKind::from)),
-        }
+        dispatch!(self, frame => Box::new(frame.into_channel_iter().map(ChannelKind::from)))
     }
 
     pub fn into_indexed_channel_iter(self) -> BoxIterator<'static, ((usize, usize), ChannelKind)> {
-        match self {
-            Self::Single16(frame) => Box::new(
-                frame
-                    .into_indexed_channel_iter()
-                    .map(|(index, point)| (index, ChannelKind::from(point))),
-            ),
-            Self::Single32(frame) => Box::new(
-                frame
-                    .into_indexed_channel_iter()
-                    .map(|(index, point)| (index, ChannelKind::from(point))),
-            ),
-            Self::Dual16(frame) => Box::new(
-                frame
-                    .into_indexed_channel_iter()
-                    .map(|(index, point)| (index, ChannelKind::from(point))),
-            ),
-            Self::Dual32(frame) => Box::new(
-                frame
-                    .into_indexed_channel_iter()
-                    .map(|(index, point)| (index, ChannelKind::from(point))),
-            ),
-        }
+        dispatch!(self, frame => Box::new(
+            frame
+                .into_indexed_channel_iter()
+                .map(|(index, channels)| (index, ChannelKind::from(channels))),
+        ))
     }
 }
 
-impl From<FrameRawD16> for FrameRaw {
-    fn from(v: FrameRawD16) -> Self {
-        Self::Dual16(v)
+impl<const LINES: usize, const RETURNS: usize> From<FrameRawN<LINES, RETURNS>> for FrameRaw
+where
+    LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+{
+    fn from(v: FrameRawN<LINES, RETURNS>) -> Self {
+        Self::from_layout(v)
     }
 }
 
-impl From<FrameRawD32> for FrameRaw {
-    fn from(v: FrameRawD32) -> Self {
-        Self::Dual32(v)
-    }
+/// A frame of raw firings of `LINES` lasers with `RETURNS` returns
+/// each.
+#[derive(Debug, Clone)]
+pub struct FrameRawN<const LINES: usize, const RETURNS: usize> {
+    pub header: FrameHeader,
+    pub firings: Vec<FiringRawN<LINES, RETURNS>>,
 }
 
-impl From<FrameRawS32> for FrameRaw {
-    fn from(v: FrameRawS32) -> Self {
-        Self::Single32(v)
-    }
-}
+impl<const LINES: usize, const RETURNS: usize> PointField for FrameRawN<LINES, RETURNS> {
+    type Point<'a> = [&'a Channel; RETURNS];
 
-impl From<FrameRawS16> for FrameRaw {
-    fn from(v: FrameRawS16) -> Self {
-        Self::Single16(v)
+    fn nrows(&self) -> usize {
+        LINES
     }
-}
 
-macro_rules! declare_type_single {
-    ($name:ident, $firing:ident, $nrows:expr) => {
-        #[derive(Debug, Clone)]
-        pub struct $name {
-            pub firings: Vec<$firing>,
-        }
+    fn ncols(&self) -> usize {
+        self.firings.len()
+    }
 
-        impl PointField for $name {
-            type Point<'a> = &'a Channel;
+    fn point_at(&self, row: usize, col: usize) -> Option<Self::Point<'_>> {
+        let firing = self.firings.get(col)?;
+        (row < LINES).then(|| array::from_fn(|return_index| firing.channel(return_index, row)))
+    }
+}
 
-            fn nrows(&self) -> usize {
-                $nrows
-            }
+impl<const LINES: usize, const RETURNS: usize> FrameRawN<LINES, RETURNS> {
+    /// Iterates over the channels of every laser in every return.
+    pub fn into_channel_iter(
+        self,
+    ) -> impl Iterator<Item = [Channel; RETURNS]> + Clone + Sync + Send {
+        self.firings.into_iter().flat_map(|firing| {
+            (0..LINES).map(move |laser_id| {
+                array::from_fn(|return_index| *firing.channel(return_index, laser_id))
+            })
+        })
+    }
 
-            fn ncols(&self) -> usize {
-                self.firings.len()
-            }
+    pub fn into_indexed_channel_iter(
+        self,
+    ) -> impl Iterator<Item = ((usize, usize), [Channel; RETURNS])> + Clone + Sync + Send {
+        self.firings
+            .into_iter()
+            .enumerate()
+            .flat_map(|(col, firing)| {
+                (0..LINES).map(move |row| {
+                    let channels =
+                        array::from_fn(|return_index| *firing.channel(return_index, row));
+                    ((row, col), channels)
+                })
+            })
+    }
 
-            fn point_at(&self, row: usize, col: usize) -> Option<Self::Point<'_>> {
-                self.firings.get(col)?.channels.get(row)
-            }
+    /// Projects the raw channels to points using the calibration.
+    ///
+    /// Firings outside the azimuth windows of the configuration are
+    /// cropped like in the point frame iterators, and the header is
+    /// rebuilt from the remaining firings, except that `num_packets`
+    /// still counts the packets of the raw frame. The sensor ID of the
+    /// configuration is used if the header does not have one.
+    pub fn to_frame_xyz(&self, config: &ConfigN<LINES>) -> FrameXyzN<LINES, RETURNS> {
+        let converter = ConverterN::new(config);
+        let firings: Vec<_> = self
+            .firings
+            .iter()
+            .filter(|firing| converter.prepared.overlaps_fov(&firing.azimuth_range))
+            .map(|firing| converter.firing_raw_to_xyz(firing))
+            .collect();
+
+        let FrameHeader {
+            seq,
+            num_packets,
+            product_id,
+            sensor_id,
+            ..
+        } = self.header;
+        let header = FrameHeader {
+            sensor_id: sensor_id.or(config.sensor_id),
+            ..FrameHeader::from_firings(seq, num_packets, product_id, &firings)
         }
+        .with_azimuth_windows(&config.azimuth_windows);
 
-        impl $name {
-            pub fn into_channel_iter(self) -> impl Iterator<Item = Channel> + Clone + Sync + Send {
-                self.firings.into_iter().flat_map(|firing| firing.channels)
-            }
-
-            pub fn into_indexed_channel_iter(
-                self,
-            ) -> impl Iterator<Item = ((usize, usize), Channel)> + Clone + Sync + Send {
-                self.firings
-                    .into_iter()
-                    .enumerate()
-                    .flat_map(|(col, firing)| {
-                        firing
-                            .channels
-                            .into_iter()
-                            .enumerate()
-                            .map(move |(row, point)| ((row, col), point))
-                    })
-            }
-        }
-    };
+        FrameXyzN { header, firings }
+    }
 }
 
-macro_rules! declare_type_dual {
-    ($name:ident, $firing:ident, $nrows:expr) => {
-        #[derive(Debug, Clone)]
-        pub struct $name {
-            pub firings: Vec<$firing>,
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::AzimuthWindow,
+        iter::{data_packet_to_frame_raw, data_packet_to_frame_xyz},
+        packet::ProductID,
+        test_utils::PacketBuilder,
+        types::{format::Format, frame_header::FrameHeader},
+        Config, ReturnMode,
+    };
+    use measurements::Angle;
+
+    #[test]
+    fn frame_raw_to_frame_xyz_test() {
+        let builder =
+            PacketBuilder::new(ProductID::VLP32C, ReturnMode::Strongest).with_azimuth(0, 1000);
+        let packets: Vec<_> = (0..8u32)
+            .map(|index| {
+                let mut packet = builder.packet(index);
+
+                for (nth, block) in packet.blocks.iter_mut().enumerate() {
+                    let count = index as usize * 12 + nth;
+
+                    for (row, channel) in block.channels.iter_mut().enumerate() {
+                        channel.distance = (count * 32 + row) as u16;
+                        channel.intensity = row as u8;
+                    }
+                }
+
+                packet
+            })
+            .collect();
+
+        let config = Config::new_vlp_32c_strongest();
+        let expect: Vec<_> = data_packet_to_frame_xyz(config.clone(), packets.clone())
+            .unwrap()
+            .collect();
+        let frames: Vec<_> = data_packet_to_frame_raw(Format::Single32, packets.clone())
+            .map(|frame| frame.to_frame_xyz(&config).unwrap())
+            .collect();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames.len(), expect.len());
+
+        for (frame, expect) in frames.iter().zip(&expect) {
+            assert_eq!(frame.header(), expect.header());
+            assert!(frame
+                .firing_iter()
+                .zip(expect.firing_iter())
+                .all(|(lhs, rhs)| lhs == rhs));
         }
 
-        impl PointField for $name {
-            type Point<'a> = ChannelRefD<'a>;
-
-            fn nrows(&self) -> usize {
-                $nrows
-            }
-
-            fn ncols(&self) -> usize {
-                self.firings.len()
-            }
-
-            fn point_at(&self, row: usize, col: usize) -> Option<Self::Point<'_>> {
-                let firing = self.firings.get(col)?;
-                let strongest = firing.channels.strongest.get(row)?;
-                let last = firing.channels.last.get(row)?;
-                Some(ChannelRefD { strongest, last })
-            }
+        // the windows of the configuration crop the projected frames
+        let window = AzimuthWindow::new(Angle::from_degrees(90.0), Angle::from_degrees(200.0));
+        let config = Config::new_vlp_32c_strongest().with_azimuth_windows(vec![window]);
+        let expect: Vec<_> = data_packet_to_frame_xyz(config.clone(), packets.clone())
+            .unwrap()
+            .collect();
+        let frames: Vec<_> = data_packet_to_frame_raw(Format::Single32, packets.clone())
+            .map(|frame| frame.to_frame_xyz(&config).unwrap())
+            .collect();
+
+        assert_eq!(frames.len(), expect.len());
+        for (frame, expect) in frames.iter().zip(&expect) {
+            // packets without firings in the window are counted too
+            let header = frame.header();
+            let num_packets = expect.header().num_packets;
+            assert!(header.num_packets >= num_packets);
+            assert_eq!(
+                &FrameHeader {
+                    num_packets,
+                    ..header.clone()
+                },
+                expect.header()
+            );
+            assert_eq!(header.azimuth_windows, [window]);
+            // firings crossing the window edges are kept whole
+            assert!(header.coverage() >= 1.0);
+            assert!(frame
+                .firing_iter()
+                .zip(expect.firing_iter())
+                .all(|(lhs, rhs)| lhs == rhs));
         }
 
-        impl $name {
-            pub fn into_channel_iter(self) -> impl Iterator<Item = ChannelD> + Clone + Sync + Send {
-                self.firings.into_iter().flat_map(|firing| {
-                    let ChannelArrayD { strongest, last } = firing.channels;
-                    izip!(strongest, last).map(|(strongest, last)| ChannelD { strongest, last })
-                })
-            }
-
-            pub fn into_indexed_channel_iter(
-                self,
-            ) -> impl Iterator<Item = ((usize, usize), ChannelD)> + Clone + Sync + Send {
-                self.firings
-                    .into_iter()
-                    .enumerate()
-                    .flat_map(|(col, firing)| {
-                        let ChannelArrayD { strongest, last } = firing.channels;
-                        izip!(strongest, last)
-                            .enumerate()
-                            .map(move |(row, (strongest, last))| {
-                                let pair = ChannelD { strongest, last };
-                                ((row, col), pair)
-                            })
-                    })
-            }
-        }
-    };
+        let config = Config::new_vlp_16_strongest();
+        let frame = data_packet_to_frame_raw(Format::Single32, packets).next();
+        assert!(frame.unwrap().to_frame_xyz(&config).is_err());
+    }
 }
-
-declare_type_single!(FrameRawS16, FiringRawS16, 16);
-declare_type_single!(FrameRawS32, FiringRawS32, 32);
-declare_type_dual!(FrameRawD16, FiringRawD16, 16);
-declare_type_dual!(FrameRawD32, FiringRawD32, 32);
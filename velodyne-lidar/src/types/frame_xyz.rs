@@ -2,93 +2,69 @@
 
 pub use frame_kind::*;
 mod frame_kind {
-    use super::{FrameXyzD16, FrameXyzD32, FrameXyzS16, FrameXyzS32};
+    use super::FrameXyzN;
     use crate::{
         traits::BoxIterator,
         types::{
             firing_xyz::{FiringXyz, FiringXyzRef},
-            format::FormatKind,
+            format::{dispatch, FormatKind, LayoutFamily, LayoutN, SupportedLayout},
+            frame_header::FrameHeader,
             point::Point,
         },
     };
 
-    pub type FrameXyz = FormatKind<FrameXyzS16, FrameXyzS32, FrameXyzD16, FrameXyzD32>;
+    /// Selects [FrameXyzN] in a [FormatKind].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FrameXyzFamily;
+
+    impl LayoutFamily for FrameXyzFamily {
+        type Of<const LINES: usize, const RETURNS: usize> = FrameXyzN<LINES, RETURNS>;
+    }
+
+    pub type FrameXyz = FormatKind<FrameXyzFamily>;
 
     impl FrameXyz {
-        pub fn firing_iter(&self) -> impl Iterator<Item = FiringXyzRef<'_>> + Clone + Sync + Send {
-            match self {
-                FrameXyz::Single16(me) => FormatKind::from_s16(me.firings.iter()),
-                FrameXyz::Single32(me) => FormatKind::from_s32(me.firings.iter()),
-                FrameXyz::Dual16(me) => FormatKind::from_d16(me.firings.iter()),
-                FrameXyz::Dual32(me) => FormatKind::from_d32(me.firings.iter()),
-            }
+        pub fn header(&self) -> &FrameHeader {
+            dispatch!(self, me => &me.header)
         }
 
-        pub fn into_firing_iter(self) -> impl Iterator<Item = FiringXyz> + Clone + Sync + Send {
-            match self {
-                FrameXyz::Single16(me) => FormatKind::from_s16(me.firings.into_iter()),
-                FrameXyz::Single32(me) => FormatKind::from_s32(me.firings.into_iter()),
-                FrameXyz::Dual16(me) => FormatKind::from_d16(me.firings.into_iter()),
-                FrameXyz::Dual32(me) => FormatKind::from_d32(me.firings.into_iter()),
-            }
+        /// Gets the ring of the lasers on the row.
+        pub fn ring_of_row(&self, row: usize) -> Option<usize> {
+            dispatch!(self, me => me.ring_of_row(row))
         }
 
-        pub fn into_point_iter(self) -> BoxIterator<'static, Point> {
-            match self {
-                Self::Single16(frame) => Box::new(frame.into_point_iter().map(Point::from)),
-                Self::Single32(frame) => Box::new(frame.into_point_iter().map(Point::from)),
-                Self::Dual16(frame) => Box::new(frame.into_point_iter().map(Point::from)),
-                Self::Dual32(frame) => Box::new(frame.into_point_iter().map(Point::from)),
-            }
+        /// Gets the row of the lasers on the ring.
+        pub fn row_of_ring(&self, ring: usize) -> Option<usize> {
+            dispatch!(self, me => me.row_of_ring(ring))
         }
 
-        pub fn into_indexed_point_iter(self) -> BoxIterator<'static, ((usize, usize), Point)> {
-            match self {
-                Self::Single16(frame) => Box::new(
-                    frame
-                        .into_indexed_point_iter()
-                        .map(|(index, point)| (index, Point::from(point))),
-                ),
-                Self::Single32(frame) => Box::new(
-                    frame
-                        .into_indexed_point_iter()
-                        .map(|(index, point)| (index, Point::from(point))),
-                ),
-                Self::Dual16(frame) => Box::new(
-                    frame
-                        .into_indexed_point_iter()
-                        .map(|(index, point)| (index, Point::from(point))),
-                ),
-                Self::Dual32(frame) => Box::new(
-                    frame
-                        .into_indexed_point_iter()
-                        .map(|(index, point)| (index, Point::from(point))),
-                ),
-            }
+        pub fn firing_iter(&self) -> BoxIterator<'_, FiringXyzRef<'_>> {
+            dispatch!(self, me => Box::new(me.firings.iter().map(FiringXyzRef::from)))
         }
-    }
 
-    impl From<FrameXyzD16> for FrameXyz {
-        fn from(v: FrameXyzD16) -> Self {
-            Self::Dual16(v)
+        pub fn into_firing_iter(self) -> BoxIterator<'static, FiringXyz> {
+            dispatch!(self, me => Box::new(me.firings.into_iter().map(FiringXyz::from)))
         }
-    }
 
-    impl From<FrameXyzD32> for FrameXyz {
-        fn from(v: FrameXyzD32) -> Self {
-            Self::Dual32(v)
+        pub fn into_point_iter(self) -> BoxIterator<'static, Point> {
+            dispatch!(self, frame => Box::new(frame.into_point_iter().map(Point::from)))
         }
-    }
 
-    impl From<FrameXyzS32> for FrameXyz {
-        fn from(v: FrameXyzS32) -> Self {
-            Self::Single32(v)
+        pub fn into_indexed_point_iter(self) -> BoxIterator<'static, ((usize, usize), Point)> {
+            dispatch!(self, frame => Box::new(
+                frame
+                    .into_indexed_point_iter()
+                    .map(|(index, point)| (index, Point::from(point))),
+            ))
         }
     }
 
-    impl From<FrameXyzS16> for FrameXyz {
-        fn from(v: FrameXyzS16) -> Self {
-            Self::Single16(v)
+    impl<const LINES: usize, const RETURNS: usize> From<FrameXyzN<LINES, RETURNS>> for FrameXyz
+    where
+        LayoutN<LINES, RETURNS>: SupportedLayout<LINES, RETURNS>,
+    {
+        fn from(v: FrameXyzN<LINES, RETURNS>) -> Self {
+            Self::from_layout(v)
         }
     }
 }
@@ -97,60 +73,71 @@ pub use frame_types::*;
 mod frame_types {
     use crate::{
         traits::PointField,
-        types::{
-            firing_xyz::{FiringXyzD16, FiringXyzD32, FiringXyzS16, FiringXyzS32},
-            point::{PointD, PointS},
-        },
+        types::{firing_xyz::FiringXyzN, frame_header::FrameHeader, point::PointN},
     };
 
-    macro_rules! declare_type {
-        ($name:ident, $firing:ident, $nrows:expr, $point:ident) => {
-            #[derive(Debug, Clone)]
-            pub struct $name {
-                pub firings: Vec<$firing>,
-            }
-
-            impl PointField for $name {
-                type Point<'a> = &'a $point;
-
-                fn nrows(&self) -> usize {
-                    $nrows
-                }
-
-                fn ncols(&self) -> usize {
-                    self.firings.len()
-                }
-
-                fn point_at(&self, row: usize, col: usize) -> Option<Self::Point<'_>> {
-                    self.firings.get(col)?.points.get(row)
-                }
-            }
-
-            impl $name {
-                pub fn into_point_iter(self) -> impl Iterator<Item = $point> + Clone + Sync + Send {
-                    self.firings.into_iter().flat_map(|firing| firing.points)
-                }
-
-                pub fn into_indexed_point_iter(
-                    self,
-                ) -> impl Iterator<Item = ((usize, usize), $point)> + Clone + Sync + Send {
-                    self.firings
+    /// A frame of firings of `LINES` lasers with `RETURNS` returns each.
+    #[derive(Debug, Clone)]
+    pub struct FrameXyzN<const LINES: usize, const RETURNS: usize> {
+        pub header: FrameHeader,
+        pub firings: Vec<FiringXyzN<LINES, RETURNS>>,
+    }
+
+    impl<const LINES: usize, const RETURNS: usize> PointField for FrameXyzN<LINES, RETURNS> {
+        type Point<'a> = &'a PointN<RETURNS>;
+
+        fn nrows(&self) -> usize {
+            LINES
+        }
+
+        fn ncols(&self) -> usize {
+            self.firings.len()
+        }
+
+        fn point_at(&self, row: usize, col: usize) -> Option<Self::Point<'_>> {
+            self.firings.get(col)?.points.get(row)
+        }
+    }
+
+    impl<const LINES: usize, const RETURNS: usize> FrameXyzN<LINES, RETURNS> {
+        /// Gets the ring of the lasers on the row.
+        pub fn ring_of_row(&self, row: usize) -> Option<usize> {
+            Some(self.firings.first()?.points.get(row)?.ring)
+        }
+
+        /// Gets the row of the lasers on the ring.
+        pub fn row_of_ring(&self, ring: usize) -> Option<usize> {
+            self.firings
+                .first()?
+                .points
+                .iter()
+                .position(|point| point.ring == ring)
+        }
+
+        /// Gets the point on the ring in the column.
+        pub fn point_at_ring(&self, ring: usize, col: usize) -> Option<&PointN<RETURNS>> {
+            self.point_at(self.row_of_ring(ring)?, col)
+        }
+
+        pub fn into_point_iter(
+            self,
+        ) -> impl Iterator<Item = PointN<RETURNS>> + Clone + Sync + Send {
+            self.firings.into_iter().flat_map(|firing| firing.points)
+        }
+
+        pub fn into_indexed_point_iter(
+            self,
+        ) -> impl Iterator<Item = ((usize, usize), PointN<RETURNS>)> + Clone + Sync + Send {
+            self.firings
+                .into_iter()
+                .enumerate()
+                .flat_map(|(col, firing)| {
+                    firing
+                        .points
                         .into_iter()
                         .enumerate()
-                        .flat_map(|(col, firing)| {
-                            firing
-                                .points
-                                .into_iter()
-                                .enumerate()
-                                .map(move |(row, point)| ((row, col), point))
-                        })
-                }
-            }
-        };
+                        .map(move |(row, point)| ((row, col), point))
+                })
+        }
     }
-
-    declare_type!(FrameXyzS16, FiringXyzS16, 16, PointS);
-    declare_type!(FrameXyzS32, FiringXyzS32, 32, PointS);
-    declare_type!(FrameXyzD16, FiringXyzD16, 16, PointD);
-    declare_type!(FrameXyzD32, FiringXyzD32, 32, PointD);
 }
@@ -1,35 +1,23 @@
 use measurements::Length;
 
 /// Point in strongest or last return mode.
+///
+/// The `valid` field was added after 0.3. Patterns destructuring a
+/// measurement need to bind it or end with `..`.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Measurement {
     pub distance: Length,
     pub intensity: u8,
     pub xyz: [Length; 3],
+    /// False if the laser reported no return or the return was rejected
+    /// by the range gate.
+    pub valid: bool,
 }
 
-/// Point in strongest or last return mode.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct MeasurementDual {
-    pub strongest: Measurement,
-    pub last: Measurement,
-}
-
-/// Point in strongest or last return mode.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum MeasurementKind {
-    Single(Measurement),
-    Dual(MeasurementDual),
-}
-
-impl From<Measurement> for MeasurementKind {
-    fn from(from: Measurement) -> Self {
-        Self::Single(from)
-    }
-}
-
-impl From<MeasurementDual> for MeasurementKind {
-    fn from(from: MeasurementDual) -> Self {
-        Self::Dual(from)
+impl Measurement {
+    /// Checks if the coordinates are NaN, which marks an invalid return
+    /// in dense outputs.
+    pub fn is_nan(&self) -> bool {
+        self.xyz.iter().any(|v| v.as_meters().is_nan())
     }
 }
@@ -1,60 +1,100 @@
 //! Point types for laser measurements.
-use crate::types::measurements::{Measurement, MeasurementDual};
+use crate::types::measurements::Measurement;
 use measurements::Angle;
 use std::time::Duration;
 
+/// A point with `RETURNS` returns.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct PointS {
+pub struct PointN<const RETURNS: usize> {
     pub laser_id: usize,
+    pub ring: usize,
     pub toh: Duration,
     pub azimuth: Angle,
-    pub measurement: Measurement,
+    /// The returns in the block order, where the strongest return
+    /// precedes the last return in dual return mode.
+    pub measurements: [Measurement; RETURNS],
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct PointD {
-    pub laser_id: usize,
-    pub toh: Duration,
-    pub azimuth: Angle,
-    pub measurements: MeasurementDual,
+pub type PointS = PointN<1>;
+pub type PointD = PointN<2>;
+pub type PointT = PointN<3>;
+
+impl<const RETURNS: usize> PointN<RETURNS> {
+    /// Gets the first return, which is the strongest return in dual
+    /// return mode.
+    pub fn measurement(&self) -> &Measurement {
+        &self.measurements[0]
+    }
+
+    /// Checks if any return is valid.
+    pub fn is_valid(&self) -> bool {
+        self.measurements
+            .iter()
+            .any(|measurement| measurement.valid)
+    }
 }
 
 impl PointD {
+    /// Checks if the sensor reported the same return as both the
+    /// strongest and the last return.
+    pub fn is_duplicate(&self) -> bool {
+        let [strongest, last] = &self.measurements;
+        strongest.distance == last.distance && strongest.intensity == last.intensity
+    }
+
     pub fn measurement_strongest(&self) -> &Measurement {
-        &self.measurements.strongest
+        &self.measurements[0]
     }
 
     pub fn measurement_last(&self) -> &Measurement {
-        &self.measurements.last
+        &self.measurements[1]
     }
 }
 
+/// Evaluates the expression on the point of any variant.
+macro_rules! match_returns {
+    ($point:expr, $inner:ident => $body:expr) => {
+        match $point {
+            Self::Single($inner) => $body,
+            Self::Dual($inner) => $body,
+            Self::Triple($inner) => $body,
+        }
+    };
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Point {
     Single(PointS),
     Dual(PointD),
+    Triple(PointT),
 }
 
+// The errors hand back the input rather than describing a failure.
+#[allow(clippy::result_large_err)]
 impl Point {
     pub fn laser_id(&self) -> usize {
-        match self {
-            Self::Single(point) => point.laser_id,
-            Self::Dual(point) => point.laser_id,
-        }
+        match_returns!(self, point => point.laser_id)
+    }
+
+    pub fn ring(&self) -> usize {
+        match_returns!(self, point => point.ring)
     }
 
     pub fn time(&self) -> Duration {
-        match self {
-            Self::Single(point) => point.toh,
-            Self::Dual(point) => point.toh,
-        }
+        match_returns!(self, point => point.toh)
     }
 
     pub fn azimuth(&self) -> Angle {
-        match self {
-            Self::Single(point) => point.azimuth,
-            Self::Dual(point) => point.azimuth,
-        }
+        match_returns!(self, point => point.azimuth)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match_returns!(self, point => point.is_valid())
+    }
+
+    /// Gets the returns of the point.
+    pub fn measurements(&self) -> &[Measurement] {
+        match_returns!(self, point => &point.measurements)
     }
 
     pub fn try_into_single(self) -> Result<PointS, Self> {
@@ -73,6 +113,14 @@ impl Point {
         }
     }
 
+    pub fn try_into_triple(self) -> Result<PointT, Self> {
+        if let Self::Triple(v) = self {
+            Ok(v)
+        } else {
+            Err(self)
+        }
+    }
+
     pub fn as_single(&self) -> Option<&PointS> {
         if let Self::Single(v) = self {
             Some(v)
@@ -88,6 +136,20 @@ impl Point {
             None
         }
     }
+
+    pub fn as_triple(&self) -> Option<&PointT> {
+        if let Self::Triple(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<PointS> for Point {
+    fn from(v: PointS) -> Self {
+        Self::Single(v)
+    }
 }
 
 impl From<PointD> for Point {
@@ -96,9 +158,9 @@ impl From<PointD> for Point {
     }
 }
 
-impl From<PointS> for Point {
-    fn from(v: PointS) -> Self {
-        Self::Single(v)
+impl From<PointT> for Point {
+    fn from(v: PointT) -> Self {
+        Self::Triple(v)
     }
 }
 
@@ -106,28 +168,40 @@ impl From<PointS> for Point {
 pub enum PointRef<'a> {
     Single(&'a PointS),
     Dual(&'a PointD),
+    Triple(&'a PointT),
 }
 
 impl<'a> PointRef<'a> {
     pub fn laser_id(&self) -> usize {
-        match self {
-            Self::Single(point) => point.laser_id,
-            Self::Dual(point) => point.laser_id,
-        }
+        match_returns!(self, point => point.laser_id)
+    }
+
+    pub fn ring(&self) -> usize {
+        match_returns!(self, point => point.ring)
     }
 
     pub fn time(&self) -> Duration {
-        match self {
-            Self::Single(point) => point.toh,
-            Self::Dual(point) => point.toh,
-        }
+        match_returns!(self, point => point.toh)
     }
 
     pub fn azimuth(&self) -> Angle {
-        match self {
-            Self::Single(point) => point.azimuth,
-            Self::Dual(point) => point.azimuth,
-        }
+        match_returns!(self, point => point.azimuth)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match_returns!(self, point => point.is_valid())
+    }
+
+    /// Gets the returns of the point.
+    pub fn measurements(&self) -> &'a [Measurement] {
+        match_returns!(*self, point => &point.measurements)
+    }
+
+    /// Gets the measurement of single return points, or the first
+    /// return of multiple return points, which is the strongest return
+    /// in dual return mode.
+    pub fn measurement_strongest(&self) -> &'a Measurement {
+        match_returns!(*self, point => point.measurement())
     }
 
     pub fn as_single(&self) -> Option<&'a PointS> {
@@ -145,6 +219,20 @@ impl<'a> PointRef<'a> {
             None
         }
     }
+
+    pub fn as_triple(&self) -> Option<&'a PointT> {
+        if let Self::Triple(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> From<&'a PointS> for PointRef<'a> {
+    fn from(v: &'a PointS) -> Self {
+        Self::Single(v)
+    }
 }
 
 impl<'a> From<&'a PointD> for PointRef<'a> {
@@ -153,8 +241,8 @@ impl<'a> From<&'a PointD> for PointRef<'a> {
     }
 }
 
-impl<'a> From<&'a PointS> for PointRef<'a> {
-    fn from(v: &'a PointS) -> Self {
-        Self::Single(v)
+impl<'a> From<&'a PointT> for PointRef<'a> {
+    fn from(v: &'a PointT) -> Self {
+        Self::Triple(v)
     }
 }
@@ -0,0 +1,112 @@
+//! Compact point clouds.
+
+use crate::{
+    types::{
+        format::dispatch,
+        frame_header::FrameHeader,
+        frame_xyz::{FrameXyz, FrameXyzN},
+        measurements::Measurement,
+        point::PointN,
+    },
+    utils::elapsed_toh,
+};
+use std::time::Duration;
+
+/// A compact point cloud stored as a structure of arrays.
+///
+/// Coordinates are in meters and times are in seconds since the
+/// `start_toh` of the header. All arrays have the same length.
+///
+/// For multiple return frames, the first returns are used, which are
+/// the strongest returns in dual return mode.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PointCloud {
+    pub header: FrameHeader,
+    pub x: Vec<f32>,
+    pub y: Vec<f32>,
+    pub z: Vec<f32>,
+    pub intensity: Vec<u8>,
+    pub ring: Vec<u16>,
+    pub time: Vec<f32>,
+}
+
+impl PointCloud {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty cloud that holds `capacity` points without
+    /// reallocation.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            header: FrameHeader::default(),
+            x: Vec::with_capacity(capacity),
+            y: Vec::with_capacity(capacity),
+            z: Vec::with_capacity(capacity),
+            intensity: Vec::with_capacity(capacity),
+            ring: Vec::with_capacity(capacity),
+            time: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Removes all points and resets the header, keeping the allocated
+    /// storage.
+    pub fn clear(&mut self) {
+        self.header = FrameHeader::default();
+        self.x.clear();
+        self.y.clear();
+        self.z.clear();
+        self.intensity.clear();
+        self.ring.clear();
+        self.time.clear();
+    }
+
+    /// Appends a point. Its time is measured from the `start_toh` of
+    /// the header.
+    pub fn push_point<const RETURNS: usize>(&mut self, point: &PointN<RETURNS>) {
+        self.push(point.ring, point.toh, point.measurement());
+    }
+
+    fn push(&mut self, ring: usize, toh: Duration, measurement: &Measurement) {
+        let Measurement { intensity, xyz, .. } = *measurement;
+        let [x, y, z] = xyz.map(|v| v.as_meters() as f32);
+        let start_toh = self.header.start_toh;
+        let elapsed = elapsed_toh(start_toh, toh);
+
+        self.x.push(x);
+        self.y.push(y);
+        self.z.push(z);
+        self.intensity.push(intensity);
+        self.ring.push(ring as u16);
+        self.time.push(elapsed.as_secs_f32());
+    }
+}
+
+impl From<&FrameXyz> for PointCloud {
+    fn from(frame: &FrameXyz) -> Self {
+        dispatch!(frame, frame => frame.into())
+    }
+}
+
+impl<const LINES: usize, const RETURNS: usize> From<&FrameXyzN<LINES, RETURNS>> for PointCloud {
+    fn from(frame: &FrameXyzN<LINES, RETURNS>) -> Self {
+        let mut cloud = Self::with_capacity(frame.firings.len() * LINES);
+        cloud.header = frame.header.clone();
+
+        for firing in &frame.firings {
+            for point in &firing.points {
+                cloud.push_point(point);
+            }
+        }
+
+        cloud
+    }
+}
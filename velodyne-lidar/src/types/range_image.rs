@@ -0,0 +1,237 @@
+//! Organized range images.
+
+use crate::{
+    traits::PointField,
+    types::{
+        frame_header::FrameHeader, frame_raw::FrameRaw, frame_xyz::FrameXyz,
+        measurements::Measurement,
+    },
+    utils::AngleExt as _,
+    Config,
+};
+use eyre::{ensure, Result};
+use measurements::{Angle, Length};
+use std::{f64::consts::PI, time::Duration};
+
+/// A measurement in a range image cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeCell {
+    pub laser_id: usize,
+    pub ring: usize,
+    pub range: Length,
+    pub intensity: u8,
+    pub xyz: [Length; 3],
+    pub toh: Duration,
+}
+
+/// An elevation-sorted image with fixed azimuth bins.
+///
+/// Rows are rings from the top to the bottom, and columns
+/// are equally spaced azimuth bins starting from zero azimuth in the
+/// clockwise direction. A cell is empty if no point falls into it or
+/// the return is invalid. If multiple points fall into the same
+/// cell, the nearest one is kept.
+///
+/// For dual return frames, the strongest returns are used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeImage {
+    pub header: FrameHeader,
+    ncols: usize,
+    laser_ids: Vec<usize>,
+    cells: Vec<Option<RangeCell>>,
+}
+
+impl RangeImage {
+    /// Creates an empty image for the lasers in the configuration.
+    pub fn new(config: &Config, ncols: usize) -> Self {
+        assert!(ncols > 0, "ncols must be positive");
+
+        let rings = config.rings();
+        let nrows = rings.len();
+        let mut laser_ids = vec![0; nrows];
+        for (laser_id, ring) in rings.into_iter().enumerate() {
+            laser_ids[nrows - 1 - ring] = laser_id;
+        }
+
+        Self {
+            header: FrameHeader::default(),
+            ncols,
+            cells: vec![None; laser_ids.len() * ncols],
+            laser_ids,
+        }
+    }
+
+    /// Builds the image from a frame of points.
+    pub fn from_frame_xyz(frame: &FrameXyz, config: &Config, ncols: usize) -> Result<Self> {
+        ensure!(
+            frame.format().num_lines() == config.lasers.len(),
+            "the frame has {} lasers, but the configuration has {}",
+            frame.format().num_lines(),
+            config.lasers.len()
+        );
+
+        let mut image = Self::new(config, ncols);
+        image.header = frame.header().clone();
+
+        for firing in frame.firing_iter() {
+            for point in firing.point_iter() {
+                image.insert(
+                    point.laser_id(),
+                    point.ring(),
+                    point.azimuth(),
+                    point.time(),
+                    point.measurement_strongest(),
+                );
+            }
+        }
+
+        Ok(image)
+    }
+
+    /// Builds the image from a raw frame by projecting the channels
+    /// using the calibration.
+    pub fn from_frame_raw(frame: &FrameRaw, config: &Config, ncols: usize) -> Result<Self> {
+        let frame = frame.to_frame_xyz(config)?;
+        Self::from_frame_xyz(&frame, config, ncols)
+    }
+
+    /// Gets the laser ID of a row.
+    pub fn laser_id_of_row(&self, row: usize) -> Option<usize> {
+        self.laser_ids.get(row).copied()
+    }
+
+    /// Gets the ring of a row.
+    pub fn ring_of_row(&self, row: usize) -> Option<usize> {
+        (row < self.nrows()).then(|| self.nrows() - 1 - row)
+    }
+
+    /// Gets the row of a ring.
+    pub fn row_of_ring(&self, ring: usize) -> Option<usize> {
+        (ring < self.nrows()).then(|| self.nrows() - 1 - ring)
+    }
+
+    /// Gets the azimuth at the center of a column.
+    pub fn azimuth_of_col(&self, col: usize) -> Option<Angle> {
+        (col < self.ncols).then(|| Angle::from_radians((col as f64 + 0.5) * self.col_width()))
+    }
+
+    /// Gets the column that contains the azimuth.
+    pub fn col_of_azimuth(&self, azimuth: Angle) -> usize {
+        let col = (azimuth.wrap_to_2pi().as_radians() / self.col_width()) as usize;
+        col.min(self.ncols - 1)
+    }
+
+    /// Gets the cell at the position, which is `None` if it is empty or
+    /// out of bounds.
+    pub fn get(&self, row: usize, col: usize) -> Option<&RangeCell> {
+        self.point_at(row, col)?.as_ref()
+    }
+
+    /// Number of non-empty cells.
+    pub fn num_filled(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.is_some()).count()
+    }
+
+    /// Gets the cells in row-major order.
+    pub fn cells(&self) -> &[Option<RangeCell>] {
+        &self.cells
+    }
+
+    fn col_width(&self) -> f64 {
+        PI * 2.0 / self.ncols as f64
+    }
+
+    fn insert(
+        &mut self,
+        laser_id: usize,
+        ring: usize,
+        azimuth: Angle,
+        toh: Duration,
+        measurement: &Measurement,
+    ) {
+        let Measurement {
+            distance,
+            intensity,
+            xyz,
+            valid,
+        } = *measurement;
+
+        if !valid {
+            return;
+        }
+        let Some(row) = self.row_of_ring(ring) else {
+            return;
+        };
+        let col = self.col_of_azimuth(azimuth);
+        let cell = &mut self.cells[row * self.ncols + col];
+
+        if matches!(cell, Some(prev) if prev.range <= distance) {
+            return;
+        }
+
+        *cell = Some(RangeCell {
+            laser_id,
+            ring,
+            range: distance,
+            intensity,
+            xyz,
+            toh,
+        });
+    }
+}
+
+impl PointField for RangeImage {
+    type Point<'a> = &'a Option<RangeCell>;
+
+    fn nrows(&self) -> usize {
+        self.laser_ids.len()
+    }
+
+    fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    fn point_at(&self, row: usize, col: usize) -> Option<Self::Point<'_>> {
+        if row >= self.nrows() || col >= self.ncols {
+            return None;
+        }
+        self.cells.get(row * self.ncols + col)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_image_test() {
+        let config = Config::new_vlp_16_strongest();
+        let mut image = RangeImage::new(&config, 4);
+
+        assert_eq!(image.laser_id_of_row(0), Some(15));
+        assert_eq!(image.laser_id_of_row(15), Some(0));
+        assert_eq!(image.ring_of_row(0), Some(15));
+        assert_eq!(image.col_of_azimuth(Angle::from_degrees(-10.0)), 3);
+
+        let measurement = |meters: f64| Measurement {
+            distance: Length::from_meters(meters),
+            intensity: 7,
+            xyz: [
+                Length::from_meters(meters),
+                Length::default(),
+                Length::default(),
+            ],
+            valid: meters > 0.0,
+        };
+        let azimuth = Angle::from_degrees(100.0);
+        image.insert(15, 15, azimuth, Duration::ZERO, &measurement(5.0));
+        image.insert(15, 15, azimuth, Duration::ZERO, &measurement(3.0));
+        image.insert(15, 15, azimuth, Duration::ZERO, &measurement(4.0));
+        image.insert(0, 0, azimuth, Duration::ZERO, &measurement(0.0));
+
+        assert_eq!(image.num_filled(), 1);
+        assert_eq!(image.get(0, 1).unwrap().range, Length::from_meters(3.0));
+        assert_eq!(image.point_at(15, 1), Some(&None));
+        assert_eq!(image.point_at(16, 0), None);
+    }
+}
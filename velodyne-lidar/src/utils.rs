@@ -1,17 +1,24 @@
+use core::{f64::consts::PI, time::Duration};
 use measurements::Angle;
-use std::{f64::consts::PI, time::Duration};
+
+/// Length of one hour, the period of the `toh` field.
+pub(crate) const HOUR: Duration = Duration::from_secs(3600);
 
 pub(crate) trait AngleExt {
+    #[cfg(feature = "std")]
     fn sin(self) -> f64;
+    #[cfg(feature = "std")]
     fn cos(self) -> f64;
     fn wrap_to_2pi(self) -> Self;
 }
 
 impl AngleExt for Angle {
+    #[cfg(feature = "std")]
     fn sin(self) -> f64 {
         self.as_radians().sin()
     }
 
+    #[cfg(feature = "std")]
     fn cos(self) -> f64 {
         self.as_radians().cos()
     }
@@ -27,12 +34,54 @@ impl AngleExt for Angle {
     }
 }
 
+#[cfg(feature = "std")]
 pub(crate) trait DurationExt {
     fn div_duration(self, rhs: Duration) -> f64;
 }
 
+#[cfg(feature = "std")]
 impl DurationExt for Duration {
     fn div_duration(self, rhs: Duration) -> f64 {
         self.as_secs_f64() / rhs.as_secs_f64()
     }
 }
+
+/// Computes the time elapsed from `from` to `to`, where `to` may have
+/// wrapped around the hour.
+pub(crate) fn elapsed_toh(from: Duration, to: Duration) -> Duration {
+    if to >= from {
+        to - from
+    } else {
+        to + HOUR - from
+    }
+}
+
+/// Computes the signed time from `from` to `to`, assuming they are less
+/// than half an hour apart.
+#[cfg(feature = "std")]
+pub(crate) fn signed_elapsed_toh(from: Duration, to: Duration) -> f64 {
+    let elapsed = elapsed_toh(from, to);
+    if elapsed >= HOUR / 2 {
+        -(HOUR - elapsed).as_secs_f64()
+    } else {
+        elapsed.as_secs_f64()
+    }
+}
+
+/// Places a `toh` value on a time line that does not wrap around every
+/// hour, choosing the instant nearest to `reference`.
+///
+/// Both the reference and the returned value are in microseconds.
+#[cfg(feature = "std")]
+pub(crate) fn unwrap_toh(toh: u32, reference: i64) -> i64 {
+    let hour_micros = HOUR.as_micros() as i64;
+    let from = Duration::from_micros(reference.rem_euclid(hour_micros) as u64);
+    let to = Duration::from_micros(toh as u64 % hour_micros as u64);
+    let delta = elapsed_toh(from, to).as_micros() as i64;
+    let delta = if delta > hour_micros / 2 {
+        delta - hour_micros
+    } else {
+        delta
+    };
+    reference + delta
+}
@@ -3,5 +3,7 @@ mod serialized;
 mod types;
 
 pub use error::Error;
-pub use serialized::{SerializedLaser, SerializedVelodyneParams};
-pub use types::{Laser, VelodyneParams};
+pub use serialized::{
+    SerializedLaser, SerializedRotation, SerializedTransform, SerializedVelodyneParams,
+};
+pub use types::{quaternion_to_matrix, Laser, Transform, VelodyneParams};
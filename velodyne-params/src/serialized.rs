@@ -5,6 +5,29 @@ pub struct SerializedVelodyneParams {
     pub num_lasers: usize,
     pub distance_resolution: f64,
     pub lasers: Vec<SerializedLaser>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<SerializedTransform>,
+}
+
+/// The sensor mount transform. Missing fields default to identity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializedTransform {
+    #[serde(default)]
+    pub translation: [f64; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<SerializedRotation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SerializedRotation {
+    /// A quaternion in `x, y, z, w` components.
+    Quaternion { x: f64, y: f64, z: f64, w: f64 },
+    /// Roll, pitch and yaw angles in radians, applied in the extrinsic
+    /// X-Y-Z order.
+    Rpy { roll: f64, pitch: f64, yaw: f64 },
+    /// A row-major rotation matrix.
+    Matrix([[f64; 3]; 3]),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,6 +1,8 @@
 use crate::{
     error::Error,
-    serialized::{SerializedLaser, SerializedVelodyneParams},
+    serialized::{
+        SerializedLaser, SerializedRotation, SerializedTransform, SerializedVelodyneParams,
+    },
 };
 use itertools::Itertools;
 use measurements::Length;
@@ -14,6 +16,7 @@ use serde::{Deserialize, Serialize};
 pub struct VelodyneParams {
     pub distance_resolution: Length,
     pub lasers: Vec<Laser>,
+    pub transform: Option<Transform>,
 }
 
 impl TryFrom<SerializedVelodyneParams> for VelodyneParams {
@@ -24,6 +27,7 @@ impl TryFrom<SerializedVelodyneParams> for VelodyneParams {
             num_lasers,
             distance_resolution,
             lasers,
+            transform,
         } = orig;
 
         if num_lasers != lasers.len() {
@@ -41,10 +45,12 @@ impl TryFrom<SerializedVelodyneParams> for VelodyneParams {
         }
 
         let lasers: Vec<_> = lasers.into_iter().map(Laser::try_from).try_collect()?;
+        let transform = transform.map(Transform::try_from).transpose()?;
 
         Ok(Self {
             distance_resolution: Length::from_meters(distance_resolution),
             lasers,
+            transform,
         })
     }
 }
@@ -54,6 +60,7 @@ impl From<VelodyneParams> for SerializedVelodyneParams {
         let VelodyneParams {
             distance_resolution,
             lasers,
+            transform,
         } = orig;
         let lasers: Vec<_> = lasers.into_iter().map(SerializedLaser::from).collect();
 
@@ -61,6 +68,7 @@ impl From<VelodyneParams> for SerializedVelodyneParams {
             num_lasers: lasers.len(),
             distance_resolution: distance_resolution.as_meters(),
             lasers,
+            transform: transform.map(SerializedTransform::from),
         }
     }
 }
@@ -141,3 +149,180 @@ impl From<Laser> for SerializedLaser {
         }
     }
 }
+
+/// Tolerance of the orthonormality of a rotation matrix.
+const ROTATION_TOLERANCE: f64 = 1e-6;
+
+/// A rigid transform from the sensor frame to the mount frame.
+///
+/// The sensor frame has the axes of the coordinate convention chosen
+/// for conversion, which is applied before the transform.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SerializedTransform", into = "SerializedTransform")]
+pub struct Transform {
+    /// Row-major rotation matrix.
+    pub rotation: [[f64; 3]; 3],
+    /// Translation in meters.
+    pub translation: [f64; 3],
+}
+
+impl TryFrom<SerializedTransform> for Transform {
+    type Error = Error;
+
+    fn try_from(orig: SerializedTransform) -> Result<Self, Self::Error> {
+        let SerializedTransform {
+            translation,
+            rotation,
+        } = orig;
+
+        let rotation = match rotation {
+            None => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            Some(SerializedRotation::Quaternion { x, y, z, w }) => {
+                let norm = (x * x + y * y + z * z + w * w).sqrt();
+                if !norm.is_normal() {
+                    return Err(Error::invalid_params(format!(
+                        "The rotation quaternion must be nonzero and finite, but get {:?}",
+                        [x, y, z, w]
+                    )));
+                }
+                quaternion_to_matrix([x, y, z, w])
+            }
+            Some(SerializedRotation::Rpy { roll, pitch, yaw }) => {
+                let (sr, cr) = roll.sin_cos();
+                let (sp, cp) = pitch.sin_cos();
+                let (sy, cy) = yaw.sin_cos();
+
+                [
+                    [cy * cp, cy * sp * sr - sy * cr, cy * sp * cr + sy * sr],
+                    [sy * cp, sy * sp * sr + cy * cr, sy * sp * cr - cy * sr],
+                    [-sp, cp * sr, cp * cr],
+                ]
+            }
+            Some(SerializedRotation::Matrix(matrix)) => {
+                if !is_rotation(&matrix) {
+                    return Err(Error::invalid_params(format!(
+                        "The rotation matrix must be orthonormal with determinant 1, but get {matrix:?}"
+                    )));
+                }
+                matrix
+            }
+        };
+
+        let is_finite = rotation
+            .iter()
+            .flatten()
+            .chain(&translation)
+            .all(|value| value.is_finite());
+        if !is_finite {
+            return Err(Error::invalid_params(
+                "The transform must consist of finite values",
+            ));
+        }
+
+        Ok(Self {
+            rotation,
+            translation,
+        })
+    }
+}
+
+/// Converts a unit quaternion in `[x, y, z, w]` order to a row-major
+/// rotation matrix. The quaternion is normalized.
+pub fn quaternion_to_matrix(quaternion: [f64; 4]) -> [[f64; 3]; 3] {
+    let norm = quaternion.iter().map(|v| v * v).sum::<f64>().sqrt();
+    let [x, y, z, w] = quaternion.map(|v| v / norm);
+
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ]
+}
+
+/// Checks if the matrix is orthonormal with determinant 1 within
+/// [ROTATION_TOLERANCE].
+fn is_rotation(matrix: &[[f64; 3]; 3]) -> bool {
+    let m = matrix;
+    let is_orthonormal = (0..3).all(|i| {
+        (0..3).all(|j| {
+            let dot: f64 = (0..3).map(|k| m[k][i] * m[k][j]).sum();
+            let expect = if i == j { 1.0 } else { 0.0 };
+            (dot - expect).abs() <= ROTATION_TOLERANCE
+        })
+    });
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+
+    is_orthonormal && (det - 1.0).abs() <= ROTATION_TOLERANCE
+}
+
+impl From<Transform> for SerializedTransform {
+    fn from(orig: Transform) -> Self {
+        let Transform {
+            rotation,
+            translation,
+        } = orig;
+
+        Self {
+            translation,
+            rotation: Some(SerializedRotation::Matrix(rotation)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VelodyneParams;
+
+    #[test]
+    fn transform_deserialize_test() {
+        let text = "
+num_lasers: 0
+distance_resolution: 0.004
+lasers: []
+transform:
+  translation: [1.0, 2.0, 3.0]
+  rotation: {x: 0.0, y: 0.0, z: 0.7071067811865476, w: 0.7071067811865476}
+";
+        let params: VelodyneParams = serde_yaml::from_str(text).unwrap();
+        let transform = params.transform.unwrap();
+        let expect = [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+
+        assert_eq!(transform.translation, [1.0, 2.0, 3.0]);
+        for (row, expect) in transform.rotation.iter().zip(&expect) {
+            for (value, expect) in row.iter().zip(expect) {
+                assert!((value - expect).abs() < 1e-12);
+            }
+        }
+
+        let rpy = "{rotation: {roll: 0.0, pitch: 0.0, yaw: 1.5707963267948966}}";
+        let rpy: super::Transform = serde_yaml::from_str(rpy).unwrap();
+        assert!((rpy.rotation[1][0] - 1.0).abs() < 1e-12);
+        assert_eq!(rpy.translation, [0.0; 3]);
+    }
+
+    #[test]
+    fn transform_matrix_test() {
+        let matrix = "{rotation: [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]}";
+        assert!(serde_yaml::from_str::<super::Transform>(matrix).is_ok());
+
+        let scaled = "{rotation: [[2.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]}";
+        assert!(serde_yaml::from_str::<super::Transform>(scaled).is_err());
+
+        let reflection = "{rotation: [[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]}";
+        assert!(serde_yaml::from_str::<super::Transform>(reflection).is_err());
+    }
+}
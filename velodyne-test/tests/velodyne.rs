@@ -1,6 +1,7 @@
 use eyre::{ensure, Result};
 use itertools::{izip, Itertools};
 use pcap::Capture;
+use std::iter;
 use velodyne_lidar::{config::Config, consts, DataPacket};
 
 const UDP_HEADER_SIZE: usize = 42;
@@ -37,7 +38,7 @@ fn velodyne_vlp_16_pcap_file() -> Result<()> {
     // convert to point cloud
     {
         let config = Config::new_vlp_16_strongest();
-        velodyne_lidar::iter::data_packet_to_frame_xyz(config, data_packets.into_iter())?.count();
+        velodyne_lidar::iter::data_packet_to_frame_xyz(config, data_packets)?.count();
     }
 
     Ok(())
@@ -48,7 +49,7 @@ fn velodyne_vlp_32_pcap_file() -> Result<()> {
     let mut cap = Capture::from_file("test_files/velodyne_vlp32.pcap")?;
     cap.filter("udp", true)?;
 
-    let data_packets: Vec<_> = itertools::unfold(cap, |cap| {
+    let data_packets: Vec<_> = iter::from_fn(|| {
         Some(loop {
             let packet = cap.next_packet().ok()?;
             let slice = &packet.data[UDP_HEADER_SIZE..];
@@ -84,7 +85,7 @@ fn velodyne_vlp_32_pcap_file() -> Result<()> {
     // convert to point cloud
     {
         let config = Config::new_vlp_32c_strongest();
-        velodyne_lidar::iter::data_packet_to_frame_xyz(config, data_packets.into_iter())?.count();
+        velodyne_lidar::iter::data_packet_to_frame_xyz(config, data_packets)?.count();
     }
 
     Ok(())